@@ -3,12 +3,27 @@ use proc_macro::TokenStream;
 use quote::quote;
 
 /// Generates a struct name from the current crate and implements RegisteredBin
+///
+/// `kestrelize!(args = MyArgs)` also implements TypedBin, so the binary can be run with
+/// `Bin::with_args`.
 #[proc_macro]
-pub fn kestrelize(_input: TokenStream) -> TokenStream {
+pub fn kestrelize(input: TokenStream) -> TokenStream {
 	let crate_name = std::env!("CARGO_PKG_NAME");
 	let struct_name = crate_name.to_case(Case::Pascal); // e.g., "my-crate" -> "MyCrate"
 	let ident = syn::Ident::new(&struct_name, proc_macro2::Span::call_site());
 
+	let args = match syn::parse::Parser::parse(parse_kestrelize_args, input) {
+		Ok(args) => args,
+		Err(e) => return TokenStream::from(e.to_compile_error()),
+	};
+	let typed = args.map(|args| {
+		quote! {
+			impl kestrel::TypedBin for #ident {
+				type Args = #args;
+			}
+		}
+	});
+
 	TokenStream::from(quote! {
 		pub struct #ident;
 
@@ -17,5 +32,228 @@ pub fn kestrelize(_input: TokenStream) -> TokenStream {
 				env!("CARGO_PKG_NAME")
 			}
 		}
+
+		#typed
+	})
+}
+
+/// Parses the optional `args = Type` input of [kestrelize].
+fn parse_kestrelize_args(input: syn::parse::ParseStream) -> syn::Result<Option<syn::Type>> {
+	if input.is_empty() {
+		return Ok(None);
+	}
+	let key: syn::Ident = input.parse()?;
+	if key != "args" {
+		return Err(syn::Error::new(key.span(), "expected `args = Type`"));
+	}
+	input.parse::<syn::Token![=]>()?;
+	Ok(Some(input.parse()?))
+}
+
+/// Derives BinArgs, serializing each field of the struct to a command-line flag
+///
+/// Fields become `--kebab-case` flags. `bool` fields are switches, `Option` fields are skipped
+/// when `None`, `Vec` fields repeat the flag for each value, and other fields are formatted with
+/// `ToString`. Use `#[bin_args(rename = "name")]` to change a flag and `#[bin_args(positional)]`
+/// to pass a value without one.
+#[proc_macro_derive(BinArgs, attributes(bin_args))]
+pub fn derive_bin_args(input: TokenStream) -> TokenStream {
+	let input = syn::parse_macro_input!(input as syn::DeriveInput);
+	match expand_bin_args(&input) {
+		Ok(tokens) => TokenStream::from(tokens),
+		Err(e) => TokenStream::from(e.to_compile_error()),
+	}
+}
+
+/// The kinds of field BinArgs serializes differently.
+enum ArgKind {
+	Switch,
+	Optional,
+	Repeated,
+	Value,
+}
+
+fn arg_kind(ty: &syn::Type) -> ArgKind {
+	let syn::Type::Path(path) = ty else {
+		return ArgKind::Value;
+	};
+	match path.path.segments.last().map(|segment| segment.ident.to_string()).as_deref() {
+		Some("bool") => ArgKind::Switch,
+		Some("Option") => ArgKind::Optional,
+		Some("Vec") => ArgKind::Repeated,
+		_ => ArgKind::Value,
+	}
+}
+
+fn expand_bin_args(input: &syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+	let fields = match &input.data {
+		syn::Data::Struct(syn::DataStruct { fields: syn::Fields::Named(fields), .. }) => {
+			&fields.named
+		}
+		_ => {
+			return Err(syn::Error::new_spanned(
+				&input.ident,
+				"BinArgs can only be derived for structs with named fields",
+			))
+		}
+	};
+
+	let mut pushes = Vec::new();
+	for field in fields {
+		let Some(ident) = field.ident.as_ref() else {
+			continue;
+		};
+		let mut flag = format!("--{}", ident.to_string().to_case(Case::Kebab));
+		let mut positional = false;
+		for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("bin_args")) {
+			attr.parse_nested_meta(|meta| {
+				if meta.path.is_ident("rename") {
+					let name: syn::LitStr = meta.value()?.parse()?;
+					flag = format!("--{}", name.value());
+					Ok(())
+				} else if meta.path.is_ident("positional") {
+					positional = true;
+					Ok(())
+				} else {
+					Err(meta.error("expected `rename = \"...\"` or `positional`"))
+				}
+			})?;
+		}
+
+		let push_flag = (!positional).then(|| quote! { args.push(#flag.to_string()); });
+		pushes.push(match arg_kind(&field.ty) {
+			ArgKind::Switch if positional => quote! { args.push(self.#ident.to_string()); },
+			ArgKind::Switch => quote! {
+				if self.#ident {
+					#push_flag
+				}
+			},
+			ArgKind::Optional => quote! {
+				if let Some(value) = &self.#ident {
+					#push_flag
+					args.push(value.to_string());
+				}
+			},
+			ArgKind::Repeated => quote! {
+				for value in &self.#ident {
+					#push_flag
+					args.push(value.to_string());
+				}
+			},
+			ArgKind::Value => quote! {
+				#push_flag
+				args.push(self.#ident.to_string());
+			},
+		});
+	}
+
+	let name = &input.ident;
+	let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+	Ok(quote! {
+		impl #impl_generics kestrel::BinArgs for #name #ty_generics #where_clause {
+			fn to_args(&self) -> Vec<String> {
+				let mut args = Vec::new();
+				#(#pushes)*
+				args
+			}
+		}
+	})
+}
+
+/// Derives a companion struct holding a [kestrel::State] for every field of the struct
+///
+/// For a struct `Config`, this generates `ConfigStates` with a `{field}_writer()` and
+/// `{field}_reader()` accessor per field, and `wait_all()` which waits for every state to be set
+/// and assembles a `Config`. Each state is named `Config.{field}`.
+#[proc_macro_derive(KestrelState)]
+pub fn derive_kestrel_state(input: TokenStream) -> TokenStream {
+	let input = syn::parse_macro_input!(input as syn::DeriveInput);
+	match expand_kestrel_state(&input) {
+		Ok(tokens) => TokenStream::from(tokens),
+		Err(e) => TokenStream::from(e.to_compile_error()),
+	}
+}
+
+fn expand_kestrel_state(input: &syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+	if !input.generics.params.is_empty() {
+		return Err(syn::Error::new_spanned(
+			&input.generics,
+			"KestrelState does not support generic structs",
+		));
+	}
+	let fields = match &input.data {
+		syn::Data::Struct(syn::DataStruct { fields: syn::Fields::Named(fields), .. }) => {
+			&fields.named
+		}
+		_ => {
+			return Err(syn::Error::new_spanned(
+				&input.ident,
+				"KestrelState can only be derived for structs with named fields",
+			))
+		}
+	};
+
+	let vis = &input.vis;
+	let name = &input.ident;
+	let states = syn::Ident::new(&format!("{}States", name), name.span());
+	let idents: Vec<_> = fields.iter().filter_map(|field| field.ident.as_ref()).collect();
+	let types: Vec<_> = fields.iter().map(|field| &field.ty).collect();
+	let state_names: Vec<_> = idents.iter().map(|ident| format!("{}.{}", name, ident)).collect();
+	let writers: Vec<_> = idents
+		.iter()
+		.map(|ident| syn::Ident::new(&format!("{}_writer", ident), ident.span()))
+		.collect();
+	let readers: Vec<_> = idents
+		.iter()
+		.map(|ident| syn::Ident::new(&format!("{}_reader", ident), ident.span()))
+		.collect();
+	let doc = format!("A [kestrel::State] for every field of [{}]", name);
+
+	Ok(quote! {
+		#[doc = #doc]
+		#[derive(Clone)]
+		#vis struct #states {
+			#(pub #idents: kestrel::State<#types>,)*
+		}
+
+		impl #states {
+			/// Creates unset states, named after their fields
+			pub fn new() -> Self {
+				Self { #(#idents: kestrel::State::new().named(#state_names),)* }
+			}
+
+			#(
+				/// Returns a writer for the state of this field
+				pub fn #writers(&self) -> kestrel::WritableState<#types> {
+					self.#idents.write()
+				}
+
+				/// Returns a reader for the state of this field
+				pub fn #readers(&self) -> kestrel::ReadOnlyState<#types> {
+					self.#idents.read()
+				}
+			)*
+
+			/// Waits concurrently for every state to be set and assembles their values
+			pub async fn wait_all(&self) -> #name {
+				let (#(#idents,)*) =
+					kestrel::reexport::tokio::join!(#(self.#idents.read().wait_forever()),*);
+				#name { #(#idents,)* }
+			}
+
+			/// Waits for every state to be set up to a given condition
+			pub async fn wait_all_for(
+				&self,
+				condition: impl Into<kestrel::WaitCondition>,
+			) -> Result<#name, kestrel::WaitError> {
+				kestrel::within(condition, self.wait_all()).await
+			}
+		}
+
+		impl Default for #states {
+			fn default() -> Self {
+				Self::new()
+			}
+		}
 	})
 }
@@ -0,0 +1,108 @@
+use crate::fulfill::{Fulfill, FulfillError};
+use jsonlvar::Jsonl as JsonlOperations;
+use kestrel_state::WritableState;
+use std::future::Future;
+use tokio::sync::mpsc::Sender;
+
+/// A fulfiller that fulfills requests from environment variables.
+///
+/// The value is read in one shot, so no pipe needs to be attached to a process.
+pub struct Env<T>
+where
+	T: JsonlOperations + Clone + Send + Sync + 'static,
+{
+	dependency: WritableState<T>,
+	var_prefix: Option<String>,
+}
+
+impl<T> Env<T>
+where
+	T: JsonlOperations + Clone + Send + Sync + 'static,
+{
+	/// Creates a new Env fulfiller.
+	pub fn new(dependency: WritableState<T>, var_prefix: Option<String>) -> Self {
+		Self { dependency, var_prefix }
+	}
+}
+
+impl<T> Fulfill<T> for Env<T>
+where
+	T: JsonlOperations + Clone + Send + Sync + 'static,
+{
+	/// Returns a sender whose receiver is already closed, as lines are never needed.
+	fn sender(&self) -> Result<Sender<String>, FulfillError> {
+		let (sender, _) = tokio::sync::mpsc::channel(1);
+		Ok(sender)
+	}
+
+	fn dependency(&self) -> Result<WritableState<T>, FulfillError> {
+		Ok(self.dependency.clone())
+	}
+
+	fn try_get(&mut self) -> impl Future<Output = Result<Option<T>, FulfillError>> + Send {
+		async move {
+			T::try_from_env(self.var_prefix.as_deref())
+				.map(Some)
+				.map_err(|e| FulfillError::Fulfill(Box::new(e)))
+		}
+	}
+
+	/// Fulfills immediately, or fails listing the missing variables.
+	///
+	/// The environment does not change while waiting, so this does not retry.
+	fn run(mut self) -> impl Future<Output = Result<T, FulfillError>> + Send {
+		async move { self.try_fulfill().await }
+	}
+
+	/// Fulfills once, as the environment does not change while waiting.
+	fn run_continuous(self) -> impl Future<Output = Result<(), FulfillError>> + Send {
+		async move { self.run().await.map(|_| ()) }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use jsonlvar::{Jsonl, JsonlError};
+	use kestrel_state::State;
+	use serde::{Deserialize, Serialize};
+
+	#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Jsonl)]
+	struct Node {
+		host: String,
+		port: u16,
+	}
+
+	#[tokio::test]
+	async fn test_env_missing_variables() -> Result<(), anyhow::Error> {
+		std::env::set_var("KESTREL_ENV_MISSING_HOST", "localhost");
+		let state = State::new();
+
+		let env = Env::<Node>::new(state.write(), Some("kestrel_env_missing".to_string()));
+		let error = match env.run().await {
+			Err(FulfillError::Fulfill(error)) => error,
+			other => panic!("expected a fulfillment error, got {:?}", other),
+		};
+		match error.downcast_ref::<JsonlError>() {
+			Some(JsonlError::MissingEnvVars(missing)) => {
+				assert_eq!(missing, &vec!["KESTREL_ENV_MISSING_PORT".to_string()]);
+			}
+			other => panic!("expected missing env vars, got {:?}", other),
+		}
+		assert_eq!(state.read().get().await, None);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_env_run_with_times_out() -> Result<(), anyhow::Error> {
+		let state = State::new();
+
+		let env = Env::<Node>::new(state.write(), Some("kestrel_env_never".to_string()));
+		let result = env.run_with(std::time::Duration::from_millis(50)).await;
+		assert!(matches!(result, Err(FulfillError::TimedOut { .. })));
+		assert_eq!(state.read().get().await, None);
+
+		Ok(())
+	}
+}
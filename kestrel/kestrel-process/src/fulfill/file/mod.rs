@@ -0,0 +1,111 @@
+use crate::fulfill::{Fulfill, FulfillError};
+use kestrel_state::WritableState;
+use serde::de::DeserializeOwned;
+use std::future::Future;
+use std::path::PathBuf;
+use tokio::sync::mpsc::Sender;
+use tokio::time::Duration;
+
+/// A fulfiller that fulfills requests by polling a file until it exists and parses as JSON.
+///
+/// The file is read whole on every attempt, so a partially written file is simply retried.
+pub struct File<T>
+where
+	T: DeserializeOwned + Clone + Send + Sync + 'static,
+{
+	path: PathBuf,
+	dependency: WritableState<T>,
+	interval: Duration,
+	polled: bool,
+}
+
+impl<T> File<T>
+where
+	T: DeserializeOwned + Clone + Send + Sync + 'static,
+{
+	/// Creates a new File fulfiller.
+	pub fn new(dependency: WritableState<T>, path: impl Into<PathBuf>) -> Self {
+		Self { path: path.into(), dependency, interval: Duration::from_millis(100), polled: false }
+	}
+
+	/// Sets the interval between reads of the file.
+	pub fn interval(mut self, interval: Duration) -> Self {
+		self.interval = interval;
+		self
+	}
+}
+
+impl<T> Fulfill<T> for File<T>
+where
+	T: DeserializeOwned + Clone + Send + Sync + 'static,
+{
+	/// Returns a sender whose receiver is already closed, as lines are never needed.
+	fn sender(&self) -> Result<Sender<String>, FulfillError> {
+		let (sender, _) = tokio::sync::mpsc::channel(1);
+		Ok(sender)
+	}
+
+	fn dependency(&self) -> Result<WritableState<T>, FulfillError> {
+		Ok(self.dependency.clone())
+	}
+
+	fn try_get(&mut self) -> impl Future<Output = Result<Option<T>, FulfillError>> + Send {
+		async move {
+			if std::mem::replace(&mut self.polled, true) {
+				tokio::time::sleep(self.interval).await;
+			}
+			let contents = match tokio::fs::read_to_string(&self.path).await {
+				Ok(contents) => contents,
+				Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+				Err(e) => return Err(FulfillError::Fulfill(Box::new(e))),
+			};
+			serde_json::from_str(&contents).map(Some).map_err(|e| {
+				FulfillError::Fulfill(format!("{} is not valid: {}", self.path.display(), e).into())
+			})
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use kestrel_state::State;
+
+	#[tokio::test]
+	async fn test_file_waits_for_contents() -> Result<(), anyhow::Error> {
+		let temp_dir = tempfile::tempdir()?;
+		let path = temp_dir.path().join("port.json");
+		let state = State::new();
+
+		let file = File::<u16>::new(state.write(), &path).interval(Duration::from_millis(10));
+		let handle = file.spawn()?;
+
+		tokio::time::sleep(Duration::from_millis(30)).await;
+		assert_eq!(state.read().get().await, None);
+		std::fs::write(&path, "9000")?;
+
+		assert_eq!(handle.await??, 9000);
+		assert_eq!(state.read().get().await, Some(9000));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_file_retries_invalid_contents() -> Result<(), anyhow::Error> {
+		let temp_dir = tempfile::tempdir()?;
+		let path = temp_dir.path().join("config.json");
+		std::fs::write(&path, "{\"port\":")?;
+		let state = State::new();
+
+		let file = File::<std::collections::HashMap<String, u16>>::new(state.write(), &path)
+			.interval(Duration::from_millis(10));
+		let result = file.run_with_attempts(3, Duration::ZERO).await;
+		assert!(matches!(result, Err(FulfillError::TimedOut { .. })));
+
+		std::fs::write(&path, "{\"port\": 9000}")?;
+		let file = File::<std::collections::HashMap<String, u16>>::new(state.write(), &path);
+		assert_eq!(file.run().await?["port"], 9000);
+
+		Ok(())
+	}
+}
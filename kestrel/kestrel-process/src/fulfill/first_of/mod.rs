@@ -0,0 +1,169 @@
+use crate::fulfill::zip::fan_out;
+use crate::fulfill::{Fulfill, FulfillError};
+use kestrel_state::WritableState;
+use std::future::Future;
+use tokio::sync::mpsc::Sender;
+
+/// A fulfiller that takes the value of whichever of two fulfillers is fulfilled first.
+pub struct FirstOf<T, FA, FB>
+where
+	T: Clone + Send + Sync + 'static,
+	FA: Fulfill<T>,
+	FB: Fulfill<T>,
+{
+	a: FA,
+	b: FB,
+	state: WritableState<T>,
+}
+
+impl<T, FA, FB> FirstOf<T, FA, FB>
+where
+	T: Clone + Send + Sync + 'static,
+	FA: Fulfill<T>,
+	FB: Fulfill<T>,
+{
+	/// Creates a new FirstOf fulfiller.
+	pub fn new(state: WritableState<T>, a: FA, b: FB) -> Self {
+		Self { a, b, state }
+	}
+}
+
+/// Combines two fulfillers of the same value into one fulfilled by whichever arrives first.
+///
+/// Each inner fulfiller still sets its own state once fulfilled. Use [crate::fulfill::zip] to
+/// wait for both instead.
+pub fn first_of<T, FA, FB>(state: WritableState<T>, a: FA, b: FB) -> FirstOf<T, FA, FB>
+where
+	T: Clone + Send + Sync + 'static,
+	FA: Fulfill<T>,
+	FB: Fulfill<T>,
+{
+	FirstOf::new(state, a, b)
+}
+
+impl<T, FA, FB> Fulfill<T> for FirstOf<T, FA, FB>
+where
+	T: Clone + Send + Sync + 'static,
+	FA: Fulfill<T>,
+	FB: Fulfill<T>,
+{
+	/// Gets a sender which forwards each line to both inner fulfillers.
+	fn sender(&self) -> Result<Sender<String>, FulfillError> {
+		Ok(fan_out(self.a.sender()?, self.b.sender()?))
+	}
+
+	fn dependency(&self) -> Result<WritableState<T>, FulfillError> {
+		Ok(self.state.clone())
+	}
+
+	/// Attempts both inner fulfillers, failing only once both attempts have failed.
+	///
+	/// A failed attempt on one side keeps waiting on the other, so a side which fails quickly
+	/// does not cancel a slower attempt which would succeed.
+	fn try_get(&mut self) -> impl Future<Output = Result<Option<T>, FulfillError>> + Send {
+		async move {
+			let a = self.a.try_fulfill();
+			let b = self.b.try_fulfill();
+			tokio::pin!(a, b);
+			let (mut a_failed, mut b_failed) = (false, false);
+			loop {
+				tokio::select! {
+					result = &mut a, if !a_failed => match result {
+						Err(FulfillError::Fulfill(_)) if !b_failed => a_failed = true,
+						result => return result.map(Some),
+					},
+					result = &mut b, if !b_failed => match result {
+						Err(FulfillError::Fulfill(_)) if !a_failed => b_failed = true,
+						result => return result.map(Some),
+					},
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::fulfill::custom::{Custom, CustomProcessor};
+	use kestrel_state::State;
+	use tokio::sync::mpsc::Receiver;
+
+	/// Parses the next line as a number if it starts with the given prefix.
+	struct Prefixed(&'static str);
+
+	impl CustomProcessor<u64> for Prefixed {
+		fn process_receiver(
+			&self,
+			receiver: &mut Receiver<String>,
+		) -> impl Future<Output = Result<Option<u64>, FulfillError>> + Send {
+			async move {
+				let line = receiver.recv().await.unwrap_or_default();
+				Ok(line.trim().strip_prefix(self.0).and_then(|value| value.parse().ok()))
+			}
+		}
+	}
+
+	#[tokio::test]
+	async fn test_first_of() -> Result<(), anyhow::Error> {
+		let port = State::new();
+		let from_flag = State::new();
+		let from_log = State::new();
+
+		let first = first_of(
+			port.write(),
+			Custom::new(from_flag.write(), Prefixed("--port=")),
+			Custom::new(from_log.write(), Prefixed("listening on ")),
+		);
+
+		let sender = first.sender()?;
+		let handle = first.spawn()?;
+
+		sender.send("starting\n".to_string()).await?;
+		sender.send("listening on 9000\n".to_string()).await?;
+
+		assert_eq!(handle.await??, 9000);
+		assert_eq!(port.read().get().await, Some(9000));
+		assert_eq!(from_log.read().get().await, Some(9000));
+		assert_eq!(from_flag.read().get().await, None);
+
+		Ok(())
+	}
+
+	/// Fails every attempt without awaiting anything, like a missing environment variable.
+	struct Failing;
+
+	impl CustomProcessor<u64> for Failing {
+		fn process_receiver(
+			&self,
+			_receiver: &mut Receiver<String>,
+		) -> impl Future<Output = Result<Option<u64>, FulfillError>> + Send {
+			async move { Err(FulfillError::Fulfill("not available".into())) }
+		}
+	}
+
+	#[tokio::test]
+	async fn test_first_of_waits_past_failed_side() -> Result<(), anyhow::Error> {
+		let port = State::new();
+		let from_env = State::new();
+		let from_log = State::new();
+
+		let first = first_of(
+			port.write(),
+			Custom::new(from_env.write(), Failing),
+			Custom::new(from_log.write(), Prefixed("listening on ")),
+		);
+
+		let sender = first.sender()?;
+		let handle = first.spawn()?;
+
+		tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+		sender.send("listening on 9000\n".to_string()).await?;
+
+		assert_eq!(handle.await??, 9000);
+		assert_eq!(port.read().get().await, Some(9000));
+		assert_eq!(from_env.read().get().await, None);
+
+		Ok(())
+	}
+}
@@ -0,0 +1,193 @@
+use crate::fulfill::{Fulfill, FulfillError};
+use crate::ready::probe::{http_address, split_http_url};
+use kestrel_state::WritableState;
+use serde::de::DeserializeOwned;
+use std::future::Future;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::Sender;
+use tokio::time::Duration;
+
+/// A fulfiller that fulfills requests by polling an `http://` endpoint until it responds with 200
+/// and a JSON body.
+pub struct Http<T>
+where
+	T: DeserializeOwned + Clone + Send + Sync + 'static,
+{
+	authority: String,
+	path: String,
+	dependency: WritableState<T>,
+	interval: Duration,
+	timeout: Duration,
+	polled: bool,
+}
+
+impl<T> Http<T>
+where
+	T: DeserializeOwned + Clone + Send + Sync + 'static,
+{
+	/// Creates a new Http fulfiller.
+	pub fn new(dependency: WritableState<T>, url: &str) -> Result<Self, FulfillError> {
+		let (authority, path) = split_http_url(url).ok_or_else(|| {
+			FulfillError::Internal(format!("unsupported url {}, expected http://", url).into())
+		})?;
+		Ok(Self {
+			authority: authority.to_string(),
+			path: path.to_string(),
+			dependency,
+			interval: Duration::from_millis(100),
+			timeout: Duration::from_secs(1),
+			polled: false,
+		})
+	}
+
+	/// Sets the interval between requests.
+	pub fn interval(mut self, interval: Duration) -> Self {
+		self.interval = interval;
+		self
+	}
+
+	/// Sets how long a single request may take, including connecting and reading the response.
+	pub fn timeout(mut self, timeout: Duration) -> Self {
+		self.timeout = timeout;
+		self
+	}
+
+	/// Sends a GET request and returns the body if the response is 200 within the timeout.
+	async fn get(&self) -> Result<String, FulfillError> {
+		tokio::time::timeout(self.timeout, self.request()).await.map_err(|_| {
+			FulfillError::Fulfill(
+				format!(
+					"GET http://{}{} did not respond within {:?}",
+					self.authority, self.path, self.timeout
+				)
+				.into(),
+			)
+		})?
+	}
+
+	/// Sends a GET request and returns the body if the response is 200.
+	async fn request(&self) -> Result<String, FulfillError> {
+		let mut stream = TcpStream::connect(http_address(&self.authority))
+			.await
+			.map_err(|e| FulfillError::Fulfill(Box::new(e)))?;
+		// HTTP/1.0 avoids chunked responses, so the body is everything after the headers
+		let request = format!(
+			"GET {} HTTP/1.0\r\nHost: {}\r\nAccept: application/json\r\n\r\n",
+			self.path, self.authority
+		);
+		stream
+			.write_all(request.as_bytes())
+			.await
+			.map_err(|e| FulfillError::Fulfill(Box::new(e)))?;
+		let mut response = String::new();
+		stream
+			.read_to_string(&mut response)
+			.await
+			.map_err(|e| FulfillError::Fulfill(Box::new(e)))?;
+
+		let (head, body) = response.split_once("\r\n\r\n").unwrap_or((response.as_str(), ""));
+		let status = head.lines().next().unwrap_or_default();
+		if status.split_whitespace().nth(1) != Some("200") {
+			return Err(FulfillError::Fulfill(
+				format!("GET http://{}{} responded {}", self.authority, self.path, status).into(),
+			));
+		}
+		Ok(body.to_string())
+	}
+}
+
+impl<T> Fulfill<T> for Http<T>
+where
+	T: DeserializeOwned + Clone + Send + Sync + 'static,
+{
+	/// Returns a sender whose receiver is already closed, as lines are never needed.
+	fn sender(&self) -> Result<Sender<String>, FulfillError> {
+		let (sender, _) = tokio::sync::mpsc::channel(1);
+		Ok(sender)
+	}
+
+	fn dependency(&self) -> Result<WritableState<T>, FulfillError> {
+		Ok(self.dependency.clone())
+	}
+
+	fn try_get(&mut self) -> impl Future<Output = Result<Option<T>, FulfillError>> + Send {
+		async move {
+			if std::mem::replace(&mut self.polled, true) {
+				tokio::time::sleep(self.interval).await;
+			}
+			let body = self.get().await?;
+			serde_json::from_str(&body)
+				.map(Some)
+				.map_err(|e| FulfillError::Fulfill(Box::new(e)))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use kestrel_state::State;
+	use std::collections::HashMap;
+	use tokio::io::{AsyncBufReadExt, BufReader};
+	use tokio::net::TcpListener;
+
+	#[tokio::test]
+	async fn test_http_polls_until_ok() -> Result<(), anyhow::Error> {
+		let listener = TcpListener::bind("127.0.0.1:0").await?;
+		let address = listener.local_addr()?;
+		let server = tokio::spawn(async move {
+			// The first request fails, the second succeeds
+			let responses = ["503 Service Unavailable\r\n\r\n", "200 OK\r\n\r\n{\"port\": 9000}"];
+			for response in responses {
+				let (mut stream, _) = listener.accept().await?;
+				let mut request = String::new();
+				BufReader::new(&mut stream).read_line(&mut request).await?;
+				stream.write_all(format!("HTTP/1.0 {}", response).as_bytes()).await?;
+			}
+			Ok::<_, std::io::Error>(())
+		});
+
+		let state = State::new();
+		let http = Http::<HashMap<String, u16>>::new(
+			state.write(),
+			&format!("http://{}/config", address),
+		)?
+		.interval(Duration::from_millis(10));
+
+		assert_eq!(http.run().await?["port"], 9000);
+		assert!(state.read().get().await.is_some());
+		server.await??;
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_http_times_out_stalled_server() -> Result<(), anyhow::Error> {
+		let listener = TcpListener::bind("127.0.0.1:0").await?;
+		let address = listener.local_addr()?;
+		// Accepts connections but never responds
+		let _server = tokio::spawn(async move {
+			let mut streams = Vec::new();
+			while let Ok((stream, _)) = listener.accept().await {
+				streams.push(stream);
+			}
+		});
+
+		let state = State::<u16>::new();
+		let http = Http::new(state.write(), &format!("http://{}/config", address))?
+			.interval(Duration::from_millis(10))
+			.timeout(Duration::from_millis(50));
+
+		let result = http.run_with_attempts(2, Duration::ZERO).await;
+		assert!(matches!(result, Err(FulfillError::TimedOut { attempts: 2, .. })));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_http_rejects_other_schemes() {
+		let state = State::<u16>::new();
+		assert!(Http::new(state.write(), "https://localhost/config").is_err());
+	}
+}
@@ -0,0 +1,112 @@
+use crate::fulfill::{Fulfill, FulfillError};
+use kestrel_state::WritableState;
+use std::future::Future;
+use std::marker::PhantomData;
+use tokio::sync::mpsc::Sender;
+
+/// A fulfiller that transforms the value of another fulfiller.
+pub struct Map<A, T, FA, F>
+where
+	A: Clone + Send + Sync + 'static,
+	T: Clone + Send + Sync + 'static,
+	FA: Fulfill<A>,
+	F: Fn(A) -> T + Send + Sync + 'static,
+{
+	inner: FA,
+	f: F,
+	state: WritableState<T>,
+	phantom: PhantomData<fn() -> A>,
+}
+
+impl<A, T, FA, F> Map<A, T, FA, F>
+where
+	A: Clone + Send + Sync + 'static,
+	T: Clone + Send + Sync + 'static,
+	FA: Fulfill<A>,
+	F: Fn(A) -> T + Send + Sync + 'static,
+{
+	/// Creates a new Map fulfiller.
+	pub fn new(state: WritableState<T>, inner: FA, f: F) -> Self {
+		Self { inner, f, state, phantom: PhantomData }
+	}
+}
+
+/// Transforms a fulfiller into one whose value is `f(value)`.
+///
+/// The inner fulfiller still sets its own state once fulfilled.
+pub fn map<A, T, FA, F>(state: WritableState<T>, inner: FA, f: F) -> Map<A, T, FA, F>
+where
+	A: Clone + Send + Sync + 'static,
+	T: Clone + Send + Sync + 'static,
+	FA: Fulfill<A>,
+	F: Fn(A) -> T + Send + Sync + 'static,
+{
+	Map::new(state, inner, f)
+}
+
+impl<A, T, FA, F> Fulfill<T> for Map<A, T, FA, F>
+where
+	A: Clone + Send + Sync + 'static,
+	T: Clone + Send + Sync + 'static,
+	FA: Fulfill<A>,
+	F: Fn(A) -> T + Send + Sync + 'static,
+{
+	fn sender(&self) -> Result<Sender<String>, FulfillError> {
+		self.inner.sender()
+	}
+
+	fn dependency(&self) -> Result<WritableState<T>, FulfillError> {
+		Ok(self.state.clone())
+	}
+
+	fn try_get(&mut self) -> impl Future<Output = Result<Option<T>, FulfillError>> + Send {
+		async move {
+			let value = self.inner.try_fulfill().await?;
+			Ok(Some((self.f)(value)))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::fulfill::custom::{Custom, CustomProcessor};
+	use kestrel_state::State;
+	use tokio::sync::mpsc::Receiver;
+
+	/// Parses the next line as a number.
+	struct Number;
+
+	impl CustomProcessor<u16> for Number {
+		fn process_receiver(
+			&self,
+			receiver: &mut Receiver<String>,
+		) -> impl Future<Output = Result<Option<u16>, FulfillError>> + Send {
+			async move {
+				let line = receiver.recv().await.unwrap_or_default();
+				Ok(line.trim().parse().ok())
+			}
+		}
+	}
+
+	#[tokio::test]
+	async fn test_map() -> Result<(), anyhow::Error> {
+		let port = State::new();
+		let url = State::new();
+
+		let mapped = map(url.write(), Custom::new(port.write(), Number), |port: u16| {
+			format!("http://127.0.0.1:{}", port)
+		});
+
+		let sender = mapped.sender()?;
+		let handle = mapped.spawn()?;
+
+		sender.send("9000\n".to_string()).await?;
+
+		assert_eq!(handle.await??, "http://127.0.0.1:9000");
+		assert_eq!(url.read().get().await.as_deref(), Some("http://127.0.0.1:9000"));
+		assert_eq!(port.read().get().await, Some(9000));
+
+		Ok(())
+	}
+}
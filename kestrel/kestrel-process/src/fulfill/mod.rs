@@ -1,10 +1,21 @@
 pub mod custom;
+pub mod env;
+pub mod file;
+pub mod first_of;
+pub mod http;
 pub mod jsonl;
+pub mod map;
+pub mod zip;
 
-use kestrel_state::WritableState;
+pub use first_of::first_of;
+pub use map::map;
+pub use zip::zip;
+
+use kestrel_state::{WaitCondition, WritableState};
 use std::future::Future;
 use thiserror::Error;
 use tokio::sync::mpsc::Sender;
+use tokio::time::{Duration, Instant};
 
 #[derive(Debug, Error)]
 pub enum FulfillError {
@@ -16,6 +27,9 @@ pub enum FulfillError {
 
 	#[error("internal fulfillment error: {0}")]
 	Internal(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+	#[error("fulfillment timed out after {attempts} attempts over {waited:?}")]
+	TimedOut { attempts: u32, waited: Duration },
 }
 
 pub trait Fulfill<T>: Sized + Send + Sync + 'static
@@ -60,10 +74,163 @@ where
 		}
 	}
 
+	/// Runs the fulfillment task until the value is fulfilled or the condition expires.
+	fn run_with(
+		mut self,
+		condition: impl Into<WaitCondition> + Send,
+	) -> impl Future<Output = Result<T, FulfillError>> + Send {
+		async move {
+			let started = Instant::now();
+			let mut attempts = 0;
+			let fulfill = async {
+				loop {
+					attempts += 1;
+					match self.try_fulfill().await {
+						// Attempts may fail without awaiting, so yield to let the timeout fire
+						Err(FulfillError::Fulfill(_)) => tokio::task::yield_now().await,
+						result => return result,
+					}
+				}
+			};
+			match kestrel_state::within(condition, fulfill).await {
+				Ok(result) => result,
+				Err(_) => Err(FulfillError::TimedOut { attempts, waited: started.elapsed() }),
+			}
+		}
+	}
+
+	/// Runs the fulfillment task for at most `max_attempts`, sleeping for `backoff` between them.
+	fn run_with_attempts(
+		mut self,
+		max_attempts: u32,
+		backoff: Duration,
+	) -> impl Future<Output = Result<T, FulfillError>> + Send {
+		async move {
+			let started = Instant::now();
+			for attempt in 1..=max_attempts {
+				match self.try_fulfill().await {
+					Err(FulfillError::Fulfill(_)) if attempt < max_attempts => {
+						tokio::time::sleep(backoff).await;
+					}
+					Err(FulfillError::Fulfill(_)) => break,
+					result => return result,
+				}
+			}
+			Err(FulfillError::TimedOut { attempts: max_attempts, waited: started.elapsed() })
+		}
+	}
+
 	/// Spawns the fulfillment task in the background
 	fn spawn(self) -> Result<tokio::task::JoinHandle<Result<T, FulfillError>>, FulfillError> {
 		let join_handle = tokio::spawn(async move { self.run().await });
 
 		Ok(join_handle)
 	}
+
+	/// Runs the fulfillment task continuously, setting the state to every value as it arrives.
+	///
+	/// Unlike [Fulfill::run] this does not stop at the first value, so the state tracks values
+	/// which change over time. It only returns on an error other than a failed attempt.
+	fn run_continuous(mut self) -> impl Future<Output = Result<(), FulfillError>> + Send {
+		async move {
+			loop {
+				match self.try_fulfill().await {
+					Ok(_) | Err(FulfillError::Fulfill(_)) => continue,
+					Err(e) => return Err(e),
+				}
+			}
+		}
+	}
+
+	/// Spawns the continuous fulfillment task in the background
+	fn spawn_continuous(
+		self,
+	) -> Result<tokio::task::JoinHandle<Result<(), FulfillError>>, FulfillError> {
+		let join_handle = tokio::spawn(async move { self.run_continuous().await });
+
+		Ok(join_handle)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::fulfill::custom::{Custom, CustomProcessor};
+	use kestrel_state::State;
+	use tokio::sync::mpsc::Receiver;
+
+	/// Parses the next line as a number, waiting for a line if there is none.
+	struct Number;
+
+	impl CustomProcessor<u64> for Number {
+		fn process_receiver(
+			&self,
+			receiver: &mut Receiver<String>,
+		) -> impl Future<Output = Result<Option<u64>, FulfillError>> + Send {
+			async move {
+				let line = receiver.recv().await.unwrap_or_default();
+				Ok(line.trim().parse().ok())
+			}
+		}
+	}
+
+	#[tokio::test]
+	async fn test_run_with_times_out() -> Result<(), anyhow::Error> {
+		let state = State::new();
+		let custom = Custom::new(state.write(), Number);
+		let _sender = custom.sender()?;
+
+		let result = custom.run_with(Duration::from_millis(50)).await;
+		assert!(matches!(result, Err(FulfillError::TimedOut { .. })));
+		assert_eq!(state.read().get().await, None);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_run_with_fulfills() -> Result<(), anyhow::Error> {
+		let state = State::new();
+		let custom = Custom::new(state.write(), Number);
+		let sender = custom.sender()?;
+		sender.send("not a number\n".to_string()).await?;
+		sender.send("42\n".to_string()).await?;
+
+		assert_eq!(custom.run_with(Duration::from_secs(5)).await?, 42);
+		assert_eq!(state.read().get().await, Some(42));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_run_with_attempts_gives_up() -> Result<(), anyhow::Error> {
+		let state = State::new();
+		let custom = Custom::new(state.write(), Number);
+		let sender = custom.sender()?;
+		for _ in 0..5 {
+			sender.send("not a number\n".to_string()).await?;
+		}
+
+		let result = custom.run_with_attempts(3, Duration::from_millis(1)).await;
+		assert!(matches!(result, Err(FulfillError::TimedOut { attempts: 3, .. })));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_run_continuous_tracks_values() -> Result<(), anyhow::Error> {
+		let state = State::new();
+		let custom = Custom::new(state.write(), Number);
+		let sender = custom.sender()?;
+		let handle = custom.spawn_continuous()?;
+
+		let reader = state.read();
+		sender.send("1\n".to_string()).await?;
+		reader.wait_until(|value| *value == 1, Duration::from_secs(5)).await?;
+		sender.send("not a number\n".to_string()).await?;
+		sender.send("2\n".to_string()).await?;
+		reader.wait_until(|value| *value == 2, Duration::from_secs(5)).await?;
+		handle.abort();
+
+		Ok(())
+	}
 }
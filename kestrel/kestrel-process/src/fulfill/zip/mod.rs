@@ -0,0 +1,221 @@
+use crate::fulfill::{Fulfill, FulfillError};
+use kestrel_state::WritableState;
+use std::future::Future;
+use std::marker::PhantomData;
+use tokio::sync::mpsc::Sender;
+
+/// A fulfiller that combines the outputs of two fulfillers into one value.
+pub struct Zip<A, B, T, FA, FB, F>
+where
+	A: Clone + Send + Sync + 'static,
+	B: Clone + Send + Sync + 'static,
+	T: Clone + Send + Sync + 'static,
+	FA: Fulfill<A>,
+	FB: Fulfill<B>,
+	F: Fn(A, B) -> T + Send + Sync + 'static,
+{
+	a: FA,
+	b: FB,
+	a_value: Option<A>,
+	b_value: Option<B>,
+	combine: F,
+	state: WritableState<T>,
+	phantom: PhantomData<fn() -> (A, B)>,
+}
+
+impl<A, B, T, FA, FB, F> Zip<A, B, T, FA, FB, F>
+where
+	A: Clone + Send + Sync + 'static,
+	B: Clone + Send + Sync + 'static,
+	T: Clone + Send + Sync + 'static,
+	FA: Fulfill<A>,
+	FB: Fulfill<B>,
+	F: Fn(A, B) -> T + Send + Sync + 'static,
+{
+	/// Creates a new Zip fulfiller.
+	pub fn new(state: WritableState<T>, a: FA, b: FB, combine: F) -> Self {
+		Self { a, b, a_value: None, b_value: None, combine, state, phantom: PhantomData }
+	}
+}
+
+/// Combines two fulfillers into one whose value is `combine(a, b)`.
+///
+/// Each inner fulfiller still sets its own state once fulfilled.
+pub fn zip<A, B, T, FA, FB, F>(
+	state: WritableState<T>,
+	a: FA,
+	b: FB,
+	combine: F,
+) -> Zip<A, B, T, FA, FB, F>
+where
+	A: Clone + Send + Sync + 'static,
+	B: Clone + Send + Sync + 'static,
+	T: Clone + Send + Sync + 'static,
+	FA: Fulfill<A>,
+	FB: Fulfill<B>,
+	F: Fn(A, B) -> T + Send + Sync + 'static,
+{
+	Zip::new(state, a, b, combine)
+}
+
+/// Returns a sender which forwards each line to both senders.
+pub(crate) fn fan_out(a: Sender<String>, b: Sender<String>) -> Sender<String> {
+	let (sender, mut receiver) = tokio::sync::mpsc::channel::<String>(100);
+
+	tokio::spawn(async move {
+		while let Some(line) = receiver.recv().await {
+			let _ = a.send(line.clone()).await;
+			let _ = b.send(line).await;
+		}
+	});
+
+	sender
+}
+
+/// Attempts to fulfill one side of a [Zip], keeping the value once fulfilled.
+async fn fulfill_part<V, FV>(fulfiller: &mut FV, slot: &mut Option<V>) -> Result<(), FulfillError>
+where
+	V: Clone + Send + Sync + 'static,
+	FV: Fulfill<V>,
+{
+	if slot.is_none() {
+		match fulfiller.try_fulfill().await {
+			Ok(value) => *slot = Some(value),
+			Err(FulfillError::Fulfill(_)) => {}
+			Err(e) => return Err(e),
+		}
+	}
+	Ok(())
+}
+
+impl<A, B, T, FA, FB, F> Fulfill<T> for Zip<A, B, T, FA, FB, F>
+where
+	A: Clone + Send + Sync + 'static,
+	B: Clone + Send + Sync + 'static,
+	T: Clone + Send + Sync + 'static,
+	FA: Fulfill<A>,
+	FB: Fulfill<B>,
+	F: Fn(A, B) -> T + Send + Sync + 'static,
+{
+	/// Gets a sender which forwards each line to both inner fulfillers.
+	fn sender(&self) -> Result<Sender<String>, FulfillError> {
+		Ok(fan_out(self.a.sender()?, self.b.sender()?))
+	}
+
+	fn dependency(&self) -> Result<WritableState<T>, FulfillError> {
+		Ok(self.state.clone())
+	}
+
+	fn try_get(&mut self) -> impl Future<Output = Result<Option<T>, FulfillError>> + Send {
+		async move {
+			tokio::try_join!(
+				fulfill_part(&mut self.a, &mut self.a_value),
+				fulfill_part(&mut self.b, &mut self.b_value)
+			)?;
+
+			match (&self.a_value, &self.b_value) {
+				(Some(a), Some(b)) => Ok(Some((self.combine)(a.clone(), b.clone()))),
+				_ => Ok(None),
+			}
+		}
+	}
+
+	/// Recombines the values whenever either inner fulfiller is fulfilled again.
+	fn run_continuous(mut self) -> impl Future<Output = Result<(), FulfillError>> + Send {
+		async move {
+			loop {
+				tokio::select! {
+					result = self.a.try_fulfill() => match result {
+						Ok(value) => self.a_value = Some(value),
+						Err(FulfillError::Fulfill(_)) => continue,
+						Err(e) => return Err(e),
+					},
+					result = self.b.try_fulfill() => match result {
+						Ok(value) => self.b_value = Some(value),
+						Err(FulfillError::Fulfill(_)) => continue,
+						Err(e) => return Err(e),
+					},
+				}
+				if let (Some(a), Some(b)) = (&self.a_value, &self.b_value) {
+					self.state.set((self.combine)(a.clone(), b.clone())).await;
+				}
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::fulfill::custom::{Custom, CustomProcessor};
+	use kestrel_state::State;
+	use tokio::sync::mpsc::Receiver;
+	use tokio::time::Duration;
+
+	/// Parses the next line as a number if it starts with the given prefix.
+	struct Prefixed(&'static str);
+
+	impl CustomProcessor<u64> for Prefixed {
+		fn process_receiver(
+			&self,
+			receiver: &mut Receiver<String>,
+		) -> impl Future<Output = Result<Option<u64>, FulfillError>> + Send {
+			async move {
+				let line = receiver.recv().await.unwrap_or_default();
+				Ok(line.trim().strip_prefix(self.0).and_then(|value| value.parse().ok()))
+			}
+		}
+	}
+
+	#[tokio::test]
+	async fn test_zip() -> Result<(), anyhow::Error> {
+		let a = State::new();
+		let b = State::new();
+		let sum = State::new();
+
+		let zipped = zip(
+			sum.write(),
+			Custom::new(a.write(), Prefixed("a=")),
+			Custom::new(b.write(), Prefixed("b=")),
+			|a: u64, b: u64| a + b,
+		);
+
+		let sender = zipped.sender()?;
+		let handle = zipped.spawn()?;
+
+		sender.send("a=1\n".to_string()).await?;
+		sender.send("unrelated\n".to_string()).await?;
+		sender.send("b=2\n".to_string()).await?;
+
+		assert_eq!(handle.await??, 3);
+		assert_eq!(sum.read().get().await, Some(3));
+		assert_eq!(a.read().get().await, Some(1));
+		assert_eq!(b.read().get().await, Some(2));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_zip_continuous() -> Result<(), anyhow::Error> {
+		let sum = State::new();
+		let zipped = zip(
+			sum.write(),
+			Custom::new(State::new().write(), Prefixed("a=")),
+			Custom::new(State::new().write(), Prefixed("b=")),
+			|a: u64, b: u64| a + b,
+		);
+
+		let sender = zipped.sender()?;
+		let handle = zipped.spawn_continuous()?;
+		let reader = sum.read();
+
+		sender.send("a=1\n".to_string()).await?;
+		sender.send("b=2\n".to_string()).await?;
+		reader.wait_until(|sum| *sum == 3, Duration::from_secs(5)).await?;
+		sender.send("a=5\n".to_string()).await?;
+		reader.wait_until(|sum| *sum == 7, Duration::from_secs(5)).await?;
+		handle.abort();
+
+		Ok(())
+	}
+}
@@ -1,3 +1,6 @@
 pub mod fulfill;
 pub mod process;
-pub use process::cargo::RegisteredBin;
+pub mod ready;
+pub use process::cargo::{
+	prebuild, target_directory, workspace_bin_path, BinArgs, BuildTarget, RegisteredBin, TypedBin,
+};
@@ -0,0 +1,18 @@
+use crate::process::cargo::RegisteredBin;
+
+/// Typed arguments of a binary which serialize to command-line flags.
+///
+/// Usually derived with `#[derive(BinArgs)]`, which turns each field into a kebab-case flag:
+/// `bool` fields become switches, `Option` fields are skipped when `None`, and `Vec` fields
+/// repeat the flag for every value.
+pub trait BinArgs {
+	/// Serializes the arguments to command-line flags.
+	fn to_args(&self) -> Vec<String>;
+}
+
+/// A registered binary which declares its typed arguments.
+///
+/// `kestrelize!(args = MyArgs)` implements this alongside [RegisteredBin].
+pub trait TypedBin: RegisteredBin {
+	type Args: BinArgs;
+}
@@ -1,8 +1,19 @@
-use crate::process::{command::Command, Pipe, ProcessError, ProcessOperations};
+pub mod args;
+
+pub use args::{BinArgs, TypedBin};
+
+use crate::process::{
+	command::{Command, Source},
+	Pipe, ProcessError, ProcessOperations, ProcessOutput,
+};
+use cargo_metadata::{Metadata, MetadataCommand};
+use std::collections::{BTreeMap, HashMap};
 use std::ffi::OsStr;
 use std::future::Future;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
 use tokio::sync::mpsc::Sender;
+use tokio::sync::OnceCell;
 
 /// This trait ensures that the binary is imported from somewhere within the workspace.
 /// This has the neat side effect of ensuring that most of the binary is already built.
@@ -26,36 +37,86 @@ pub trait RegisteredBin {
 		}
 	}
 
+	/// Returns the Cargo package which contains the binary.
+	fn cargo_package() -> &'static str {
+		Self::cargo_bin()
+	}
+
+	/// Returns the features to enable when building the binary.
+	fn cargo_features() -> &'static [&'static str] {
+		&[]
+	}
+
+	/// Returns extra flags passed to `cargo build`, e.g. `--locked`.
+	fn cargo_build_flags() -> &'static [&'static str] {
+		&[]
+	}
+
+	/// Returns the environment variable which overrides the path of the binary.
+	///
+	/// e.g. a binary named `my-node` is overridden by `KESTREL_BIN_MY_NODE`.
+	fn path_override_var() -> String {
+		let name = Self::cargo_bin()
+			.chars()
+			.map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+			.collect::<String>();
+		format!("KESTREL_BIN_{}", name)
+	}
+
+	/// Returns the path of the binary from its override variable, if set.
+	fn path_override() -> Option<PathBuf> {
+		std::env::var_os(Self::path_override_var())
+			.filter(|path| !path.is_empty())
+			.map(PathBuf::from)
+	}
+
+	/// Returns the arguments to `cargo` which build only this binary.
+	fn build_args() -> Vec<String> {
+		let mut args = vec![
+			"build".to_string(),
+			"--package".to_string(),
+			Self::cargo_package().to_string(),
+			"--bin".to_string(),
+			Self::cargo_bin().to_string(),
+		];
+		if Self::debug_or_release() == "release" {
+			args.push("--release".to_string());
+		}
+		if !Self::cargo_features().is_empty() {
+			args.push("--features".to_string());
+			args.push(Self::cargo_features().join(","));
+		}
+		args.extend(Self::cargo_build_flags().iter().map(|flag| flag.to_string()));
+		args
+	}
+
 	/// Ensures the binary is built when inside a Cargo workspace.
+	///
+	/// Builds are deduplicated across the process: concurrent calls wait for a single build, and
+	/// later calls return immediately once it has succeeded. A failed or aborted build is retried
+	/// by the next caller. Aborting the task running the build also stops cargo and every compiler
+	/// and linker it spawned.
 	fn build() -> impl Future<Output = Result<(), ProcessError>> + Send {
 		async move {
 			if Self::is_in_cargo_workspace() {
-				commander::Command::line(
-					"cargo",
-					vec![
-						"build".to_string(),
-						if Self::debug_or_release() == "release" {
-							"--release".to_string()
-						} else {
-							"".to_string()
-						},
-					],
-					None,
-					false,
-					vec![], // No stdout senders
-					vec![], // No stderr senders
-				)
-				.run()
-				.await
-				.map_err(|e| ProcessError::Buildtime(e.into()))?;
+				let args = Self::build_args();
+				build_once(args.clone(), cargo_command(args)).await?;
 			}
 			Ok(())
 		}
 	}
 
 	/// Returns the binary path, handling workspace and standalone cases.
+	///
+	/// Inside a workspace, the path is resolved with [workspace_bin_path] so shared and overridden
+	/// target directories are found. If cargo metadata is unavailable, the target directory is
+	/// assumed to be next to the manifest.
 	fn cargo_bin_path() -> String {
 		if Self::is_in_cargo_workspace() {
+			if let Ok(path) = workspace_bin_path(Self::cargo_bin(), Self::debug_or_release()) {
+				return path.display().to_string();
+			}
+
 			let target_dir = format!(
 				"{}/target/{}/{}",
 				env!("CARGO_MANIFEST_DIR"),
@@ -70,6 +131,176 @@ pub trait RegisteredBin {
 	}
 }
 
+/// Returns the command which runs `cargo` with the given arguments.
+fn cargo_command(args: Vec<String>) -> commander::Command {
+	commander::Command::line(
+		"cargo",
+		args,
+		None,
+		false,
+		vec![], // No stdout senders
+		vec![], // No stderr senders
+	)
+}
+
+/// Runs a build unless the build with the same arguments has already succeeded in this process.
+///
+/// The build runs in its own process group, which is killed if the build is aborted, so the
+/// processes it spawned are stopped along with it.
+async fn build_once(
+	args: Vec<String>,
+	mut command: commander::Command,
+) -> Result<(), ProcessError> {
+	build_status(args)
+		.get_or_try_init(move || async move {
+			command
+				.process_group()
+				.run()
+				.await
+				.map(|_| ())
+				.map_err(|e| ProcessError::Buildtime(e.into()))
+		})
+		.await?;
+	Ok(())
+}
+
+/// Returns the process-wide build status for a cargo invocation.
+///
+/// Keying on the arguments distinguishes builds by package, binary, profile, and features.
+fn build_status(args: Vec<String>) -> Arc<OnceCell<()>> {
+	static BUILDS: OnceLock<Mutex<HashMap<Vec<String>, Arc<OnceCell<()>>>>> = OnceLock::new();
+	let mut builds = BUILDS.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+	builds.entry(args).or_default().clone()
+}
+
+/// Loads the metadata of the workspace containing the current directory once per process.
+fn workspace_metadata() -> Result<&'static Metadata, ProcessError> {
+	static METADATA: OnceLock<Result<Metadata, String>> = OnceLock::new();
+	METADATA
+		.get_or_init(|| MetadataCommand::new().no_deps().exec().map_err(|e| e.to_string()))
+		.as_ref()
+		.map_err(|e| ProcessError::Buildtime(e.clone().into()))
+}
+
+/// Returns the target directory of the current workspace.
+///
+/// Unlike guessing from the manifest directory, this honors `CARGO_TARGET_DIR` and Cargo config.
+pub fn target_directory() -> Result<PathBuf, ProcessError> {
+	Ok(workspace_metadata()?.target_directory.clone().into())
+}
+
+/// Returns the path of a binary built from any member of the current workspace with the given
+/// profile, i.e. `debug` or `release`.
+///
+/// Errors if no workspace member has a binary target with the given name.
+pub fn workspace_bin_path(bin: &str, profile: &str) -> Result<PathBuf, ProcessError> {
+	let metadata = workspace_metadata()?;
+	let found = metadata
+		.workspace_packages()
+		.iter()
+		.flat_map(|package| package.targets.iter())
+		.any(|target| target.is_bin() && target.name == bin);
+	if !found {
+		return Err(ProcessError::Buildtime(
+			format!("no workspace member has a binary named {}", bin).into(),
+		));
+	}
+
+	Ok(metadata.target_directory.join(profile).join(bin).into())
+}
+
+/// A registered binary to build with [prebuild]
+#[derive(Debug, Clone)]
+pub struct BuildTarget {
+	package: &'static str,
+	bin: &'static str,
+	features: &'static [&'static str],
+	flags: &'static [&'static str],
+	release: bool,
+	/// The arguments of the equivalent [RegisteredBin::build], which key its build status
+	build_args: Vec<String>,
+}
+
+impl BuildTarget {
+	/// Returns the target which builds the binary as [RegisteredBin::build] would.
+	pub fn of<B: RegisteredBin>() -> Self {
+		Self {
+			package: B::cargo_package(),
+			bin: B::cargo_bin(),
+			features: B::cargo_features(),
+			flags: B::cargo_build_flags(),
+			release: B::debug_or_release() == "release",
+			build_args: B::build_args(),
+		}
+	}
+}
+
+/// Returns the arguments to `cargo` which build several binaries in a single invocation.
+///
+/// Each binary is built from its package with its features. The targets must share a profile and
+/// build flags, which are taken from the first target.
+fn prebuild_args(targets: &[BuildTarget]) -> Vec<String> {
+	let mut args = vec!["build".to_string()];
+	let mut features = Vec::new();
+	for target in targets {
+		args.extend(["--package", target.package, "--bin", target.bin].map(String::from));
+		features.extend(
+			target.features.iter().map(|feature| format!("{}/{}", target.package, feature)),
+		);
+	}
+	if let Some(first) = targets.first() {
+		if first.release {
+			args.push("--release".to_string());
+		}
+	}
+	if !features.is_empty() {
+		args.push("--features".to_string());
+		args.push(features.join(","));
+	}
+	if let Some(first) = targets.first() {
+		args.extend(first.flags.iter().map(|flag| flag.to_string()));
+	}
+	args
+}
+
+/// Returns the command used to build several binaries in a single cargo invocation.
+///
+/// Each binary is built from its package with its features. The targets must share a profile and
+/// build flags, which are taken from the first target.
+pub fn prebuild_command(targets: &[BuildTarget]) -> commander::Command {
+	cargo_command(prebuild_args(targets))
+}
+
+/// Records the targets as built, so that [RegisteredBin::build] does not build them again.
+fn mark_built(targets: &[BuildTarget]) {
+	for target in targets {
+		// Fails only if the status is already set or a build is in progress
+		let _ = build_status(target.build_args.clone()).set(());
+	}
+}
+
+/// Builds the given workspace binaries with as few `cargo build` invocations as possible.
+///
+/// Cargo builds the binaries in parallel and checks shared dependencies once, which is much
+/// faster than letting each [Bin] build on its first run. Targets with the same profile and build
+/// flags share an invocation, and each binary built counts as built for [RegisteredBin::build].
+pub async fn prebuild(targets: &[BuildTarget]) -> Result<(), ProcessError> {
+	let mut groups: BTreeMap<(bool, &[&str]), Vec<BuildTarget>> = BTreeMap::new();
+	for target in targets {
+		groups.entry((target.release, target.flags)).or_default().push(target.clone());
+	}
+
+	for group in groups.values() {
+		prebuild_command(group)
+			.process_group()
+			.run()
+			.await
+			.map_err(|e| ProcessError::Buildtime(e.into()))?;
+		mark_built(group);
+	}
+	Ok(())
+}
+
 /// Runs a command on the command line and captures its output.
 pub struct Bin<B>
 where
@@ -77,6 +308,7 @@ where
 {
 	phantom: std::marker::PhantomData<B>,
 	runtime: Command,
+	build: bool,
 }
 
 impl<B> Bin<B>
@@ -107,7 +339,98 @@ where
 				stdout_senders,
 				stderr_senders,
 			),
+			build: true,
+		}
+	}
+
+	/// Runs the registered binary with the given arguments.
+	///
+	/// If the override variable from [RegisteredBin::path_override_var] is set, that binary runs
+	/// without building, e.g. to run against released artifacts in CI. Otherwise the binary is
+	/// built from the workspace and run from [RegisteredBin::cargo_bin_path].
+	pub fn new<I, S>(args: I) -> Self
+	where
+		I: IntoIterator<Item = S> + Send,
+		S: AsRef<OsStr>,
+	{
+		match B::path_override() {
+			Some(path) => Self::prebuilt(path, args),
+			None => Self::line(B::cargo_bin_path(), args, None, true, vec![], vec![]),
+		}
+	}
+
+	/// Runs the registered binary with its typed arguments serialized to flags.
+	///
+	/// The binary is resolved as in [Bin::new].
+	pub fn with_args(args: &B::Args) -> Self
+	where
+		B: TypedBin,
+	{
+		Self::new(args.to_args())
+	}
+
+	/// Runs a prebuilt binary at the given path instead of building from the workspace.
+	pub fn prebuilt<I, S>(path: impl AsRef<Path>, args: I) -> Self
+	where
+		I: IntoIterator<Item = S> + Send,
+		S: AsRef<OsStr>,
+	{
+		let mut bin = Self::line(path.as_ref().as_os_str(), args, None, true, vec![], vec![]);
+		bin.build = false;
+		bin
+	}
+
+	/// Builds the binary before it runs, unless it is prebuilt.
+	async fn build_if_needed(&self) -> Result<(), ProcessError> {
+		if self.build {
+			B::build().await?;
 		}
+		Ok(())
+	}
+
+	/// Sets an environment variable for the binary.
+	pub fn env<K, V>(&mut self, key: K, value: V) -> &mut Self
+	where
+		K: AsRef<OsStr>,
+		V: AsRef<OsStr>,
+	{
+		self.runtime.env(key, value);
+		self
+	}
+
+	/// Sets several environment variables for the binary.
+	pub fn envs<I, K, V>(&mut self, vars: I) -> &mut Self
+	where
+		I: IntoIterator<Item = (K, V)>,
+		K: AsRef<OsStr>,
+		V: AsRef<OsStr>,
+	{
+		self.runtime.envs(vars);
+		self
+	}
+
+	/// Sets whether a nonzero exit code is returned as output instead of an error.
+	pub fn allow_nonzero_exit(&mut self, allow: bool) -> &mut Self {
+		self.runtime.allow_nonzero_exit(allow);
+		self
+	}
+
+	/// Runs the binary in its own process group, so that its whole process tree can be killed.
+	pub fn process_group(&mut self) -> &mut Self {
+		self.runtime.process_group();
+		self
+	}
+
+	/// Clears the inherited environment of the binary.
+	pub fn env_clear(&mut self) -> &mut Self {
+		self.runtime.env_clear();
+		self
+	}
+
+	/// Feeds the standard input of the binary from a string, file, or channel.
+	pub fn stdin(&mut self, source: impl Into<Source>) -> &mut Self {
+		self.runtime.stdin(source);
+		self
 	}
 }
 
@@ -117,11 +440,27 @@ where
 {
 	fn run(self) -> impl std::future::Future<Output = Result<String, ProcessError>> + Send {
 		async move {
-			B::build().await?;
+			self.build_if_needed().await?;
 			self.runtime.run().await.map_err(|e| ProcessError::Runtime(e.into()))
 		}
 	}
 
+	fn run_with_output(
+		self,
+	) -> impl std::future::Future<Output = Result<ProcessOutput, ProcessError>> + Send {
+		async move {
+			self.build_if_needed().await?;
+			self.runtime.run_with_output().await
+		}
+	}
+
+	fn rerun(&mut self) -> impl std::future::Future<Output = Result<String, ProcessError>> + Send {
+		async move {
+			self.build_if_needed().await?;
+			self.runtime.rerun().await
+		}
+	}
+
 	fn pipe(
 		&mut self,
 		pipe: Pipe,
@@ -129,4 +468,246 @@ where
 	) -> Result<(), ProcessError> {
 		self.runtime.pipe(pipe, sender)
 	}
+
+	fn pid_sender(
+		&mut self,
+		sender: tokio::sync::oneshot::Sender<u32>,
+	) -> Result<(), ProcessError> {
+		ProcessOperations::pid_sender(&mut self.runtime, sender)
+	}
+
+	fn exit_sender(
+		&mut self,
+		sender: tokio::sync::watch::Sender<bool>,
+	) -> Result<(), ProcessError> {
+		ProcessOperations::exit_sender(&mut self.runtime, sender)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::process::ProcessOperations;
+	use std::path::PathBuf;
+	use tokio::time::{sleep, Duration};
+
+	fn pid_file() -> PathBuf {
+		std::env::temp_dir().join(format!("kestrel-build-abort-{}.pid", std::process::id()))
+	}
+
+	fn build_count_file() -> PathBuf {
+		std::env::temp_dir().join(format!("kestrel-build-dedup-{}.count", std::process::id()))
+	}
+
+	/// A build step which records each run and takes a while.
+	fn counted_build() -> commander::Command {
+		let script = format!("echo built >> {} && sleep 0.2", build_count_file().display());
+		commander::Command::line("sh", ["-c", script.as_str()], None, false, vec![], vec![])
+	}
+
+	/// Returns whether the process is alive, treating zombies as dead.
+	fn is_alive(pid: &str) -> bool {
+		let output = std::process::Command::new("ps")
+			.args(["-o", "stat=", "-p", pid])
+			.output()
+			.expect("failed to run ps");
+		let stat = String::from_utf8_lossy(&output.stdout);
+		let stat = stat.trim();
+		!stat.is_empty() && !stat.starts_with('Z')
+	}
+
+	/// A binary in another package, built with extra features and flags.
+	struct Configured;
+
+	impl RegisteredBin for Configured {
+		fn cargo_bin() -> &'static str {
+			"node"
+		}
+
+		fn cargo_package() -> &'static str {
+			"node-service"
+		}
+
+		fn cargo_features() -> &'static [&'static str] {
+			&["metrics", "tracing"]
+		}
+
+		fn cargo_build_flags() -> &'static [&'static str] {
+			&["--locked"]
+		}
+	}
+
+	#[test]
+	fn test_build_args() {
+		let mut expected = vec!["build", "--package", "node-service", "--bin", "node"];
+		if Configured::debug_or_release() == "release" {
+			expected.push("--release");
+		}
+		expected.extend(["--features", "metrics,tracing", "--locked"]);
+
+		assert_eq!(Configured::build_args(), expected);
+	}
+
+	#[test]
+	fn test_workspace_bin_path() -> Result<(), anyhow::Error> {
+		let target_directory = target_directory()?;
+		assert!(target_directory.is_absolute());
+
+		let result = workspace_bin_path("kestrel-no-such-bin", "debug");
+		assert!(matches!(result, Err(ProcessError::Buildtime(_))));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_prebuild() -> Result<(), anyhow::Error> {
+		// Nothing to build
+		prebuild(&[]).await?;
+
+		let result = prebuild(&[BuildTarget::of::<Unbuildable>()]).await;
+		assert!(matches!(result, Err(ProcessError::Buildtime(_))));
+
+		Ok(())
+	}
+
+	/// A binary in the same package as [Configured], built with the same flags.
+	struct Sibling;
+
+	impl RegisteredBin for Sibling {
+		fn cargo_bin() -> &'static str {
+			"node-cli"
+		}
+
+		fn cargo_package() -> &'static str {
+			"node-service"
+		}
+
+		fn cargo_build_flags() -> &'static [&'static str] {
+			&["--locked"]
+		}
+	}
+
+	#[test]
+	fn test_prebuild_args() {
+		let targets = [BuildTarget::of::<Configured>(), BuildTarget::of::<Sibling>()];
+
+		let mut expected = vec![
+			"build",
+			"--package",
+			"node-service",
+			"--bin",
+			"node",
+			"--package",
+			"node-service",
+			"--bin",
+			"node-cli",
+		];
+		if Configured::debug_or_release() == "release" {
+			expected.push("--release");
+		}
+		expected.extend(["--features", "node-service/metrics,node-service/tracing", "--locked"]);
+
+		assert_eq!(prebuild_args(&targets), expected);
+	}
+
+	/// A binary which is built through a prebuild.
+	struct Prebuilt;
+
+	impl RegisteredBin for Prebuilt {
+		fn cargo_bin() -> &'static str {
+			"kestrel-prebuilt"
+		}
+	}
+
+	#[tokio::test]
+	async fn test_prebuild_counts_as_built() -> Result<(), anyhow::Error> {
+		mark_built(&[BuildTarget::of::<Prebuilt>()]);
+
+		// The build is skipped, so a failing build command does not run
+		let failing =
+			commander::Command::line("false", Vec::<&str>::new(), None, false, vec![], vec![]);
+		build_once(Prebuilt::build_args(), failing).await?;
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_concurrent_builds_are_deduplicated() -> Result<(), anyhow::Error> {
+		let _ = std::fs::remove_file(build_count_file());
+
+		let args = vec!["kestrel-build-dedup".to_string()];
+		let builds = (0..5).map(|_| tokio::spawn(build_once(args.clone(), counted_build())));
+		for build in futures::future::join_all(builds).await {
+			build??;
+		}
+		// Already built, so this does not run the build again
+		build_once(args, counted_build()).await?;
+
+		let count = std::fs::read_to_string(build_count_file())?;
+		let _ = std::fs::remove_file(build_count_file());
+		assert_eq!(count.lines().count(), 1);
+
+		Ok(())
+	}
+
+	/// A binary which is not in the workspace, so it can only run prebuilt.
+	struct Unbuildable;
+
+	impl RegisteredBin for Unbuildable {
+		fn cargo_bin() -> &'static str {
+			"kestrel-unbuildable"
+		}
+	}
+
+	#[tokio::test]
+	async fn test_prebuilt_bin_skips_build() -> Result<(), anyhow::Error> {
+		let output = Bin::<Unbuildable>::prebuilt("echo", ["prebuilt"]).run().await?;
+		assert_eq!(output, "prebuilt\n");
+
+		assert_eq!(Unbuildable::path_override_var(), "KESTREL_BIN_KESTREL_UNBUILDABLE");
+		std::env::set_var(Unbuildable::path_override_var(), "echo");
+		let output = Bin::<Unbuildable>::new(["overridden"]).run().await;
+		std::env::remove_var(Unbuildable::path_override_var());
+		assert_eq!(output?, "overridden\n");
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_abort_during_build_stops_build() -> Result<(), anyhow::Error> {
+		let _ = std::fs::remove_file(pid_file());
+
+		// The build step forks a child which would outlive a kill of the build step alone
+		let script = format!("sleep 30 & echo $! > {} && wait", pid_file().display());
+		let command =
+			commander::Command::line("sh", ["-c", script.as_str()], None, false, vec![], vec![]);
+		let handle = tokio::spawn(build_once(vec!["kestrel-build-abort".to_string()], command));
+
+		// Wait for the child to start
+		let pid = loop {
+			if let Ok(pid) = std::fs::read_to_string(pid_file()) {
+				if !pid.trim().is_empty() {
+					break pid.trim().to_string();
+				}
+			}
+			sleep(Duration::from_millis(10)).await;
+		};
+		assert!(is_alive(&pid));
+
+		handle.abort();
+		let _ = handle.await;
+
+		let mut alive = true;
+		for _ in 0..100 {
+			alive = is_alive(&pid);
+			if !alive {
+				break;
+			}
+			sleep(Duration::from_millis(10)).await;
+		}
+		let _ = std::fs::remove_file(pid_file());
+		assert!(!alive, "build child {} is still running after abort", pid);
+
+		Ok(())
+	}
 }
@@ -1,9 +1,11 @@
-use crate::process::{Pipe, ProcessError, ProcessOperations};
+use crate::process::{Pipe, ProcessError, ProcessOperations, ProcessOutput};
 use commander::Command as InnerCommand;
 use std::ffi::OsStr;
 use std::path::Path;
 use tokio::sync::mpsc::Sender;
 
+pub use commander::Source;
+
 /// Runs a command on the command line and captures its output.
 pub struct Command {
 	inner: InnerCommand,
@@ -47,6 +49,18 @@ impl Command {
 		self
 	}
 
+	/// Sets whether a nonzero exit code is returned as output instead of an error.
+	pub fn allow_nonzero_exit(&mut self, allow: bool) -> &mut Self {
+		self.inner.allow_nonzero_exit(allow);
+		self
+	}
+
+	/// Sets the capacity of the buffers used to read and write the command's output.
+	pub fn buffer_size(&mut self, bytes: usize) -> &mut Self {
+		self.inner.buffer_size(bytes);
+		self
+	}
+
 	/// Appends a sender for the standard output of the command.
 	pub fn append_stdout(&mut self, sender: Sender<String>) -> &mut Self {
 		self.inner.append_stdout(sender);
@@ -59,6 +73,57 @@ impl Command {
 		self
 	}
 
+	/// Sends the OS process id of the command once it has been spawned.
+	pub fn pid_sender(&mut self, sender: tokio::sync::oneshot::Sender<u32>) -> &mut Self {
+		self.inner.pid_sender(sender);
+		self
+	}
+
+	/// Reports when the command has exited and been reaped, see [commander::Command::exit_sender].
+	pub fn exit_sender(&mut self, sender: tokio::sync::watch::Sender<bool>) -> &mut Self {
+		self.inner.exit_sender(sender);
+		self
+	}
+
+	/// Sets an environment variable for the command.
+	pub fn env<K, V>(&mut self, key: K, value: V) -> &mut Self
+	where
+		K: AsRef<OsStr>,
+		V: AsRef<OsStr>,
+	{
+		self.inner.env(key, value);
+		self
+	}
+
+	/// Sets several environment variables for the command.
+	pub fn envs<I, K, V>(&mut self, vars: I) -> &mut Self
+	where
+		I: IntoIterator<Item = (K, V)>,
+		K: AsRef<OsStr>,
+		V: AsRef<OsStr>,
+	{
+		self.inner.envs(vars);
+		self
+	}
+
+	/// Runs the command in its own process group, so that its whole process tree can be killed.
+	pub fn process_group(&mut self) -> &mut Self {
+		self.inner.process_group();
+		self
+	}
+
+	/// Clears the inherited environment of the command.
+	pub fn env_clear(&mut self) -> &mut Self {
+		self.inner.env_clear();
+		self
+	}
+
+	/// Feeds the standard input of the command from a string, file, or channel.
+	pub fn stdin(&mut self, source: impl Into<Source>) -> &mut Self {
+		self.inner.stdin(source);
+		self
+	}
+
 	/// Working directory of the command.
 	pub fn get_current_dir(&self) -> Option<&Path> {
 		self.inner.get_current_dir()
@@ -67,6 +132,19 @@ impl Command {
 
 impl ProcessOperations for Command {
 	fn run(mut self) -> impl std::future::Future<Output = Result<String, ProcessError>> + Send {
+		async move { self.rerun().await }
+	}
+
+	fn run_with_output(
+		mut self,
+	) -> impl std::future::Future<Output = Result<ProcessOutput, ProcessError>> + Send {
+		async move {
+			let output = self.inner.run_with_output().await;
+			output.map(ProcessOutput::from).map_err(|e| ProcessError::Runtime(e.into()))
+		}
+	}
+
+	fn rerun(&mut self) -> impl std::future::Future<Output = Result<String, ProcessError>> + Send {
 		async move { self.inner.run().await.map_err(|e| ProcessError::Runtime(e.into())) }
 	}
 
@@ -86,4 +164,20 @@ impl ProcessOperations for Command {
 			}
 		}
 	}
+
+	fn pid_sender(
+		&mut self,
+		sender: tokio::sync::oneshot::Sender<u32>,
+	) -> Result<(), ProcessError> {
+		Command::pid_sender(self, sender);
+		Ok(())
+	}
+
+	fn exit_sender(
+		&mut self,
+		sender: tokio::sync::watch::Sender<bool>,
+	) -> Result<(), ProcessError> {
+		Command::exit_sender(self, sender);
+		Ok(())
+	}
 }
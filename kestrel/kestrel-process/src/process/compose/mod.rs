@@ -0,0 +1,209 @@
+use crate::process::{Pipe, ProcessError, ProcessOperations};
+use kestrel_state::{ReadOnlyState, State};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::mpsc::Sender;
+
+static NEXT_PROJECT: AtomicU64 = AtomicU64::new(0);
+
+/// The senders attached to the logs of a single service.
+#[derive(Default)]
+struct ServicePipes {
+	stdout: Vec<Sender<String>>,
+	stderr: Vec<Sender<String>>,
+}
+
+/// Brings up a docker compose stack and streams its logs until every service has stopped.
+///
+/// The run waits for all services to be running and healthy before following their logs. The
+/// stack is torn down with its volumes once the services stop, or when the run is aborted.
+pub struct Compose {
+	file: PathBuf,
+	project: String,
+	capture_output: bool,
+	stdout_senders: Vec<Sender<String>>,
+	stderr_senders: Vec<Sender<String>>,
+	services: HashMap<String, ServicePipes>,
+	healthy: State<()>,
+}
+
+impl Compose {
+	/// Creates a new Compose for the given file under a unique project name.
+	pub fn new(file: impl Into<PathBuf>) -> Self {
+		let id = NEXT_PROJECT.fetch_add(1, Ordering::Relaxed);
+		Self {
+			file: file.into(),
+			project: format!("kestrel-{}-{}", std::process::id(), id),
+			capture_output: false,
+			stdout_senders: Vec::new(),
+			stderr_senders: Vec::new(),
+			services: HashMap::new(),
+			healthy: State::new(),
+		}
+	}
+
+	/// Sets the compose project name.
+	pub fn project(&mut self, project: impl Into<String>) -> &mut Self {
+		self.project = project.into();
+		self
+	}
+
+	/// Gets the compose project name.
+	pub fn project_name(&self) -> &str {
+		&self.project
+	}
+
+	/// Sets whether to capture the combined logs of the stack.
+	pub fn set_capture_output(&mut self, capture_output: bool) -> &mut Self {
+		self.capture_output = capture_output;
+		self
+	}
+
+	/// Attaches a pipe to the logs of a single service, without the service name prefix.
+	pub fn service_pipe(
+		&mut self,
+		service: impl Into<String>,
+		pipe: Pipe,
+		sender: Sender<String>,
+	) -> &mut Self {
+		let pipes = self.services.entry(service.into()).or_default();
+		match pipe {
+			Pipe::STDOUT => pipes.stdout.push(sender),
+			Pipe::STDERR => pipes.stderr.push(sender),
+		}
+		self
+	}
+
+	/// Returns a state which is set once every service is running and healthy.
+	pub fn healthy(&self) -> ReadOnlyState<()> {
+		self.healthy.read()
+	}
+
+	/// Returns a `docker compose` command for this project.
+	fn command<I, S>(&self, args: I) -> commander::Command
+	where
+		I: IntoIterator<Item = S>,
+		S: AsRef<std::ffi::OsStr>,
+	{
+		compose_command(&self.file, &self.project, args)
+	}
+}
+
+fn compose_command<I, S>(file: &Path, project: &str, args: I) -> commander::Command
+where
+	I: IntoIterator<Item = S>,
+	S: AsRef<std::ffi::OsStr>,
+{
+	let mut command = commander::Command::new("docker", false, vec![], vec![]);
+	command
+		.arg("compose")
+		.arg("--file")
+		.arg(file)
+		.arg("--project-name")
+		.arg(project)
+		.args(args);
+	command
+}
+
+impl ProcessOperations for Compose {
+	fn run(mut self) -> impl std::future::Future<Output = Result<String, ProcessError>> + Send {
+		async move {
+			let mut teardown =
+				Teardown { file: self.file.clone(), project: Some(self.project.clone()) };
+			self.command(["up", "--detach", "--wait"])
+				.run()
+				.await
+				.map_err(|e| ProcessError::Runtime(e.into()))?;
+			self.healthy.write().set(()).await;
+
+			let services = std::mem::take(&mut self.services);
+			let service_logs = services.into_iter().map(|(service, pipes)| {
+				let mut logs =
+					self.command(["logs", "--follow", "--no-log-prefix", service.as_str()]);
+				for sender in pipes.stdout {
+					logs.append_stdout(sender);
+				}
+				for sender in pipes.stderr {
+					logs.append_stderr(sender);
+				}
+				async move { logs.run().await }
+			});
+			let service_logs: Vec<_> = service_logs.collect();
+
+			let mut logs = self.command(["logs", "--follow"]);
+			logs.set_capture_output(self.capture_output);
+			for sender in self.stdout_senders.drain(..) {
+				logs.append_stdout(sender);
+			}
+			for sender in self.stderr_senders.drain(..) {
+				logs.append_stderr(sender);
+			}
+
+			let (result, _) = tokio::join!(logs.run(), futures::future::join_all(service_logs));
+			teardown.down().await;
+
+			result.map_err(|e| ProcessError::Runtime(e.into()))
+		}
+	}
+
+	fn pipe(&mut self, pipe: Pipe, sender: Sender<String>) -> Result<(), ProcessError> {
+		match pipe {
+			Pipe::STDOUT => self.stdout_senders.push(sender),
+			Pipe::STDERR => self.stderr_senders.push(sender),
+		}
+		Ok(())
+	}
+}
+
+/// Tears the stack down when dropped, so aborting the run does not leak containers.
+struct Teardown {
+	file: PathBuf,
+	project: Option<String>,
+}
+
+impl Teardown {
+	fn args() -> [&'static str; 3] {
+		["down", "--volumes", "--remove-orphans"]
+	}
+
+	async fn down(&mut self) {
+		if let Some(project) = self.project.take() {
+			let _ = compose_command(&self.file, &project, Self::args()).run().await;
+		}
+	}
+}
+
+impl Drop for Teardown {
+	fn drop(&mut self) {
+		if let Some(project) = self.project.take() {
+			// The runtime may be shutting down, so tear down from a plain thread
+			let file = self.file.clone();
+			std::thread::spawn(move || {
+				let _ = std::process::Command::new("docker")
+					.arg("compose")
+					.arg("--file")
+					.arg(&file)
+					.arg("--project-name")
+					.arg(&project)
+					.args(Self::args())
+					.status();
+			});
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_unique_project_names() {
+		let first = Compose::new("docker-compose.yml");
+		let mut second = Compose::new("docker-compose.yml");
+		assert_ne!(first.project_name(), second.project_name());
+
+		second.project("my-stack");
+		assert_eq!(second.project_name(), "my-stack");
+	}
+}
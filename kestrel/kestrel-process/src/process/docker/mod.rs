@@ -0,0 +1,354 @@
+use crate::process::{Pipe, ProcessError, ProcessOperations, ProcessOutput};
+use bollard::container::{
+	Config, CreateContainerOptions, LogOutput, LogsOptions, RemoveContainerOptions,
+	StartContainerOptions, WaitContainerOptions,
+};
+use bollard::image::{CreateImageOptions, ListImagesOptions};
+use bollard::models::{HostConfig, PortBinding};
+use bollard::Docker;
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::path::Path;
+use tokio::sync::mpsc::Sender;
+use tokio::time::Instant;
+
+/// Runs a command inside a Docker container and streams its logs.
+///
+/// The image is pulled if it does not exist locally. The container is removed once it exits, or
+/// when the task running it is aborted.
+pub struct Container {
+	image: String,
+	command: Vec<String>,
+	env: Vec<String>,
+	mounts: Vec<String>,
+	ports: Vec<(u16, u16)>,
+	name: Option<String>,
+	capture_output: bool,
+	stdout_senders: Vec<Sender<String>>,
+	stderr_senders: Vec<Sender<String>>,
+}
+
+impl Container {
+	/// Creates a new Container running the default command of the image.
+	pub fn new(image: impl Into<String>) -> Self {
+		Self {
+			image: image.into(),
+			command: Vec::new(),
+			env: Vec::new(),
+			mounts: Vec::new(),
+			ports: Vec::new(),
+			name: None,
+			capture_output: true,
+			stdout_senders: Vec::new(),
+			stderr_senders: Vec::new(),
+		}
+	}
+
+	/// Overrides the command of the image.
+	pub fn command<I, S>(&mut self, command: I) -> &mut Self
+	where
+		I: IntoIterator<Item = S>,
+		S: Into<String>,
+	{
+		self.command = command.into_iter().map(Into::into).collect();
+		self
+	}
+
+	/// Sets an environment variable inside the container.
+	pub fn env(&mut self, key: impl AsRef<str>, value: impl AsRef<str>) -> &mut Self {
+		self.env.push(format!("{}={}", key.as_ref(), value.as_ref()));
+		self
+	}
+
+	/// Mounts a host path into the container.
+	pub fn mount(&mut self, host: impl AsRef<Path>, container: impl AsRef<str>) -> &mut Self {
+		self.mounts.push(format!("{}:{}", host.as_ref().display(), container.as_ref()));
+		self
+	}
+
+	/// Publishes a TCP port of the container on a port of the host.
+	pub fn port(&mut self, host: u16, container: u16) -> &mut Self {
+		self.ports.push((host, container));
+		self
+	}
+
+	/// Sets the name of the container.
+	pub fn name(&mut self, name: impl Into<String>) -> &mut Self {
+		self.name = Some(name.into());
+		self
+	}
+
+	/// Sets whether to capture the output of the container.
+	pub fn set_capture_output(&mut self, capture_output: bool) -> &mut Self {
+		self.capture_output = capture_output;
+		self
+	}
+
+	/// Appends a sender for the standard output of the container.
+	pub fn append_stdout(&mut self, sender: Sender<String>) -> &mut Self {
+		self.stdout_senders.push(sender);
+		self
+	}
+
+	/// Appends a sender for the standard error of the container.
+	pub fn append_stderr(&mut self, sender: Sender<String>) -> &mut Self {
+		self.stderr_senders.push(sender);
+		self
+	}
+
+	/// Builds the configuration the container is created with.
+	fn config(&self) -> Config<String> {
+		let mut bindings: HashMap<String, Vec<PortBinding>> = HashMap::new();
+		for (host, container) in &self.ports {
+			let binding = PortBinding { host_ip: None, host_port: Some(host.to_string()) };
+			bindings.entry(format!("{}/tcp", container)).or_default().push(binding);
+		}
+		let exposed_ports = bindings.keys().map(|port| (port.clone(), HashMap::new())).collect();
+		let port_bindings =
+			bindings.into_iter().map(|(port, bindings)| (port, Some(bindings))).collect();
+
+		Config {
+			image: Some(self.image.clone()),
+			cmd: (!self.command.is_empty()).then(|| self.command.clone()),
+			env: Some(self.env.clone()),
+			exposed_ports: Some(exposed_ports),
+			host_config: Some(HostConfig {
+				binds: Some(self.mounts.clone()),
+				port_bindings: Some(port_bindings),
+				..Default::default()
+			}),
+			..Default::default()
+		}
+	}
+
+	/// Pulls the image if it does not exist locally.
+	async fn ensure_image(&self, docker: &Docker) -> Result<(), ProcessError> {
+		let mut filters = HashMap::new();
+		filters.insert("reference", vec![self.image.as_str()]);
+		let options = ListImagesOptions { filters, ..Default::default() };
+		let images = docker
+			.list_images(Some(options))
+			.await
+			.map_err(|e| ProcessError::Buildtime(e.into()))?;
+		if !images.is_empty() {
+			return Ok(());
+		}
+
+		let options = CreateImageOptions { from_image: self.image.as_str(), ..Default::default() };
+		let mut stream = docker.create_image(Some(options), None, None);
+		while let Some(message) = stream.next().await {
+			message.map_err(|e| ProcessError::Buildtime(e.into()))?;
+		}
+		Ok(())
+	}
+
+	/// Runs the container until it exits, streaming its logs, regardless of its exit code.
+	async fn run_to_completion(&mut self) -> Result<ProcessOutput, ProcessError> {
+		let docker =
+			Docker::connect_with_local_defaults().map_err(|e| ProcessError::Runtime(e.into()))?;
+		self.ensure_image(&docker).await?;
+
+		let options = self
+			.name
+			.as_ref()
+			.map(|name| CreateContainerOptions { name: name.clone(), ..Default::default() });
+		let created = docker
+			.create_container(options, self.config())
+			.await
+			.map_err(|e| ProcessError::Runtime(e.into()))?;
+		let mut guard = ContainerGuard { docker: docker.clone(), id: Some(created.id.clone()) };
+
+		let started = Instant::now();
+		docker
+			.start_container(&created.id, None::<StartContainerOptions<String>>)
+			.await
+			.map_err(|e| ProcessError::Runtime(e.into()))?;
+
+		let mut stdout = LineBuffer::default();
+		let mut stderr = LineBuffer::default();
+		let options = LogsOptions::<String> {
+			follow: true,
+			stdout: true,
+			stderr: true,
+			..Default::default()
+		};
+		let mut logs = docker.logs(&created.id, Some(options));
+		while let Some(log) = logs.next().await {
+			match log.map_err(|e| ProcessError::Runtime(e.into()))? {
+				LogOutput::StdOut { message } | LogOutput::Console { message } => {
+					stdout.push(&message, &self.stdout_senders, self.capture_output).await;
+				}
+				LogOutput::StdErr { message } => {
+					stderr.push(&message, &self.stderr_senders, self.capture_output).await;
+				}
+				LogOutput::StdIn { .. } => {}
+			}
+		}
+		stdout.flush(&self.stdout_senders, self.capture_output).await;
+		stderr.flush(&self.stderr_senders, self.capture_output).await;
+
+		let options = WaitContainerOptions { condition: "not-running" };
+		let mut wait = docker.wait_container(&created.id, Some(options));
+		let exit_code = match wait.next().await {
+			Some(Ok(response)) => response.status_code,
+			Some(Err(bollard::errors::Error::DockerContainerWaitError { code, .. })) => code,
+			Some(Err(e)) => return Err(ProcessError::Runtime(e.into())),
+			None => return Err(ProcessError::Runtime("container wait ended early".into())),
+		};
+		let duration = started.elapsed();
+		guard.remove().await;
+
+		Ok(ProcessOutput {
+			exit_code: i32::try_from(exit_code).ok(),
+			stdout: stdout.output,
+			stderr: stderr.output,
+			duration,
+		})
+	}
+}
+
+impl ProcessOperations for Container {
+	fn run(mut self) -> impl std::future::Future<Output = Result<String, ProcessError>> + Send {
+		async move { self.rerun().await }
+	}
+
+	fn run_with_output(
+		mut self,
+	) -> impl std::future::Future<Output = Result<ProcessOutput, ProcessError>> + Send {
+		async move { self.run_to_completion().await }
+	}
+
+	fn rerun(&mut self) -> impl std::future::Future<Output = Result<String, ProcessError>> + Send {
+		async move {
+			let output = self.run_to_completion().await?;
+			if output.exit_code != Some(0) {
+				return Err(ProcessError::Runtime(
+					format!(
+						"container {} exited with code {:?}\nError  {}",
+						self.image, output.exit_code, output.stderr
+					)
+					.into(),
+				));
+			}
+			Ok(output.stdout)
+		}
+	}
+
+	fn pipe(&mut self, pipe: Pipe, sender: Sender<String>) -> Result<(), ProcessError> {
+		match pipe {
+			Pipe::STDOUT => {
+				self.append_stdout(sender);
+				Ok(())
+			}
+			Pipe::STDERR => {
+				self.append_stderr(sender);
+				Ok(())
+			}
+		}
+	}
+}
+
+/// Splits log chunks into lines, which Docker does not guarantee chunks to align with.
+#[derive(Default)]
+struct LineBuffer {
+	partial: String,
+	output: String,
+}
+
+impl LineBuffer {
+	async fn push(&mut self, chunk: &[u8], senders: &[Sender<String>], capture_output: bool) {
+		self.partial.push_str(&String::from_utf8_lossy(chunk));
+		while let Some(index) = self.partial.find('\n') {
+			let line: String = self.partial.drain(..=index).collect();
+			self.send(line, senders, capture_output).await;
+		}
+	}
+
+	async fn flush(&mut self, senders: &[Sender<String>], capture_output: bool) {
+		if !self.partial.is_empty() {
+			let line = format!("{}\n", std::mem::take(&mut self.partial));
+			self.send(line, senders, capture_output).await;
+		}
+	}
+
+	async fn send(&mut self, line: String, senders: &[Sender<String>], capture_output: bool) {
+		for sender in senders {
+			let _ = sender.send(line.clone()).await;
+		}
+		if capture_output {
+			self.output.push_str(&line);
+		}
+	}
+}
+
+/// Removes the container when dropped, so aborting the run does not leak it.
+struct ContainerGuard {
+	docker: Docker,
+	id: Option<String>,
+}
+
+impl ContainerGuard {
+	async fn remove(&mut self) {
+		if let Some(id) = self.id.take() {
+			let _ = self.docker.remove_container(&id, Some(force_remove())).await;
+		}
+	}
+}
+
+impl Drop for ContainerGuard {
+	fn drop(&mut self) {
+		if let (Some(id), Ok(handle)) = (self.id.take(), tokio::runtime::Handle::try_current()) {
+			let docker = self.docker.clone();
+			handle.spawn(async move {
+				let _ = docker.remove_container(&id, Some(force_remove())).await;
+			});
+		}
+	}
+}
+
+fn force_remove() -> RemoveContainerOptions {
+	RemoveContainerOptions { force: true, ..Default::default() }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_container_config() {
+		let mut container = Container::new("alpine:3.19");
+		container
+			.command(["sh", "-c", "echo hello"])
+			.env("RUST_LOG", "info")
+			.mount("/tmp/data", "/data")
+			.port(30731, 8080);
+		let config = container.config();
+
+		assert_eq!(config.image.as_deref(), Some("alpine:3.19"));
+		assert_eq!(config.cmd, Some(vec!["sh".into(), "-c".into(), "echo hello".into()]));
+		assert_eq!(config.env, Some(vec!["RUST_LOG=info".to_string()]));
+		assert!(config.exposed_ports.unwrap_or_default().contains_key("8080/tcp"));
+
+		let host_config = config.host_config.unwrap_or_default();
+		assert_eq!(host_config.binds, Some(vec!["/tmp/data:/data".to_string()]));
+		let bindings = host_config.port_bindings.unwrap_or_default();
+		let binding = &bindings["8080/tcp"].as_ref().unwrap()[0];
+		assert_eq!(binding.host_port.as_deref(), Some("30731"));
+	}
+
+	#[tokio::test]
+	async fn test_line_buffer_splits_chunks() {
+		let (sender, mut receiver) = tokio::sync::mpsc::channel(10);
+		let senders = vec![sender];
+		let mut buffer = LineBuffer::default();
+
+		buffer.push(b"first\nsec", &senders, true).await;
+		buffer.push(b"ond\nthird", &senders, true).await;
+		buffer.flush(&senders, true).await;
+
+		assert_eq!(receiver.recv().await.as_deref(), Some("first\n"));
+		assert_eq!(receiver.recv().await.as_deref(), Some("second\n"));
+		assert_eq!(receiver.recv().await.as_deref(), Some("third\n"));
+		assert_eq!(buffer.output, "first\nsecond\nthird\n");
+	}
+}
@@ -0,0 +1,325 @@
+use crate::process::ProcessError;
+use tokio::sync::{oneshot, watch};
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+
+/// A signal which can be sent to a running process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+	Interrupt,
+	Terminate,
+	Kill,
+	Hangup,
+}
+
+impl Signal {
+	fn as_raw(self) -> libc::c_int {
+		match self {
+			Signal::Interrupt => libc::SIGINT,
+			Signal::Terminate => libc::SIGTERM,
+			Signal::Kill => libc::SIGKILL,
+			Signal::Hangup => libc::SIGHUP,
+		}
+	}
+}
+
+/// A process running in the background with access to the underlying OS child.
+///
+/// Like a kestrel task, the process is killed when the handle is dropped without being waited on.
+pub struct ProcessHandle {
+	pid: Option<u32>,
+	pid_receiver: Option<oneshot::Receiver<u32>>,
+	/// Set once the child has been reaped, or closed once it can no longer be signalled
+	exit_receiver: watch::Receiver<bool>,
+	join_handle: Option<JoinHandle<Result<String, ProcessError>>>,
+}
+
+impl ProcessHandle {
+	/// Creates a new ProcessHandle from the pid and exit receivers and the task running the
+	/// process.
+	pub fn new(
+		pid_receiver: oneshot::Receiver<u32>,
+		exit_receiver: watch::Receiver<bool>,
+		join_handle: JoinHandle<Result<String, ProcessError>>,
+	) -> Self {
+		Self {
+			pid: None,
+			pid_receiver: Some(pid_receiver),
+			exit_receiver,
+			join_handle: Some(join_handle),
+		}
+	}
+
+	/// Gets the OS process id, waiting for the process to be spawned.
+	///
+	/// Processes which build before running, like [crate::process::cargo::Bin], only report their
+	/// pid once the build has finished.
+	pub async fn pid(&mut self) -> Result<u32, ProcessError> {
+		if let Some(pid) = self.pid {
+			return Ok(pid);
+		}
+
+		let receiver = self.pid_receiver.take().ok_or_else(|| {
+			ProcessError::Signal("process exited before reporting its pid".into())
+		})?;
+		let pid = receiver
+			.await
+			.map_err(|_| ProcessError::Signal("process exited before reporting its pid".into()))?;
+		self.pid = Some(pid);
+
+		Ok(pid)
+	}
+
+	/// Returns whether the process is still running, i.e. has not been reaped.
+	fn is_running(&self) -> bool {
+		!*self.exit_receiver.borrow() && self.exit_receiver.has_changed().is_ok()
+	}
+
+	/// Fails if the process has exited, as its pid may since have been reused by another process.
+	fn ensure_running(&self) -> Result<(), ProcessError> {
+		if !self.is_running() {
+			return Err(ProcessError::Signal("process has already exited".into()));
+		}
+		Ok(())
+	}
+
+	/// Sends a signal to the process.
+	///
+	/// Fails without signalling anything if the process has already exited.
+	pub async fn kill(&mut self, signal: Signal) -> Result<(), ProcessError> {
+		let pid = self.pid().await?;
+		self.ensure_running()?;
+		let pid = libc::pid_t::try_from(pid).map_err(|e| ProcessError::Signal(e.into()))?;
+
+		// SAFETY: kill has no memory safety requirements
+		if unsafe { libc::kill(pid, signal.as_raw()) } != 0 {
+			return Err(ProcessError::Signal(std::io::Error::last_os_error().into()));
+		}
+
+		Ok(())
+	}
+
+	/// Sends SIGKILL to the process and every process it spawned.
+	///
+	/// The process must have been started in its own process group, e.g. with
+	/// [crate::process::command::Command::process_group]. This then cleans up forked workers
+	/// which [ProcessHandle::kill] would leave running. Fails if the process has already exited.
+	pub async fn kill_process_tree(&mut self) -> Result<(), ProcessError> {
+		let pid = self.pid().await?;
+		self.ensure_running()?;
+		commander::kill_process_tree(pid).map_err(|e| ProcessError::Signal(e.into()))
+	}
+
+	/// Waits for the process to exit for up to the given timeout.
+	///
+	/// Returns `Ok(None)` if the process is still running, so the handle can be waited on again.
+	pub async fn wait_with_timeout(
+		&mut self,
+		timeout: Duration,
+	) -> Result<Option<String>, ProcessError> {
+		let join_handle = self
+			.join_handle
+			.as_mut()
+			.ok_or_else(|| ProcessError::Runtime("process has already been waited on".into()))?;
+
+		match tokio::time::timeout(timeout, join_handle).await {
+			Ok(result) => {
+				self.join_handle = None;
+				result.map_err(|e| ProcessError::Runtime(e.into()))?.map(Some)
+			}
+			Err(_) => Ok(None),
+		}
+	}
+
+	/// Waits for the process to exit.
+	pub async fn wait(mut self) -> Result<String, ProcessError> {
+		let join_handle = self
+			.join_handle
+			.take()
+			.ok_or_else(|| ProcessError::Runtime("process has already been waited on".into()))?;
+
+		join_handle.await.map_err(|e| ProcessError::Runtime(e.into()))?
+	}
+
+	/// Sends SIGTERM and waits for the process to exit, sending SIGKILL after the grace period.
+	///
+	/// Returns whether the process exited within the grace period. The result of the process itself
+	/// is discarded since a signalled process is expected to fail.
+	pub async fn terminate_graceful(mut self, grace: Duration) -> Result<bool, ProcessError> {
+		self.pid().await?;
+		if self.is_running() {
+			self.kill(Signal::Terminate).await?;
+		}
+		if self.exited_within(grace).await {
+			return Ok(true);
+		}
+
+		if self.is_running() {
+			self.kill(Signal::Kill).await?;
+		}
+		if let Some(join_handle) = self.join_handle.take() {
+			let _ = join_handle.await;
+		}
+
+		Ok(false)
+	}
+
+	/// Returns whether the process exited within the given duration, regardless of its result.
+	async fn exited_within(&mut self, duration: Duration) -> bool {
+		match self.join_handle.as_mut() {
+			Some(join_handle) => match tokio::time::timeout(duration, join_handle).await {
+				Ok(_) => {
+					self.join_handle = None;
+					true
+				}
+				Err(_) => false,
+			},
+			None => true,
+		}
+	}
+}
+
+impl Drop for ProcessHandle {
+	/// Kills the process unless it has been waited on, by aborting the task which owns the child.
+	fn drop(&mut self) {
+		if let Some(join_handle) = self.join_handle.take() {
+			join_handle.abort();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::fulfill::custom::{Custom, CustomProcessor};
+	use crate::fulfill::FulfillError;
+	use crate::process::{command::Command, ProcessOperations};
+	use kestrel_state::State;
+	use tokio::sync::mpsc::Receiver;
+
+	fn sleeper(script: &str) -> Command {
+		Command::line("sh", ["-c", script], None, true, vec![], vec![])
+	}
+
+	#[tokio::test]
+	async fn test_pid_and_kill() -> Result<(), anyhow::Error> {
+		let mut handle = sleeper("exec sleep 30").spawn_handle()?;
+		assert!(handle.pid().await? > 0);
+
+		assert_eq!(handle.wait_with_timeout(Duration::from_millis(50)).await?, None);
+
+		handle.kill(Signal::Kill).await?;
+		let result = handle.wait_with_timeout(Duration::from_secs(5)).await;
+		assert!(matches!(result, Err(ProcessError::Runtime(_))));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_kill_after_exit() -> Result<(), anyhow::Error> {
+		let mut handle = sleeper("true").spawn_handle()?;
+		handle.pid().await?;
+		tokio::time::sleep(Duration::from_millis(200)).await;
+
+		// The pid may have been reused, so it must not be signalled
+		assert!(matches!(handle.kill(Signal::Kill).await, Err(ProcessError::Signal(_))));
+		assert!(matches!(handle.kill_process_tree().await, Err(ProcessError::Signal(_))));
+		assert!(handle.terminate_graceful(Duration::from_millis(50)).await?);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_drop_kills_process() -> Result<(), anyhow::Error> {
+		let mut handle = sleeper("exec sleep 30").spawn_handle()?;
+		let pid = handle.pid().await?.to_string();
+		drop(handle);
+
+		// Zombies count as dead, as the child is reaped in the background
+		let is_alive = || {
+			let output = std::process::Command::new("ps")
+				.args(["-o", "stat=", "-p", pid.as_str()])
+				.output()
+				.expect("failed to run ps");
+			let stat = String::from_utf8_lossy(&output.stdout).trim().to_string();
+			!stat.is_empty() && !stat.starts_with('Z')
+		};
+		let mut alive = true;
+		for _ in 0..100 {
+			alive = is_alive();
+			if !alive {
+				break;
+			}
+			tokio::time::sleep(Duration::from_millis(10)).await;
+		}
+		assert!(!alive, "process {} is still running after its handle was dropped", pid);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_terminate_graceful() -> Result<(), anyhow::Error> {
+		// Exits on SIGTERM
+		let handle = sleeper("exec sleep 30").spawn_handle()?;
+		assert!(handle.terminate_graceful(Duration::from_secs(5)).await?);
+
+		// Ignores SIGTERM and is killed after the grace period
+		let mut handle =
+			sleeper("trap '' TERM; echo ready; while true; do sleep 0.05; done").spawn_handle()?;
+		handle.pid().await?;
+		tokio::time::sleep(Duration::from_millis(100)).await;
+		assert!(!handle.terminate_graceful(Duration::from_millis(200)).await?);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_kill_process_tree() -> Result<(), anyhow::Error> {
+		let mut command = sleeper("sleep 30 & sleep 30 & wait");
+		command.process_group();
+		let mut handle = command.spawn_handle()?;
+		handle.pid().await?;
+
+		handle.kill_process_tree().await?;
+		let result = handle.wait_with_timeout(Duration::from_secs(5)).await;
+		assert!(matches!(result, Err(ProcessError::Runtime(_))));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_wait() -> Result<(), anyhow::Error> {
+		let handle = sleeper("echo done").spawn_handle()?;
+		assert_eq!(handle.wait().await?, "done\n");
+
+		Ok(())
+	}
+
+	/// Parses the port out of a `listening on` line.
+	struct ListeningOn;
+
+	impl CustomProcessor<u16> for ListeningOn {
+		fn process_receiver(
+			&self,
+			receiver: &mut Receiver<String>,
+		) -> impl std::future::Future<Output = Result<Option<u16>, FulfillError>> + Send {
+			async move {
+				let line = receiver.recv().await.unwrap_or_default();
+				Ok(line.trim().strip_prefix("listening on ").and_then(|port| port.parse().ok()))
+			}
+		}
+	}
+
+	#[tokio::test]
+	async fn test_fulfills() -> Result<(), anyhow::Error> {
+		let port = State::new();
+		let fulfiller = Custom::new(port.write(), ListeningOn);
+		let (mut handle, port) =
+			sleeper("echo starting; echo listening on 9000; exec sleep 30").fulfills(fulfiller)?;
+
+		assert_eq!(port.wait_for(Duration::from_secs(5)).await?, 9000);
+		handle.kill(Signal::Kill).await?;
+
+		Ok(())
+	}
+}
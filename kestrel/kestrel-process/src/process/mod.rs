@@ -1,8 +1,26 @@
 pub mod cargo;
 pub mod command;
+pub mod compose;
+pub mod docker;
+pub mod handle;
+pub mod remote;
+pub mod supervised;
 
+pub use handle::{ProcessHandle, Signal};
+pub use supervised::{RestartEvent, RestartOn, RestartPolicy, Supervised};
+
+use crate::fulfill::Fulfill;
+use crate::ready::{Probe, Ready};
+use kestrel_state::ReadOnlyState;
 use thiserror::Error;
-use tokio::{sync::mpsc::Sender, task::JoinHandle};
+use tokio::{
+	sync::{
+		mpsc::{self, Sender},
+		oneshot, watch,
+	},
+	task::JoinHandle,
+	time::{Duration, Instant},
+};
 
 /// The pipe to attach to the process
 ///
@@ -22,6 +40,31 @@ pub enum ProcessError {
 
 	#[error("failed to attach pipe: {0}")]
 	Pipe(#[source] Box<dyn std::error::Error + Send + Sync>),
+
+	#[error("failed to signal process: {0}")]
+	Signal(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
+
+/// The exit code and output of a process which ran to completion
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessOutput {
+	/// The exit code, or `None` if the process was terminated by a signal.
+	pub exit_code: Option<i32>,
+	pub stdout: String,
+	pub stderr: String,
+	/// How long the process ran for.
+	pub duration: Duration,
+}
+
+impl From<commander::CommandOutput> for ProcessOutput {
+	fn from(output: commander::CommandOutput) -> Self {
+		Self {
+			exit_code: output.exit_code,
+			stdout: output.stdout,
+			stderr: output.stderr,
+			duration: output.duration,
+		}
+	}
 }
 
 pub trait ProcessOperations: Sized + Send + Sync + 'static {
@@ -30,6 +73,39 @@ pub trait ProcessOperations: Sized + Send + Sync + 'static {
 	/// It is up to the individual implementation to decide how to the process actually runs.
 	fn run(self) -> impl std::future::Future<Output = Result<String, ProcessError>> + Send;
 
+	/// Runs the process and returns its exit code and output, regardless of its exit status
+	///
+	/// Implementations which cannot observe the exit code report a successful run as exit code 0
+	/// with its standard output, and a failed run as an error.
+	fn run_with_output(
+		self,
+	) -> impl std::future::Future<Output = Result<ProcessOutput, ProcessError>> + Send {
+		async move {
+			let started = Instant::now();
+			let stdout = self.run().await?;
+			Ok(ProcessOutput {
+				exit_code: Some(0),
+				stdout,
+				stderr: String::new(),
+				duration: started.elapsed(),
+			})
+		}
+	}
+
+	/// Runs the process without consuming it, so that it can be run again
+	///
+	/// Implementations which can only run once return an error.
+	fn rerun(&mut self) -> impl std::future::Future<Output = Result<String, ProcessError>> + Send {
+		async { Err(ProcessError::Runtime("process cannot be run again".into())) }
+	}
+
+	/// Wraps the process in a restart loop following the given policy
+	///
+	/// The process must support [ProcessOperations::rerun].
+	fn supervised(self, policy: RestartPolicy) -> Supervised<Self> {
+		Supervised::new(self, policy)
+	}
+
 	/// Spawns the process in the background
 	///
 	/// Kestrel processes should more or less never end and so do not have return values.
@@ -40,8 +116,82 @@ pub trait ProcessOperations: Sized + Send + Sync + 'static {
 		Ok(join_handle)
 	}
 
+	/// Spawns the process in the background, keeping a handle to the OS process
+	///
+	/// Unlike [ProcessOperations::spawn], the [ProcessHandle] can signal the exact child it started.
+	fn spawn_handle(mut self) -> Result<ProcessHandle, ProcessError> {
+		let (pid_sender, pid_receiver) = oneshot::channel();
+		self.pid_sender(pid_sender)?;
+		let (exit_sender, exit_receiver) = watch::channel(false);
+		self.exit_sender(exit_sender)?;
+		let join_handle = self.spawn()?;
+
+		Ok(ProcessHandle::new(pid_receiver, exit_receiver, join_handle))
+	}
+
+	/// Spawns the process with its standard output piped into the fulfiller
+	///
+	/// The fulfiller runs alongside the process and is stopped when the process exits, so the
+	/// returned state is only set if the value appears while the process runs. Like
+	/// [ProcessOperations::spawn_handle], the process must report its pid.
+	fn fulfills<T, F>(
+		mut self,
+		fulfiller: F,
+	) -> Result<(ProcessHandle, ReadOnlyState<T>), ProcessError>
+	where
+		T: Clone + Send + Sync + 'static,
+		F: Fulfill<T>,
+	{
+		let sender = fulfiller.sender().map_err(|e| ProcessError::Pipe(e.into()))?;
+		let state = fulfiller.dependency().map_err(|e| ProcessError::Pipe(e.into()))?.read_only();
+		self.pipe(Pipe::STDOUT, sender)?;
+		let (pid_sender, pid_receiver) = oneshot::channel();
+		self.pid_sender(pid_sender)?;
+		let (exit_sender, exit_receiver) = watch::channel(false);
+		self.exit_sender(exit_sender)?;
+
+		let join_handle = tokio::spawn(async move {
+			let run = self.run();
+			tokio::pin!(run);
+			tokio::select! {
+				result = &mut run => return result,
+				// Fulfillment errors leave the state unset, which readers observe by waiting
+				_ = fulfiller.run() => {}
+			}
+			run.await
+		});
+
+		Ok((ProcessHandle::new(pid_receiver, exit_receiver, join_handle), state))
+	}
+
+	/// Registers a sender for the OS process id once the process has been spawned
+	///
+	/// Implementations which do not run an OS process return an error.
+	fn pid_sender(&mut self, _sender: oneshot::Sender<u32>) -> Result<(), ProcessError> {
+		Err(ProcessError::Signal("process does not report its pid".into()))
+	}
+
+	/// Registers a sender which is set to `true` once the OS process has exited and been reaped
+	///
+	/// Implementations which do not run an OS process return an error.
+	fn exit_sender(&mut self, _sender: watch::Sender<bool>) -> Result<(), ProcessError> {
+		Err(ProcessError::Signal("process does not report its exit".into()))
+	}
+
 	/// Attaches a pipe to the process
 	///
 	/// It is up to the individual implementation to decide how to actually perform the sends within the `run` method.
 	fn pipe(&mut self, pipe: Pipe, sender: Sender<String>) -> Result<(), ProcessError>;
+
+	/// Tracks when the process becomes ready according to the probe
+	///
+	/// Both standard output and standard error are piped to the probe, so this must be called
+	/// before the process runs.
+	fn ready_when(&mut self, probe: Probe) -> Result<ReadOnlyState<Ready>, ProcessError> {
+		let (sender, receiver) = mpsc::channel(100);
+		self.pipe(Pipe::STDOUT, sender.clone())?;
+		self.pipe(Pipe::STDERR, sender)?;
+
+		Ok(probe.watch(receiver))
+	}
 }
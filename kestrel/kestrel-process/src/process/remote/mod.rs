@@ -0,0 +1,234 @@
+use crate::process::{Pipe, ProcessError, ProcessOperations};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::{self, Sender};
+
+/// Printed by the remote shell before it execs the command, so the remote pid can be killed.
+const PID_MARKER: &str = "__KESTREL_REMOTE_PID__";
+
+/// Runs a command on a remote host over SSH using the local `ssh` client.
+///
+/// Standard output and error are streamed back through the usual pipes. Aborting the run kills
+/// the remote command, which closing the connection alone does not guarantee.
+pub struct Ssh {
+	destination: String,
+	port: Option<u16>,
+	identity: Option<PathBuf>,
+	options: Vec<String>,
+	command: Vec<String>,
+	capture_output: bool,
+	stdout_senders: Vec<Sender<String>>,
+	stderr_senders: Vec<Sender<String>>,
+}
+
+impl Ssh {
+	/// Creates a new Ssh process running the command on the destination, e.g. `user@host`.
+	pub fn new<I, S>(destination: impl Into<String>, command: I) -> Self
+	where
+		I: IntoIterator<Item = S>,
+		S: Into<String>,
+	{
+		Self {
+			destination: destination.into(),
+			port: None,
+			identity: None,
+			options: Vec::new(),
+			command: command.into_iter().map(Into::into).collect(),
+			capture_output: true,
+			stdout_senders: Vec::new(),
+			stderr_senders: Vec::new(),
+		}
+	}
+
+	/// Sets the port to connect to.
+	pub fn port(&mut self, port: u16) -> &mut Self {
+		self.port = Some(port);
+		self
+	}
+
+	/// Sets the identity file used to authenticate.
+	pub fn identity(&mut self, identity: impl Into<PathBuf>) -> &mut Self {
+		self.identity = Some(identity.into());
+		self
+	}
+
+	/// Passes an option to ssh, e.g. `StrictHostKeyChecking=no`.
+	pub fn option(&mut self, option: impl Into<String>) -> &mut Self {
+		self.options.push(option.into());
+		self
+	}
+
+	/// Sets whether to capture the output of the command.
+	pub fn set_capture_output(&mut self, capture_output: bool) -> &mut Self {
+		self.capture_output = capture_output;
+		self
+	}
+
+	/// Appends a sender for the standard output of the command.
+	pub fn append_stdout(&mut self, sender: Sender<String>) -> &mut Self {
+		self.stdout_senders.push(sender);
+		self
+	}
+
+	/// Appends a sender for the standard error of the command.
+	pub fn append_stderr(&mut self, sender: Sender<String>) -> &mut Self {
+		self.stderr_senders.push(sender);
+		self
+	}
+
+	/// Returns the arguments to ssh which select the remote host.
+	fn connection_args(&self) -> Vec<String> {
+		// Never read the local standard input or prompt, since the terminal is not ours to use
+		let mut args = vec!["-n".to_string(), "-o".to_string(), "BatchMode=yes".to_string()];
+		if let Some(port) = self.port {
+			args.extend(["-p".to_string(), port.to_string()]);
+		}
+		if let Some(identity) = &self.identity {
+			args.extend(["-i".to_string(), identity.display().to_string()]);
+		}
+		for option in &self.options {
+			args.extend(["-o".to_string(), option.clone()]);
+		}
+		args.push(self.destination.clone());
+		args
+	}
+
+	/// Returns the script run by the remote shell.
+	fn remote_script(&self) -> String {
+		let command = self.command.iter().map(|arg| shell_quote(arg)).collect::<Vec<_>>().join(" ");
+		format!("echo {}$$; exec {}", PID_MARKER, command)
+	}
+}
+
+/// Quotes an argument for a POSIX shell.
+fn shell_quote(arg: &str) -> String {
+	format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+impl ProcessOperations for Ssh {
+	fn run(self) -> impl std::future::Future<Output = Result<String, ProcessError>> + Send {
+		async move {
+			let mut args = self.connection_args();
+			let remote_pid = Arc::new(Mutex::new(None));
+			let mut remote_kill =
+				RemoteKill { connection_args: Some(args.clone()), remote_pid: remote_pid.clone() };
+			args.push(self.remote_script());
+
+			// Intercept standard output to pick out the remote pid
+			let (stdout_sender, mut stdout_receiver) = mpsc::channel::<String>(100);
+			let stdout_senders = self.stdout_senders;
+			let forward = async move {
+				while let Some(line) = stdout_receiver.recv().await {
+					if let Some(pid) = line.trim_end().strip_prefix(PID_MARKER) {
+						if let Ok(mut remote_pid) = remote_pid.lock() {
+							*remote_pid = pid.parse::<u32>().ok();
+						}
+						continue;
+					}
+					for sender in &stdout_senders {
+						let _ = sender.send(line.clone()).await;
+					}
+				}
+			};
+
+			let mut command = commander::Command::line(
+				"ssh",
+				args,
+				None,
+				self.capture_output,
+				vec![stdout_sender],
+				self.stderr_senders,
+			);
+			// Dropping the command closes the interception channel, which ends the forwarding
+			let run = async move { command.run().await };
+			let (result, _) = tokio::join!(run, forward);
+			// The remote command has exited, so there is nothing left to kill
+			remote_kill.connection_args = None;
+
+			let output = result.map_err(|e| ProcessError::Runtime(e.into()))?;
+			Ok(strip_pid_line(&output))
+		}
+	}
+
+	fn pipe(&mut self, pipe: Pipe, sender: Sender<String>) -> Result<(), ProcessError> {
+		match pipe {
+			Pipe::STDOUT => {
+				self.append_stdout(sender);
+				Ok(())
+			}
+			Pipe::STDERR => {
+				self.append_stderr(sender);
+				Ok(())
+			}
+		}
+	}
+}
+
+/// Removes the pid line printed by the remote shell from the captured output.
+fn strip_pid_line(output: &str) -> String {
+	match output.split_once('\n') {
+		Some((first, rest)) if first.starts_with(PID_MARKER) => rest.to_string(),
+		_ => output.to_string(),
+	}
+}
+
+/// Kills the remote command when dropped before it has exited.
+struct RemoteKill {
+	connection_args: Option<Vec<String>>,
+	remote_pid: Arc<Mutex<Option<u32>>>,
+}
+
+impl Drop for RemoteKill {
+	fn drop(&mut self) {
+		let pid = self.remote_pid.lock().ok().and_then(|pid| *pid);
+		if let (Some(mut args), Some(pid)) = (self.connection_args.take(), pid) {
+			args.push(format!("kill -KILL {}", pid));
+			// The runtime may be shutting down, so kill from a plain thread
+			std::thread::spawn(move || {
+				let _ = std::process::Command::new("ssh").args(args).status();
+			});
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_remote_script_quotes_arguments() {
+		let ssh = Ssh::new("node@10.0.0.2", ["echo", "it's a test", "$HOME"]);
+		assert_eq!(
+			ssh.remote_script(),
+			format!("echo {}$$; exec 'echo' 'it'\\''s a test' '$HOME'", PID_MARKER)
+		);
+	}
+
+	#[test]
+	fn test_connection_args() {
+		let mut ssh = Ssh::new("node@10.0.0.2", ["true"]);
+		ssh.port(2222).identity("/keys/id").option("StrictHostKeyChecking=no");
+		assert_eq!(
+			ssh.connection_args(),
+			vec![
+				"-n",
+				"-o",
+				"BatchMode=yes",
+				"-p",
+				"2222",
+				"-i",
+				"/keys/id",
+				"-o",
+				"StrictHostKeyChecking=no",
+				"node@10.0.0.2"
+			]
+		);
+	}
+
+	#[test]
+	fn test_strip_pid_line() {
+		let output = format!("{}4242\nhello\n", PID_MARKER);
+		assert_eq!(strip_pid_line(&output), "hello\n");
+		assert_eq!(strip_pid_line("hello\n"), "hello\n");
+	}
+}
@@ -0,0 +1,202 @@
+use crate::process::{Pipe, ProcessError, ProcessOperations};
+use tokio::sync::{mpsc::Sender, oneshot, watch};
+use tokio::time::Duration;
+
+/// Which exits cause a supervised process to be restarted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartOn {
+	/// Restart only when the process fails, e.g. exits with a nonzero code.
+	Failure,
+	/// Restart whenever the process exits.
+	Always,
+}
+
+/// How a supervised process is restarted.
+#[derive(Debug, Clone)]
+pub struct RestartPolicy {
+	max_restarts: usize,
+	backoff: Duration,
+	max_backoff: Duration,
+	restart_on: RestartOn,
+}
+
+impl RestartPolicy {
+	/// Creates a policy restarting a failed process up to the given number of times.
+	pub fn new(max_restarts: usize) -> Self {
+		Self {
+			max_restarts,
+			backoff: Duration::from_millis(100),
+			max_backoff: Duration::from_secs(10),
+			restart_on: RestartOn::Failure,
+		}
+	}
+
+	/// Sets the delay before the first restart, which doubles on every following restart.
+	pub fn backoff(mut self, backoff: Duration) -> Self {
+		self.backoff = backoff;
+		self
+	}
+
+	/// Sets the upper bound on the delay between restarts.
+	pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+		self.max_backoff = max_backoff;
+		self
+	}
+
+	/// Sets which exits cause a restart.
+	pub fn restart_on(mut self, restart_on: RestartOn) -> Self {
+		self.restart_on = restart_on;
+		self
+	}
+
+	/// Gets the maximum number of restarts.
+	pub fn max_restarts(&self) -> usize {
+		self.max_restarts
+	}
+}
+
+/// A restart performed by a supervised process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestartEvent {
+	/// The number of this restart, starting at 1.
+	pub restart: usize,
+	/// Why the previous run ended, or `None` if it exited successfully.
+	pub reason: Option<String>,
+	/// How long the supervisor waits before restarting.
+	pub backoff: Duration,
+}
+
+/// A process which is restarted according to a [RestartPolicy].
+pub struct Supervised<P>
+where
+	P: ProcessOperations,
+{
+	inner: P,
+	policy: RestartPolicy,
+	events: Option<Sender<RestartEvent>>,
+}
+
+impl<P> Supervised<P>
+where
+	P: ProcessOperations,
+{
+	/// Creates a new Supervised process.
+	pub fn new(inner: P, policy: RestartPolicy) -> Self {
+		Self { inner, policy, events: None }
+	}
+
+	/// Publishes every restart to the given sender.
+	pub fn events(mut self, sender: Sender<RestartEvent>) -> Self {
+		self.events = Some(sender);
+		self
+	}
+}
+
+impl<P> ProcessOperations for Supervised<P>
+where
+	P: ProcessOperations,
+{
+	fn run(mut self) -> impl std::future::Future<Output = Result<String, ProcessError>> + Send {
+		async move { self.rerun().await }
+	}
+
+	fn rerun(&mut self) -> impl std::future::Future<Output = Result<String, ProcessError>> + Send {
+		async move {
+			let mut restarts = 0;
+			let mut backoff = self.policy.backoff;
+			loop {
+				let result = self.inner.rerun().await;
+				let reason = match (&result, self.policy.restart_on) {
+					(Ok(_), RestartOn::Failure) => return result,
+					(Ok(_), RestartOn::Always) => None,
+					(Err(e), _) => Some(e.to_string()),
+				};
+				if restarts >= self.policy.max_restarts {
+					return result;
+				}
+
+				restarts += 1;
+				if let Some(events) = &self.events {
+					let _ = events.send(RestartEvent { restart: restarts, reason, backoff }).await;
+				}
+				tokio::time::sleep(backoff).await;
+				backoff = (backoff * 2).min(self.policy.max_backoff);
+			}
+		}
+	}
+
+	fn pid_sender(&mut self, sender: oneshot::Sender<u32>) -> Result<(), ProcessError> {
+		self.inner.pid_sender(sender)
+	}
+
+	fn exit_sender(&mut self, sender: watch::Sender<bool>) -> Result<(), ProcessError> {
+		self.inner.exit_sender(sender)
+	}
+
+	fn pipe(&mut self, pipe: Pipe, sender: Sender<String>) -> Result<(), ProcessError> {
+		self.inner.pipe(pipe, sender)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::process::command::Command;
+
+	/// A command which fails until it has been run the given number of times.
+	fn flaky(counter: &std::path::Path, succeed_on: usize) -> Command {
+		let script = format!(
+			"n=$(cat {path} 2>/dev/null || echo 0); n=$((n + 1)); echo $n > {path}; \
+			 echo run $n; [ $n -ge {succeed_on} ]",
+			path = counter.display(),
+		);
+		Command::line("sh", ["-c", script.as_str()], None, true, vec![], vec![])
+	}
+
+	#[tokio::test]
+	async fn test_supervised_restarts_until_success() -> Result<(), anyhow::Error> {
+		let temp_dir = tempfile::tempdir()?;
+		let counter = temp_dir.path().join("runs");
+		let (sender, mut receiver) = tokio::sync::mpsc::channel(10);
+
+		let policy = RestartPolicy::new(5).backoff(Duration::from_millis(10));
+		let output = flaky(&counter, 3).supervised(policy).events(sender).run().await?;
+		assert_eq!(output, "run 3\n");
+
+		let first = receiver.recv().await.expect("first restart");
+		let second = receiver.recv().await.expect("second restart");
+		assert_eq!((first.restart, first.backoff), (1, Duration::from_millis(10)));
+		assert_eq!((second.restart, second.backoff), (2, Duration::from_millis(20)));
+		assert!(first.reason.is_some());
+		assert!(receiver.recv().await.is_none());
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_supervised_gives_up_after_max_restarts() -> Result<(), anyhow::Error> {
+		let temp_dir = tempfile::tempdir()?;
+		let counter = temp_dir.path().join("runs");
+
+		let policy = RestartPolicy::new(2).backoff(Duration::from_millis(10));
+		let result = flaky(&counter, 10).supervised(policy).run().await;
+		assert!(matches!(result, Err(ProcessError::Runtime(_))));
+		assert_eq!(std::fs::read_to_string(&counter)?.trim(), "3");
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_supervised_restarts_always() -> Result<(), anyhow::Error> {
+		let temp_dir = tempfile::tempdir()?;
+		let counter = temp_dir.path().join("runs");
+
+		let policy = RestartPolicy::new(2)
+			.backoff(Duration::from_millis(10))
+			.restart_on(RestartOn::Always);
+		let output = flaky(&counter, 0).supervised(policy).run().await?;
+		assert_eq!(output, "run 3\n");
+
+		Ok(())
+	}
+}
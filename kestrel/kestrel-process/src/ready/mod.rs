@@ -0,0 +1,15 @@
+pub mod probe;
+pub mod socket;
+
+pub use probe::{Probe, Ready};
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ReadyError {
+	#[error("readiness not reached: {0}")]
+	NotReady(String),
+
+	#[error("internal readiness error: {0}")]
+	Internal(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
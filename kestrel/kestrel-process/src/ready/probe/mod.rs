@@ -0,0 +1,252 @@
+use crate::ready::ReadyError;
+use jsonlvar::JsonlParser;
+use kestrel_state::{ReadOnlyState, State, WritableState};
+use regex::Regex;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::Receiver;
+use tokio::time::{Duration, Instant};
+
+/// Evidence that a process has become ready.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ready {
+	/// What satisfied the probe, e.g. the matching log line or the JSONL value.
+	pub evidence: String,
+	/// How long after the probe started the process became ready.
+	pub elapsed: Duration,
+}
+
+enum ProbeKind {
+	LogLine(Regex),
+	Tcp(String),
+	Http { authority: String, path: String },
+	Jsonl(String),
+}
+
+/// A check for whether a process is ready.
+///
+/// Log-line and JSONL probes inspect the output of the process. TCP and HTTP probes poll until
+/// they succeed or the output of the process closes.
+pub struct Probe {
+	kind: ProbeKind,
+	interval: Duration,
+}
+
+impl Probe {
+	fn new(kind: ProbeKind) -> Self {
+		Self { kind, interval: Duration::from_millis(50) }
+	}
+
+	/// Ready once a line of output matches the regex.
+	pub fn log_line(pattern: &str) -> Result<Self, ReadyError> {
+		let regex = Regex::new(pattern).map_err(|e| ReadyError::Internal(Box::new(e)))?;
+		Ok(Self::new(ProbeKind::LogLine(regex)))
+	}
+
+	/// Ready once the address accepts a TCP connection.
+	pub fn tcp(address: impl Into<String>) -> Self {
+		Self::new(ProbeKind::Tcp(address.into()))
+	}
+
+	/// Ready once a GET request to the `http://` URL responds with 200.
+	pub fn http(url: &str) -> Result<Self, ReadyError> {
+		let (authority, path) = split_http_url(url).ok_or_else(|| {
+			ReadyError::Internal(format!("unsupported url {}, expected http://", url).into())
+		})?;
+		Ok(Self::new(ProbeKind::Http { authority: authority.to_string(), path: path.to_string() }))
+	}
+
+	/// Ready once the output contains the JSONL variable.
+	pub fn jsonl(var: impl Into<String>) -> Self {
+		Self::new(ProbeKind::Jsonl(var.into()))
+	}
+
+	/// Sets the interval between TCP and HTTP attempts.
+	pub fn interval(mut self, interval: Duration) -> Self {
+		self.interval = interval;
+		self
+	}
+
+	/// Watches the output lines of a process and sets the state once the probe succeeds.
+	///
+	/// The lines are drained until the sender closes, so the process is never blocked on output.
+	pub fn watch(self, lines: Receiver<String>) -> ReadOnlyState<Ready> {
+		let state = State::new();
+		let writer = state.write();
+		tokio::spawn(self.run(lines, writer));
+		state.read()
+	}
+
+	async fn run(self, mut lines: Receiver<String>, writer: WritableState<Ready>) {
+		let started = Instant::now();
+		let parser = JsonlParser::new();
+		let mut interval = tokio::time::interval(self.interval);
+		let evidence = loop {
+			let evidence = tokio::select! {
+				line = lines.recv() => match line {
+					Some(line) => self.check_line(&parser, &line),
+					None => return,
+				},
+				_ = interval.tick() => self.check_network().await,
+			};
+			if let Some(evidence) = evidence {
+				break evidence;
+			}
+		};
+
+		writer.set(Ready { evidence, elapsed: started.elapsed() }).await;
+		while lines.recv().await.is_some() {}
+	}
+
+	fn check_line(&self, parser: &JsonlParser, line: &str) -> Option<String> {
+		match &self.kind {
+			ProbeKind::LogLine(regex) if regex.is_match(line) => Some(line.trim_end().to_string()),
+			ProbeKind::Jsonl(var) => {
+				parser.parse(line).remove(var).map(|value| format!("{} = {}", var, value))
+			}
+			_ => None,
+		}
+	}
+
+	async fn check_network(&self) -> Option<String> {
+		let attempt = async {
+			match &self.kind {
+				ProbeKind::Tcp(address) => {
+					TcpStream::connect(address).await.ok().map(|_| address.clone())
+				}
+				ProbeKind::Http { authority, path } => http_ok(authority, path)
+					.await
+					.then(|| format!("GET http://{}{} responded 200", authority, path)),
+				_ => None,
+			}
+		};
+		tokio::time::timeout(Duration::from_secs(1), attempt).await.ok().flatten()
+	}
+}
+
+/// Splits an `http://` URL into its authority and path.
+pub(crate) fn split_http_url(url: &str) -> Option<(&str, &str)> {
+	let rest = url.strip_prefix("http://")?;
+	Some(match rest.find('/') {
+		Some(index) => (&rest[..index], &rest[index..]),
+		None => (rest, "/"),
+	})
+}
+
+/// Returns the socket address of an authority, defaulting to port 80.
+pub(crate) fn http_address(authority: &str) -> String {
+	if authority.contains(':') {
+		authority.to_string()
+	} else {
+		format!("{}:80", authority)
+	}
+}
+
+/// Returns whether a GET request responds with 200.
+async fn http_ok(authority: &str, path: &str) -> bool {
+	let Ok(mut stream) = TcpStream::connect(http_address(authority)).await else {
+		return false;
+	};
+	let request =
+		format!("GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", path, authority);
+	if stream.write_all(request.as_bytes()).await.is_err() {
+		return false;
+	}
+
+	let mut status_line = String::new();
+	if BufReader::new(stream).read_line(&mut status_line).await.is_err() {
+		return false;
+	}
+	let mut parts = status_line.split_whitespace();
+	parts.next().is_some_and(|version| version.starts_with("HTTP/")) && parts.next() == Some("200")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::process::{command::Command, ProcessOperations};
+	use tokio::net::TcpListener;
+
+	fn script(script: &str) -> Command {
+		Command::line("sh", ["-c", script], None, false, vec![], vec![])
+	}
+
+	#[tokio::test]
+	async fn test_log_line_probe() -> Result<(), anyhow::Error> {
+		let mut command = script("echo starting; sleep 0.1; echo listening on 9000; sleep 0.1");
+		let ready = command.ready_when(Probe::log_line(r"listening on \d+")?)?;
+		let process = command.spawn()?;
+
+		let ready = ready.wait_forever().await;
+		assert_eq!(ready.evidence, "listening on 9000");
+		process.await??;
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_jsonl_probe() -> Result<(), anyhow::Error> {
+		let mut command = script("echo 'JSONL node.port = 9000'; sleep 0.1");
+		let ready = command.ready_when(Probe::jsonl("node.port"))?;
+		let process = command.spawn()?;
+
+		assert_eq!(ready.wait_forever().await.evidence, "node.port = 9000");
+		process.await??;
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_tcp_probe() -> Result<(), anyhow::Error> {
+		let listener = TcpListener::bind("127.0.0.1:0").await?;
+		let address = listener.local_addr()?.to_string();
+
+		let mut command = script("sleep 5");
+		let ready = command.ready_when(Probe::tcp(address.clone()))?;
+		let _process = command.spawn()?;
+
+		assert_eq!(ready.wait_forever().await.evidence, address);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_http_probe() -> Result<(), anyhow::Error> {
+		let listener = TcpListener::bind("127.0.0.1:0").await?;
+		let address = listener.local_addr()?;
+		let server = tokio::spawn(async move {
+			// The first request fails, the second succeeds
+			for status in ["503 Service Unavailable", "200 OK"] {
+				let (mut stream, _) = listener.accept().await?;
+				let mut request = String::new();
+				BufReader::new(&mut stream).read_line(&mut request).await?;
+				let response = format!("HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status);
+				stream.write_all(response.as_bytes()).await?;
+			}
+			Ok::<_, std::io::Error>(())
+		});
+
+		let mut command = script("sleep 5");
+		let probe = Probe::http(&format!("http://{}/health", address))?;
+		let ready = command.ready_when(probe.interval(Duration::from_millis(10)))?;
+		let _process = command.spawn()?;
+
+		let ready = ready.wait_forever().await;
+		assert!(ready.evidence.contains("/health"));
+		server.await??;
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_probe_stops_with_process() -> Result<(), anyhow::Error> {
+		let mut command = script("echo never ready");
+		let ready = command.ready_when(Probe::log_line("ready to serve")?)?;
+		command.spawn()?.await??;
+
+		let result = ready.wait_for(Duration::from_millis(100)).await;
+		assert!(result.is_err());
+
+		Ok(())
+	}
+}
@@ -0,0 +1,104 @@
+use crate::ready::ReadyError;
+use kestrel_state::{wait_poll, WritableState};
+use std::path::{Path, PathBuf};
+use tokio::net::UnixStream;
+use tokio::time::Duration;
+
+/// Waits for a service to signal readiness by accepting connections on a Unix socket.
+pub struct SocketReady {
+	path: PathBuf,
+	timeout: Duration,
+	interval: Duration,
+}
+
+impl SocketReady {
+	/// Creates a new SocketReady for the given socket path and timeout.
+	pub fn new(path: impl Into<PathBuf>, timeout: Duration) -> Self {
+		Self { path: path.into(), timeout, interval: Duration::from_millis(50) }
+	}
+
+	/// Sets the interval between connection attempts.
+	pub fn interval(mut self, interval: Duration) -> Self {
+		self.interval = interval;
+		self
+	}
+
+	/// Gets the socket path.
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// Waits until the socket exists and accepts a connection.
+	pub async fn wait(&self) -> Result<(), ReadyError> {
+		let path = self.path.clone();
+		let result = wait_poll(
+			move || {
+				let path = path.clone();
+				async move { path.exists() && UnixStream::connect(&path).await.is_ok() }
+			},
+			self.interval,
+			self.timeout,
+		)
+		.await;
+
+		result.map_err(|_| {
+			if self.path.exists() {
+				ReadyError::NotReady(format!(
+					"socket {} did not accept a connection within {:?}",
+					self.path.display(),
+					self.timeout
+				))
+			} else {
+				ReadyError::NotReady(format!(
+					"socket {} did not appear within {:?}",
+					self.path.display(),
+					self.timeout
+				))
+			}
+		})
+	}
+
+	/// Waits until the socket is ready and then sets the state.
+	pub async fn wait_and_set(&self, state: WritableState<()>) -> Result<(), ReadyError> {
+		self.wait().await?;
+		state.set(()).await;
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tokio::net::UnixListener;
+
+	#[tokio::test]
+	async fn test_socket_ready() -> Result<(), anyhow::Error> {
+		let temp_dir = tempfile::tempdir()?;
+		let path = temp_dir.path().join("ready.sock");
+
+		let listener_path = path.clone();
+		let listener = tokio::spawn(async move {
+			tokio::time::sleep(Duration::from_millis(100)).await;
+			let listener = UnixListener::bind(&listener_path)?;
+			listener.accept().await?;
+			Ok::<_, std::io::Error>(())
+		});
+
+		SocketReady::new(&path, Duration::from_secs(5)).wait().await?;
+		listener.await??;
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_socket_ready_timeout() -> Result<(), anyhow::Error> {
+		let temp_dir = tempfile::tempdir()?;
+		let path = temp_dir.path().join("never.sock");
+
+		let result = SocketReady::new(&path, Duration::from_millis(100)).wait().await;
+		let error = result.unwrap_err().to_string();
+		assert!(error.contains("did not appear"), "unexpected error: {}", error);
+
+		Ok(())
+	}
+}
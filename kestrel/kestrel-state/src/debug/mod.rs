@@ -0,0 +1,186 @@
+//! Introspection of which tasks wait on which states, for diagnosing hung tests.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use tokio::task::{Id, JoinHandle};
+use tokio::time::{Duration, Instant};
+
+/// A wait on a state which has not been fulfilled yet
+#[derive(Debug, Clone)]
+pub struct Waiter {
+	/// The name of the state, if it was given one
+	pub state_name: Option<String>,
+	/// The tokio task which is waiting, if the wait happens within a task
+	pub task: Option<Id>,
+	/// When the wait started blocking
+	pub since: Instant,
+	state_id: u64,
+}
+
+/// The waits currently blocked, and the tasks which claimed to write each state
+#[derive(Debug, Default)]
+struct Registry {
+	waiters: BTreeMap<u64, Waiter>,
+	writers: HashMap<u64, Id>,
+}
+
+fn registry() -> &'static Mutex<Registry> {
+	static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+	REGISTRY.get_or_init(|| Mutex::new(Registry::default()))
+}
+
+/// Removes a wait from the registry when it completes or is dropped
+#[derive(Debug)]
+pub(crate) struct Waiting(u64);
+
+impl Waiting {
+	/// Registers the current task as waiting on the state until the returned guard is dropped
+	pub(crate) fn start(state_id: u64, state_name: Option<&str>) -> Self {
+		static NEXT_WAIT_ID: AtomicU64 = AtomicU64::new(0);
+		let id = NEXT_WAIT_ID.fetch_add(1, Ordering::Relaxed);
+		let waiter = Waiter {
+			state_name: state_name.map(str::to_string),
+			task: tokio::task::try_id(),
+			since: Instant::now(),
+			state_id,
+		};
+		if let Ok(mut registry) = registry().lock() {
+			registry.waiters.insert(id, waiter);
+		}
+		Waiting(id)
+	}
+}
+
+impl Drop for Waiting {
+	fn drop(&mut self) {
+		if let Ok(mut registry) = registry().lock() {
+			registry.waiters.remove(&self.0);
+		}
+	}
+}
+
+/// Records a task as the writer of a state until dropped, see [crate::WritableState::claim]
+#[derive(Debug)]
+#[must_use = "the claim ends when the guard is dropped"]
+pub struct Claim {
+	state_id: u64,
+	task: Option<Id>,
+}
+
+impl Claim {
+	/// Records the current task as the writer of the state, if called within a task
+	pub(crate) fn start(state_id: u64) -> Self {
+		let task = tokio::task::try_id();
+		if let (Some(task), Ok(mut registry)) = (task, registry().lock()) {
+			registry.writers.insert(state_id, task);
+		}
+		Claim { state_id, task }
+	}
+}
+
+impl Drop for Claim {
+	fn drop(&mut self) {
+		if let (Some(task), Ok(mut registry)) = (self.task, registry().lock()) {
+			// The state may have been claimed by another task since
+			if registry.writers.get(&self.state_id) == Some(&task) {
+				registry.writers.remove(&self.state_id);
+			}
+		}
+	}
+}
+
+/// Returns the task which claimed the state, if the claim has not ended
+#[cfg(test)]
+pub(crate) fn writer(state_id: u64) -> Option<Id> {
+	registry()
+		.lock()
+		.ok()
+		.and_then(|registry| registry.writers.get(&state_id).copied())
+}
+
+/// Returns every wait on a state which is currently blocked, oldest first
+pub fn waiters() -> Vec<Waiter> {
+	registry()
+		.lock()
+		.map(|registry| registry.waiters.values().cloned().collect())
+		.unwrap_or_default()
+}
+
+/// Returns the cycles of tasks which each wait on a state claimed by the next task
+///
+/// Such tasks are deadlocked: none of the states they wait on can be written. Only states whose
+/// writers called [crate::WritableState::claim] are considered.
+pub fn cycles() -> Vec<Vec<Id>> {
+	let edges: HashMap<Id, Vec<Id>> = match registry().lock() {
+		Ok(registry) => {
+			let mut edges: HashMap<Id, Vec<Id>> = HashMap::new();
+			for waiter in registry.waiters.values() {
+				if let (Some(task), Some(writer)) =
+					(waiter.task, registry.writers.get(&waiter.state_id))
+				{
+					edges.entry(task).or_default().push(*writer);
+				}
+			}
+			edges
+		}
+		Err(_) => return Vec::new(),
+	};
+
+	let mut cycles = Vec::new();
+	let mut visited = HashSet::new();
+	for start in edges.keys() {
+		let mut path = Vec::new();
+		find_cycles(*start, &edges, &mut path, &mut visited, &mut cycles);
+	}
+	cycles
+}
+
+/// Walks the wait edges depth first, recording every cycle closed by the current path
+fn find_cycles(
+	task: Id,
+	edges: &HashMap<Id, Vec<Id>>,
+	path: &mut Vec<Id>,
+	visited: &mut HashSet<Id>,
+	cycles: &mut Vec<Vec<Id>>,
+) {
+	if let Some(position) = path.iter().position(|on_path| *on_path == task) {
+		cycles.push(path[position..].to_vec());
+		return;
+	}
+	if !visited.insert(task) {
+		return;
+	}
+
+	path.push(task);
+	for next in edges.get(&task).into_iter().flatten() {
+		find_cycles(*next, edges, path, visited, cycles);
+	}
+	path.pop();
+}
+
+/// Spawns a task which periodically logs waits blocked for longer than the threshold, and cycles
+/// of deadlocked tasks
+///
+/// The detector runs until the returned handle is aborted.
+pub fn spawn_detector(interval: Duration, threshold: Duration) -> JoinHandle<()> {
+	tokio::spawn(async move {
+		loop {
+			tokio::time::sleep(interval).await;
+			for waiter in waiters() {
+				let waited = waiter.since.elapsed();
+				if waited >= threshold {
+					tracing::warn!(
+						"task {:?} has been waiting for {} for {:?}",
+						waiter.task,
+						waiter.state_name.as_deref().unwrap_or("an unnamed state"),
+						waited
+					);
+				}
+			}
+			for cycle in cycles() {
+				tracing::warn!("deadlock: tasks {:?} each wait on a state of the next", cycle);
+			}
+		}
+	})
+}
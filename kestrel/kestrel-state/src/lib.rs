@@ -1,31 +1,174 @@
+pub mod debug;
+mod map;
+mod once;
+mod persist;
+mod quorum;
+#[cfg(unix)]
+mod remote;
+
+use futures::Stream;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::SystemTime;
 use thiserror::Error;
-use tokio::sync::{Notify, RwLock, RwLockReadGuard, RwLockWriteGuard};
-use tokio::time::{sleep, Duration};
+use tokio::sync::{Mutex, Notify, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use tokio::time::{sleep, sleep_until, Duration, Instant};
+
+pub use map::StateMap;
+pub use once::OnceState;
+pub use persist::PersistError;
+pub use quorum::Quorum;
+#[cfg(unix)]
+pub use remote::{RemoteError, RemoteState, RemoteStateServer};
+
+/// Re-exports used by the macros in this crate.
+#[doc(hidden)]
+pub mod reexport {
+	pub use tokio;
+}
 
 /// Main state container holding an optional value.
-#[derive(Clone)]
-pub struct State<T: Clone + Send + Sync + 'static> {
+pub struct State<T: Send + Sync + 'static> {
 	inner: Arc<RwLock<Option<T>>>,
 	notify: Arc<Notify>,
+	/// The number of writes through [WritableState::set], [WritableState::try_set], and
+	/// [WritableState::reset], bumped while holding the write lock
+	version: Arc<AtomicU64>,
+	/// The most recent values set, if history is enabled
+	history: Option<Arc<History<T>>>,
+	/// Identifies the state, shared by its clones, for [debug]
+	id: u64,
+	/// The name reported when a wait for the state times out
+	name: Option<Arc<str>>,
+	/// Snapshots each write, if the state is persistent
+	persist: Option<persist::Persist<T>>,
+}
+
+/// A value recorded in the history of a state
+#[derive(Debug, Clone)]
+pub struct HistoryEntry<T> {
+	/// When the value was set
+	pub at: SystemTime,
+	/// The version of the state the value was set at
+	pub version: u64,
+	/// The value set
+	pub value: T,
+}
+
+/// The most recent values set on a state, oldest first
+struct History<T> {
+	capacity: usize,
+	entries: std::sync::Mutex<VecDeque<HistoryEntry<T>>>,
+	/// Clones values into the history, so that only states with history require `T: Clone`
+	clone_value: fn(&T) -> T,
+}
+
+/// Generates the next unique state identifier
+fn next_state_id() -> u64 {
+	static NEXT_STATE_ID: AtomicU64 = AtomicU64::new(0);
+	NEXT_STATE_ID.fetch_add(1, Ordering::Relaxed)
 }
 
 /// Wrapper for writable state
-#[derive(Clone)]
-pub struct WritableState<T: Clone + Send + Sync + 'static> {
+pub struct WritableState<T: Send + Sync + 'static> {
 	state: State<T>,
 }
 
 /// Wrapper for read-only state
-#[derive(Clone)]
-pub struct ReadOnlyState<T: Clone + Send + Sync + 'static> {
+pub struct ReadOnlyState<T: Send + Sync + 'static> {
 	state: State<T>,
 }
 
-impl<T: Clone + Send + Sync + 'static> State<T> {
+// Clones share the value, so they do not require `T: Clone`
+impl<T: Send + Sync + 'static> Clone for State<T> {
+	fn clone(&self) -> Self {
+		Self {
+			inner: self.inner.clone(),
+			notify: self.notify.clone(),
+			version: self.version.clone(),
+			history: self.history.clone(),
+			id: self.id,
+			name: self.name.clone(),
+			persist: self.persist.clone(),
+		}
+	}
+}
+
+impl<T: Send + Sync + 'static> Clone for WritableState<T> {
+	fn clone(&self) -> Self {
+		Self { state: self.state.clone() }
+	}
+}
+
+impl<T: Send + Sync + 'static> Clone for ReadOnlyState<T> {
+	fn clone(&self) -> Self {
+		Self { state: self.state.clone() }
+	}
+}
+
+impl<T: Send + Sync + 'static> State<T> {
 	/// Creates a new empty state.
 	pub fn new() -> Self {
-		Self { inner: Arc::new(RwLock::new(None)), notify: Arc::new(Notify::new()) }
+		Self::with_value(None)
+	}
+
+	/// Creates a state holding the given value.
+	fn with_value(value: Option<T>) -> Self {
+		Self {
+			inner: Arc::new(RwLock::new(value)),
+			notify: Arc::new(Notify::new()),
+			version: Arc::new(AtomicU64::new(0)),
+			history: None,
+			id: next_state_id(),
+			name: None,
+			persist: None,
+		}
+	}
+
+	/// Names the state, so that waits which time out report which state never arrived.
+	///
+	/// Name the state before handing out readers, as each reader keeps the name it was created
+	/// with.
+	pub fn named(mut self, name: impl Into<String>) -> Self {
+		self.name = Some(name.into().into());
+		self
+	}
+
+	/// Returns the name of the state, if it was given one.
+	pub fn name(&self) -> Option<&str> {
+		self.name.as_deref()
+	}
+
+	/// Registers a blocked wait on the state, for [debug::waiters]
+	fn waiting(&self) -> debug::Waiting {
+		debug::Waiting::start(self.id, self.name())
+	}
+
+	/// Writes a value while holding the write lock, bumping the version and recording it
+	///
+	/// Returns the version written.
+	fn store(&self, lock: &mut Option<T>, value: Option<T>) -> u64 {
+		let version = self.version.fetch_add(1, Ordering::SeqCst) + 1;
+		if let (Some(history), Some(value)) = (&self.history, &value) {
+			if let Ok(mut entries) = history.entries.lock() {
+				entries.push_back(HistoryEntry {
+					at: SystemTime::now(),
+					version,
+					value: (history.clone_value)(value),
+				});
+				while entries.len() > history.capacity {
+					entries.pop_front();
+				}
+			}
+		}
+		if let Some(persist) = &self.persist {
+			persist(value.as_ref());
+		}
+		*lock = value;
+		version
 	}
 
 	/// Converts the state into a writable state.
@@ -39,7 +182,41 @@ impl<T: Clone + Send + Sync + 'static> State<T> {
 	}
 }
 
-impl<T: Clone + Send + Sync + 'static> WritableState<T> {
+impl<T: Clone + Send + Sync + 'static> State<T> {
+	/// Creates a new empty state which records the last `capacity` values set.
+	///
+	/// The history is meant for post-mortem debugging, e.g. printing the progression of a state
+	/// when a test fails; see [State::history].
+	pub fn with_history(capacity: usize) -> Self {
+		let history = History {
+			capacity,
+			entries: std::sync::Mutex::new(VecDeque::new()),
+			clone_value: T::clone,
+		};
+		Self { history: Some(Arc::new(history)), ..Self::new() }
+	}
+
+	/// Returns the recorded values, oldest first.
+	///
+	/// This is empty unless the state was created with [State::with_history].
+	pub fn history(&self) -> Vec<HistoryEntry<T>> {
+		match &self.history {
+			Some(history) => history
+				.entries
+				.lock()
+				.map(|entries| entries.iter().cloned().collect())
+				.unwrap_or_default(),
+			None => Vec::new(),
+		}
+	}
+}
+
+impl<T: Send + Sync + 'static> WritableState<T> {
+	/// Returns a read-only view of the same state.
+	pub fn read_only(&self) -> ReadOnlyState<T> {
+		self.state.read()
+	}
+
 	/// Returns the write guard for the state.
 	pub async fn write(&self) -> RwLockWriteGuard<'_, Option<T>> {
 		self.state.inner.write().await
@@ -53,17 +230,141 @@ impl<T: Clone + Send + Sync + 'static> WritableState<T> {
 	/// Writes a value into the state and notifies waiting readers.
 	pub async fn set(&self, value: T) {
 		let mut lock = self.state.inner.write().await;
-		*lock = Some(value);
+		self.state.store(&mut lock, Some(value));
+		self.state.notify.notify_waiters();
+	}
+
+	/// Attempts to write a value without waiting for the lock.
+	///
+	/// Returns the value back if the lock is currently held. Waiting readers are only notified
+	/// on success.
+	pub fn try_set(&self, value: T) -> Result<(), T> {
+		match self.state.inner.try_write() {
+			Ok(mut lock) => {
+				self.state.store(&mut lock, Some(value));
+				self.state.notify.notify_waiters();
+				Ok(())
+			}
+			Err(_) => Err(value),
+		}
+	}
+
+	/// Records the current task as the one expected to write the state.
+	///
+	/// Tasks waiting on the state are then known to wait on this task, which lets
+	/// [debug::cycles] detect tasks deadlocked on each other's states. The claim lasts until the
+	/// returned guard is dropped, so hold it for as long as the task is expected to write.
+	pub fn claim(&self) -> debug::Claim {
+		debug::Claim::start(self.state.id)
+	}
+
+	/// Writes a value which expires after the given duration and notifies waiting readers.
+	///
+	/// Once expired, the state is reset: readers see it as unset and [ReadOnlyState::wait_forever]
+	/// waits for a fresh value. A write made before the value expires cancels the expiry.
+	pub async fn set_with_ttl(&self, value: T, ttl: Duration) {
+		let mut lock = self.state.inner.write().await;
+		let version = self.state.store(&mut lock, Some(value));
+		self.state.notify.notify_waiters();
+		drop(lock);
+
+		let state = self.state.clone();
+		tokio::spawn(async move {
+			sleep(ttl).await;
+			let mut lock = state.inner.write().await;
+			if state.version.load(Ordering::SeqCst) == version {
+				state.store(&mut lock, None);
+				state.notify.notify_waiters();
+			}
+		});
+	}
+
+	/// Writes a value only if the state is unset.
+	///
+	/// Returns the value back if the state is already set. This lets several writers publish the
+	/// same dependency without racing, as only the first one wins.
+	pub async fn set_if_unset(&self, value: T) -> Result<(), T> {
+		let mut lock = self.state.inner.write().await;
+		if lock.is_some() {
+			return Err(value);
+		}
+		self.state.store(&mut lock, Some(value));
+		self.state.notify.notify_waiters();
+		Ok(())
+	}
+
+	/// Modifies the value in place and notifies waiting readers.
+	///
+	/// The closure is given the current value, or None if unset, while holding the write lock.
+	/// Unlike [WritableState::update], the value is not cloned, so growing a large value is cheap.
+	pub async fn modify<F>(&self, f: F)
+	where
+		F: FnOnce(&mut Option<T>),
+	{
+		let mut lock = self.state.inner.write().await;
+		let mut value = lock.take();
+		f(&mut value);
+		self.state.store(&mut lock, value);
 		self.state.notify.notify_waiters();
 	}
 
 	/// Resets the value to None and notifies waiting readers.
 	pub async fn reset(&self) {
 		let mut lock = self.state.inner.write().await;
-		*lock = None;
+		self.state.store(&mut lock, None);
 		self.state.notify.notify_waiters();
 	}
 
+	/// Unsets the value and returns it, notifying waiting readers if it was set.
+	///
+	/// This consumes a value used as a signal, so that the next round waits for a fresh one.
+	pub async fn take(&self) -> Option<T> {
+		let mut lock = self.state.inner.write().await;
+		let value = lock.take()?;
+		self.state.store(&mut lock, None);
+		self.state.notify.notify_waiters();
+		Some(value)
+	}
+
+	/// Unsets the value, returning whether it was set; see [WritableState::take].
+	pub async fn clear(&self) -> bool {
+		self.take().await.is_some()
+	}
+}
+
+impl<T: Clone + Send + Sync + 'static> WritableState<T> {
+	/// Replaces the value with the one computed from it and notifies waiting readers.
+	///
+	/// The closure is given the current value, or None if unset, and runs while holding the write
+	/// lock, so no other write can happen in between. Returns the new value.
+	pub async fn update<F>(&self, f: F) -> T
+	where
+		F: FnOnce(Option<&T>) -> T,
+	{
+		let mut lock = self.state.inner.write().await;
+		let value = f(lock.as_ref());
+		self.state.store(&mut lock, Some(value.clone()));
+		self.state.notify.notify_waiters();
+		value
+	}
+
+	/// Writes a value only if the current value equals the expected one.
+	///
+	/// Returns the current value if it differs, in which case nothing is written and waiting
+	/// readers are not notified.
+	pub async fn compare_and_set(&self, expected: &T, value: T) -> Result<(), Option<T>>
+	where
+		T: PartialEq,
+	{
+		let mut lock = self.state.inner.write().await;
+		if lock.as_ref() != Some(expected) {
+			return Err(lock.clone());
+		}
+		self.state.store(&mut lock, Some(value));
+		self.state.notify.notify_waiters();
+		Ok(())
+	}
+
 	/// Gets a clone of the current value if it's set.
 	pub async fn get(&self) -> Option<T> {
 		let lock = self.state.inner.read().await;
@@ -76,15 +377,83 @@ impl<T: Clone + Send + Sync + 'static> WritableState<T> {
 pub enum WaitError {
 	#[error("condition not met: {0}")]
 	Condition(#[source] Box<dyn std::error::Error + Send + Sync>),
+	#[error("timed out after {waited:?} waiting for {}", .state_name.as_deref().unwrap_or("state"))]
+	Timeout { waited: Duration, state_name: Option<String> },
+	#[error("wait cancelled")]
+	Cancelled,
 }
 
+impl WaitError {
+	/// Attributes a timeout to the named state
+	fn for_state(self, name: Option<&str>) -> Self {
+		match self {
+			WaitError::Timeout { waited, state_name: None } => {
+				WaitError::Timeout { waited, state_name: name.map(str::to_string) }
+			}
+			e => e,
+		}
+	}
+}
+
+/// How long to wait for a state before giving up.
+#[derive(Debug, Clone)]
 pub enum WaitCondition {
 	/// Waits up to the given duration
 	Duration(Duration),
+	/// Waits until the given instant
+	Deadline(Instant),
+	/// Checks up to `n` times, `interval` apart, starting immediately
+	Attempts { n: u32, interval: Duration },
+	/// Gives up as soon as either condition does
+	Either(Box<WaitCondition>, Box<WaitCondition>),
 	/// Waits until the state is set
 	Ever,
 }
 
+impl WaitCondition {
+	/// Combines this condition with another, giving up as soon as either does.
+	///
+	/// For example, `WaitCondition::from(Duration::from_secs(30)).or(attempts)` bounds a wait by
+	/// whichever of the duration or the attempts runs out first.
+	pub fn or(self, other: impl Into<WaitCondition>) -> Self {
+		WaitCondition::Either(Box::new(self), Box::new(other.into()))
+	}
+
+	/// Returns how often the state is checked, or None if it is checked on every change
+	///
+	/// A composed condition checks at the shortest interval of its attempts. Attempts without an
+	/// interval between them are all made at once, which is the same as checking on every change.
+	fn interval(&self) -> Option<Duration> {
+		match self {
+			WaitCondition::Attempts { interval, .. } => Some(*interval).filter(|i| !i.is_zero()),
+			WaitCondition::Either(a, b) => match (a.interval(), b.interval()) {
+				(Some(a), Some(b)) => Some(a.min(b)),
+				(a, b) => a.or(b),
+			},
+			_ => None,
+		}
+	}
+
+	/// Resolves once the condition runs out, or never for [WaitCondition::Ever]
+	fn expired(self) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+		match self {
+			WaitCondition::Duration(duration) => Box::pin(sleep(duration)),
+			WaitCondition::Deadline(deadline) => Box::pin(sleep_until(deadline)),
+			// The last attempt is made (n - 1) intervals after the first
+			WaitCondition::Attempts { n, interval } => {
+				Box::pin(sleep(interval.saturating_mul(n.saturating_sub(1))))
+			}
+			WaitCondition::Either(a, b) => Box::pin(async move {
+				tokio::select! {
+					_ = a.expired() => {}
+					_ = b.expired() => {}
+				}
+			}),
+			WaitCondition::Ever => Box::pin(std::future::pending()),
+		}
+	}
+}
+
 /// Waits until the state is set
 pub const EVER: WaitCondition = WaitCondition::Ever;
 
@@ -94,14 +463,197 @@ impl From<Duration> for WaitCondition {
 	}
 }
 
-impl<T: Clone + Send + Sync + 'static> ReadOnlyState<T> {
+impl From<Instant> for WaitCondition {
+	fn from(deadline: Instant) -> Self {
+		WaitCondition::Deadline(deadline)
+	}
+}
+
+/// Runs the future up to a given condition, as used by the [join] macro.
+///
+/// The future is checked one last time when the condition runs out. With
+/// [WaitCondition::Attempts], the future is only polled once per attempt rather than whenever it
+/// is woken.
+#[doc(hidden)]
+pub async fn within<F>(condition: impl Into<WaitCondition>, f: F) -> Result<F::Output, WaitError>
+where
+	F: Future,
+{
+	let started = Instant::now();
+	let condition = condition.into();
+	let interval = match condition.interval() {
+		Some(interval) => interval,
+		None => {
+			return tokio::select! {
+				biased;
+				output = f => Ok(output),
+				_ = condition.expired() => {
+					Err(WaitError::Timeout { waited: started.elapsed(), state_name: None })
+				}
+			};
+		}
+	};
+
+	tokio::pin!(f);
+	let mut expired = condition.expired();
+	let mut attempts = tokio::time::interval_at(started + interval, interval);
+	loop {
+		if let std::task::Poll::Ready(output) = futures::poll!(f.as_mut()) {
+			return Ok(output);
+		}
+		// An attempt due when the condition runs out is still made
+		tokio::select! {
+			biased;
+			_ = attempts.tick() => {}
+			_ = &mut expired => {
+				return Err(WaitError::Timeout { waited: started.elapsed(), state_name: None });
+			}
+		}
+	}
+}
+
+/// Polls an external check until it returns true or the timeout elapses.
+///
+/// This is the pull-based counterpart to [State] for readiness which can't be pushed,
+/// e.g. waiting for a TCP port to accept connections.
+pub async fn wait_poll<F, Fut>(
+	mut check: F,
+	interval: Duration,
+	timeout: Duration,
+) -> Result<(), WaitError>
+where
+	F: FnMut() -> Fut,
+	Fut: Future<Output = bool>,
+{
+	let poll = async {
+		while !check().await {
+			sleep(interval).await;
+		}
+	};
+
+	tokio::time::timeout(timeout, poll)
+		.await
+		.map_err(|_| WaitError::Timeout { waited: timeout, state_name: None })
+}
+
+impl<T: Send + Sync + 'static> ReadOnlyState<T> {
 	/// Returns the read guard for the state.
 	pub async fn read(&self) -> RwLockReadGuard<'_, Option<T>> {
 		self.state.inner.read().await
 	}
 
+	/// Returns the name of the state, if it was given one.
+	pub fn name(&self) -> Option<&str> {
+		self.state.name()
+	}
+
+	/// Returns a read-only state holding the projection of this state's value.
+	///
+	/// The derived state is updated, or reset, whenever this state is written. The projection
+	/// borrows the value, so only the projected part is cloned. Updates are forwarded by a
+	/// background task, which stops at the first write after every reader of the derived state
+	/// is dropped.
+	///
+	/// # Panics
+	///
+	/// Panics if called outside of a tokio runtime.
+	pub fn map<U, F>(&self, f: F) -> ReadOnlyState<U>
+	where
+		U: Send + Sync + 'static,
+		F: Fn(&T) -> U + Send + Sync + 'static,
+	{
+		// Project the current value right away, so the derived state is never behind
+		let (mut seen, initial) = match self.state.inner.try_read() {
+			Ok(lock) => (Some(self.state.version.load(Ordering::SeqCst)), lock.as_ref().map(&f)),
+			Err(_) => (None, None),
+		};
+		let derived = State::with_value(initial);
+		let writer = derived.write();
+		let source = self.clone();
+		tokio::spawn(async move {
+			while Arc::strong_count(&writer.state.inner) > 1 {
+				let notified = source.state.notify.notified();
+				let (version, projected) = {
+					let lock = source.state.inner.read().await;
+					(source.state.version.load(Ordering::SeqCst), lock.as_ref().map(&f))
+				};
+				if seen == Some(version) {
+					notified.await;
+					continue;
+				}
+
+				seen = Some(version);
+				match projected {
+					Some(value) => writer.set(value).await,
+					None => writer.reset().await,
+				}
+			}
+		});
+		derived.read()
+	}
+
+	/// Waits for the state to be unset, returning immediately if it already is.
+	///
+	/// Pairs with [WritableState::take] and [WritableState::clear] to observe a value being
+	/// consumed.
+	pub async fn wait_for_clear(&self) {
+		let mut waiting = None;
+		loop {
+			let notified = self.state.notify.notified();
+			if self.state.inner.read().await.is_none() {
+				return;
+			}
+			if waiting.is_none() {
+				waiting = Some(self.state.waiting());
+			}
+			notified.await;
+		}
+	}
+
+	/// Calls the closure with a reference to the value if it's set, without cloning it.
+	pub async fn with<R, F>(&self, f: F) -> Option<R>
+	where
+		F: FnOnce(&T) -> R,
+	{
+		self.state.inner.read().await.as_ref().map(f)
+	}
+
+	/// Returns a read guard on the value if it's set, without cloning it.
+	///
+	/// Writers wait until the guard is dropped, so hold it briefly.
+	pub async fn read_ref(&self) -> Option<RwLockReadGuard<'_, T>> {
+		RwLockReadGuard::try_map(self.state.inner.read().await, Option::as_ref).ok()
+	}
+
+	/// Waits for the state to be set and returns a read guard on the value, without cloning it.
+	///
+	/// Writers wait until the guard is dropped, so hold it briefly.
+	pub async fn wait_ref(&self) -> RwLockReadGuard<'_, T> {
+		let mut waiting = None;
+		loop {
+			let notified = self.state.notify.notified();
+			if let Ok(value) =
+				RwLockReadGuard::try_map(self.state.inner.read().await, Option::as_ref)
+			{
+				return value;
+			}
+			if waiting.is_none() {
+				waiting = Some(self.state.waiting());
+			}
+			notified.await;
+		}
+	}
+
+	/// Checks if the value is already set.
+	pub async fn is_set(&self) -> bool {
+		self.state.inner.read().await.is_some()
+	}
+}
+
+impl<T: Clone + Send + Sync + 'static> ReadOnlyState<T> {
 	/// Waits for the state to be set and returns the value.
 	pub async fn wait_forever(&self) -> T {
+		let mut waiting = None;
 		loop {
 			// First check if the value is already set
 			if let Some(value) = self.state.inner.read().await.clone() {
@@ -117,6 +669,9 @@ impl<T: Clone + Send + Sync + 'static> ReadOnlyState<T> {
 			}
 
 			// Now wait for notification
+			if waiting.is_none() {
+				waiting = Some(self.state.waiting());
+			}
 			notified.await;
 		}
 	}
@@ -128,22 +683,107 @@ impl<T: Clone + Send + Sync + 'static> ReadOnlyState<T> {
 				Ok(state)
 			}
 			_ = sleep(duration) => {
-				Err(WaitError::Condition("timeout".into()))
+				Err(WaitError::Timeout { waited: duration, state_name: None }
+					.for_state(self.state.name()))
 			}
 		}
 	}
 
 	/// Waits for the state to be set up to a given condition.
 	pub async fn wait_for(&self, condition: impl Into<WaitCondition>) -> Result<T, WaitError> {
-		match condition.into() {
-			WaitCondition::Duration(duration) => self.wait_for_duration(duration).await,
-			WaitCondition::Ever => Ok(self.wait_forever().await),
+		within(condition, self.wait_forever())
+			.await
+			.map_err(|e| e.for_state(self.state.name()))
+	}
+
+	/// Waits for the state to be set up to a given condition, unless cancelled first.
+	///
+	/// Fails with [WaitError::Cancelled] if the cancellation future resolves before the state is
+	/// set, e.g. a shutdown signal.
+	pub async fn wait_for_or_cancel<C>(
+		&self,
+		condition: impl Into<WaitCondition>,
+		cancel: C,
+	) -> Result<T, WaitError>
+	where
+		C: Future,
+	{
+		tokio::select! {
+			biased;
+			result = self.wait_for(condition) => result,
+			_ = cancel => Err(WaitError::Cancelled),
 		}
 	}
 
-	/// Checks if the value is already set.
-	pub async fn is_set(&self) -> bool {
-		self.state.inner.read().await.is_some()
+	/// Returns a stream of the values set, starting with the current value if there is one.
+	///
+	/// Values set faster than the stream is consumed are coalesced into the latest one, and resets
+	/// are skipped. The stream never ends.
+	pub fn changes(&self) -> impl Stream<Item = T> {
+		futures::stream::unfold((self.clone(), None), |(reader, seen)| async move {
+			let (version, value) = reader.wait_versioned(|version, _| seen != Some(version)).await;
+			Some((value, (reader, Some(version))))
+		})
+	}
+
+	/// Gets the current value with its version if it's available.
+	///
+	/// The version starts at zero and increases with every write, including resets, so it tells
+	/// whether the state has been updated since it was last read.
+	pub async fn get_versioned(&self) -> Option<(u64, T)> {
+		let lock = self.state.inner.read().await;
+		let version = self.state.version.load(Ordering::SeqCst);
+		lock.clone().map(|value| (version, value))
+	}
+
+	/// Waits for the state to be set at the given version or a later one.
+	///
+	/// Returns the value with the version it was read at, which may be past the given version.
+	pub async fn wait_for_version(&self, version: u64) -> (u64, T) {
+		self.wait_versioned(|current, _| current >= version).await
+	}
+
+	/// Waits for the state to be set to a value satisfying the predicate up to a given condition.
+	///
+	/// The predicate is re-checked every time the state is written, so no update is missed
+	/// between checks.
+	pub async fn wait_until<P>(
+		&self,
+		predicate: P,
+		condition: impl Into<WaitCondition>,
+	) -> Result<T, WaitError>
+	where
+		P: Fn(&T) -> bool,
+	{
+		let until = async { self.wait_versioned(|_, value| predicate(value)).await.1 };
+		within(condition, until).await.map_err(|e| e.for_state(self.state.name()))
+	}
+
+	/// Waits for the state to be set at a version and to a value accepted by the check
+	async fn wait_versioned<F>(&self, accept: F) -> (u64, T)
+	where
+		F: Fn(u64, &T) -> bool,
+	{
+		let mut waiting = None;
+		loop {
+			let notified = self.state.notify.notified();
+			{
+				let lock = self.state.inner.read().await;
+				let version = self.state.version.load(Ordering::SeqCst);
+				if let Some(value) = lock.as_ref().filter(|value| accept(version, value)) {
+					return (version, value.clone());
+				}
+			}
+			if waiting.is_none() {
+				waiting = Some(self.state.waiting());
+			}
+			notified.await;
+		}
+	}
+
+	/// Returns the recorded values, oldest first; see [State::history].
+	pub fn history(&self) -> Vec<HistoryEntry<T>> {
+		self.state.history()
 	}
 
 	/// Gets the current value if it's available.
@@ -153,6 +793,121 @@ impl<T: Clone + Send + Sync + 'static> ReadOnlyState<T> {
 	}
 }
 
+/// A rendezvous point where a fixed number of parties wait for each other, round after round
+///
+/// The number of completed rounds is published as a [State], so observers can follow the rounds
+/// without taking part in them. Clones share the same barrier.
+#[derive(Clone)]
+pub struct Barrier {
+	parties: usize,
+	/// The number of parties which arrived in the current round, and the index of that round
+	arrivals: Arc<Mutex<(usize, u64)>>,
+	rounds: State<u64>,
+}
+
+impl Barrier {
+	/// Creates a barrier which releases each round once the given number of parties arrived.
+	///
+	/// A barrier for zero parties behaves as one for a single party.
+	pub fn new(parties: usize) -> Self {
+		let rounds = State::with_value(Some(0));
+		Self { parties: parties.max(1), arrivals: Arc::new(Mutex::new((0, 0))), rounds }
+	}
+
+	/// Returns the number of parties in each round.
+	pub fn parties(&self) -> usize {
+		self.parties
+	}
+
+	/// Returns the number of completed rounds as a read-only state.
+	pub fn rounds(&self) -> ReadOnlyState<u64> {
+		self.rounds.read()
+	}
+
+	/// Waits until every party has arrived, returning the index of the round released.
+	///
+	/// An arrival still counts towards its round if the waiting future is dropped.
+	pub async fn arrive(&self) -> u64 {
+		let round = self.join().await;
+		self.released(round).await;
+		round
+	}
+
+	/// Waits until every party has arrived up to a given condition.
+	///
+	/// If the wait times out, the arrival is withdrawn so that the round still waits for every
+	/// party.
+	pub async fn arrive_for(&self, condition: impl Into<WaitCondition>) -> Result<u64, WaitError> {
+		let round = self.join().await;
+		let timeout = match within(condition, self.released(round)).await {
+			Ok(()) => return Ok(round),
+			Err(e) => e,
+		};
+
+		let mut arrivals = self.arrivals.lock().await;
+		if arrivals.1 == round {
+			arrivals.0 -= 1;
+			Err(timeout.for_state(Some("barrier")))
+		} else {
+			// The round was released while the timeout fired
+			Ok(round)
+		}
+	}
+
+	/// Counts an arrival, releasing the round if it was the last party
+	async fn join(&self) -> u64 {
+		let mut arrivals = self.arrivals.lock().await;
+		let round = arrivals.1;
+		arrivals.0 += 1;
+		if arrivals.0 == self.parties {
+			*arrivals = (0, round + 1);
+			// Published while holding the arrivals so that rounds are published in order
+			self.rounds.write().set(round + 1).await;
+		}
+		round
+	}
+
+	/// Waits until the given round has been released
+	async fn released(&self, round: u64) {
+		loop {
+			let notified = self.rounds.notify.notified();
+			if matches!(*self.rounds.inner.read().await, Some(completed) if completed > round) {
+				return;
+			}
+			notified.await;
+		}
+	}
+}
+
+/// Waits concurrently for multiple read-only states to be set
+///
+/// Evaluates to a future resolving to a tuple of the values, preserving each state's type.
+/// States which are already set resolve immediately and do not block on the pending ones.
+#[macro_export]
+macro_rules! wait_all {
+    ($($state:expr),+ $(,)?) => {
+        async { $crate::reexport::tokio::join!($($state.wait_forever()),+) }
+    };
+}
+
+/// Waits concurrently for multiple read-only states to be set, up to a single condition
+///
+/// Evaluates to a future resolving to a `Result` with a tuple of the values, failing if any
+/// state is still unset when the condition runs out. The condition follows the states after a
+/// semicolon, e.g. `join!(a, b; Duration::from_secs(5))`; without one, this waits forever.
+#[macro_export]
+macro_rules! join {
+    ($($state:expr),+ ; $condition:expr) => {
+        $crate::within(
+            $condition,
+            async { $crate::reexport::tokio::join!($($state.wait_forever()),+) },
+        )
+    };
+    ($($state:expr),+ $(,)?) => {
+        $crate::join!($($state),+ ; $crate::EVER)
+    };
+}
+
 #[cfg(test)]
 pub mod test {
 
@@ -200,4 +955,601 @@ pub mod test {
 
 		Ok(())
 	}
+
+	#[tokio::test]
+	async fn test_wait_all() -> Result<(), anyhow::Error> {
+		let a = State::new();
+		let b = State::new();
+		let c = State::new();
+
+		// a is already set, b and c are set later
+		a.write().set(1u64).await;
+
+		let reader_a = a.read();
+		let reader_b = b.read();
+		let reader_c = c.read();
+
+		let writer_b = b.write();
+		let writer_c = c.write();
+		tokio::spawn(async move {
+			sleep(Duration::from_millis(50)).await;
+			writer_b.set("hello".to_string()).await;
+			writer_c.set(true).await;
+		});
+
+		let (value_a, value_b, value_c) = wait_all!(reader_a, reader_b, reader_c).await;
+
+		assert_eq!(value_a, 1);
+		assert_eq!(value_b, "hello".to_string());
+		assert!(value_c);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_wait_poll() -> Result<(), anyhow::Error> {
+		let attempts = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+		let counter = attempts.clone();
+		wait_poll(
+			move || {
+				let counter = counter.clone();
+				async move { counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst) >= 2 }
+			},
+			Duration::from_millis(10),
+			Duration::from_secs(1),
+		)
+		.await?;
+		assert_eq!(attempts.load(std::sync::atomic::Ordering::SeqCst), 3);
+
+		let result =
+			wait_poll(|| async { false }, Duration::from_millis(10), Duration::from_millis(50))
+				.await;
+		assert!(result.is_err());
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_try_set() -> Result<(), anyhow::Error> {
+		let state = State::<u32>::new();
+		let write = state.write();
+		let read = state.read();
+
+		{
+			let _guard = read.read().await;
+			assert_eq!(write.try_set(1), Err(1));
+		}
+		assert_eq!(read.get().await, None);
+
+		assert_eq!(write.try_set(2), Ok(()));
+		assert_eq!(read.wait_for_duration(Duration::from_millis(100)).await?, 2);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_barrier() -> Result<(), anyhow::Error> {
+		let barrier = Barrier::new(3);
+		let rounds = barrier.rounds();
+
+		let parties: Vec<_> = (0..3)
+			.map(|_| {
+				let barrier = barrier.clone();
+				tokio::spawn(async move { (barrier.arrive().await, barrier.arrive().await) })
+			})
+			.collect();
+		for party in parties {
+			assert_eq!(party.await?, (0, 1));
+		}
+		assert_eq!(rounds.get().await, Some(2));
+
+		// A party which times out withdraws, so the round waits for a full set again
+		let result = barrier.arrive_for(Duration::from_millis(20)).await;
+		assert!(matches!(result, Err(WaitError::Timeout { .. })));
+
+		let parties: Vec<_> = (0..3)
+			.map(|_| {
+				let barrier = barrier.clone();
+				tokio::spawn(async move { barrier.arrive_for(Duration::from_secs(5)).await })
+			})
+			.collect();
+		for party in parties {
+			assert_eq!(party.await??, 2);
+		}
+		assert_eq!(rounds.get().await, Some(3));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_changes() -> Result<(), anyhow::Error> {
+		use futures::StreamExt;
+
+		let state = State::<u64>::new();
+		let write = state.write();
+		write.set(1).await;
+
+		let mut changes = Box::pin(state.read().changes());
+		assert_eq!(changes.next().await, Some(1));
+
+		let heights = tokio::spawn(async move {
+			let mut heights = Vec::new();
+			while let Some(height) = changes.next().await {
+				heights.push(height);
+				if height == 3 {
+					break;
+				}
+			}
+			heights
+		});
+
+		// Each update is observed before the next is written
+		for height in 2..=3 {
+			sleep(Duration::from_millis(20)).await;
+			write.reset().await;
+			write.set(height).await;
+		}
+		assert_eq!(heights.await?, vec![2, 3]);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_versions() -> Result<(), anyhow::Error> {
+		let state = State::<&'static str>::new();
+		let write = state.write();
+		let read = state.read();
+		assert_eq!(read.get_versioned().await, None);
+
+		write.set("starting").await;
+		let (since, value) = read.get_versioned().await.expect("state is set");
+		assert_eq!((since, value), (1, "starting"));
+
+		// Waiting past the current version waits for the next write
+		let restarted = tokio::spawn(async move { read.wait_for_version(since + 1).await });
+		sleep(Duration::from_millis(20)).await;
+		assert!(!restarted.is_finished());
+
+		write.reset().await;
+		write.set("restarted").await;
+		assert_eq!(restarted.await?, (3, "restarted"));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_wait_until() -> Result<(), anyhow::Error> {
+		let state = State::<u64>::new();
+		let write = state.write();
+		let read = state.read();
+
+		let result = read.wait_until(|height| *height > 100, Duration::from_millis(20)).await;
+		assert!(matches!(result, Err(WaitError::Timeout { .. })));
+
+		tokio::spawn(async move {
+			for height in (0..=150).step_by(50) {
+				write.set(height).await;
+				sleep(Duration::from_millis(5)).await;
+			}
+		});
+		assert_eq!(read.wait_until(|height| *height > 100, EVER).await?, 150);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_map() -> Result<(), anyhow::Error> {
+		#[derive(Clone)]
+		struct Config {
+			port: u16,
+			_genesis: Vec<u8>,
+		}
+
+		let state = State::new();
+		let write = state.write();
+		write.set(Config { port: 30731, _genesis: vec![0; 1024] }).await;
+
+		let port = state.read().map(|config| config.port);
+		assert_eq!(port.get().await, Some(30731));
+
+		write.set(Config { port: 30732, _genesis: Vec::new() }).await;
+		assert_eq!(port.wait_until(|port| *port == 30732, Duration::from_secs(1)).await?, 30732);
+
+		write.reset().await;
+		wait_poll(
+			|| async { !port.is_set().await },
+			Duration::from_millis(5),
+			Duration::from_secs(1),
+		)
+		.await?;
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_join() -> Result<(), anyhow::Error> {
+		let a = State::new();
+		let b = State::new();
+		let reader_a = a.read();
+		let reader_b = b.read();
+
+		a.write().set(1u64).await;
+		let result = join!(reader_a, reader_b; Duration::from_millis(20)).await;
+		assert!(matches!(result, Err(WaitError::Timeout { .. })));
+
+		let writer_b = b.write();
+		tokio::spawn(async move {
+			sleep(Duration::from_millis(20)).await;
+			writer_b.set("ready".to_string()).await;
+		});
+		let (value_a, value_b) = join!(reader_a, reader_b; Duration::from_secs(1)).await?;
+		assert_eq!((value_a, value_b.as_str()), (1, "ready"));
+		assert_eq!(join!(reader_a, reader_b).await?, (1, "ready".to_string()));
+
+		Ok(())
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn test_wait_conditions() -> Result<(), anyhow::Error> {
+		let state = State::<u32>::new();
+		let read = state.read();
+
+		let started = Instant::now();
+		let result = read.wait_for(WaitCondition::Deadline(started + Duration::from_secs(3))).await;
+		assert!(result.is_err());
+		assert_eq!(started.elapsed(), Duration::from_secs(3));
+
+		let started = Instant::now();
+		let attempts = WaitCondition::Attempts { n: 5, interval: Duration::from_secs(1) };
+		assert!(read.wait_for(attempts.clone()).await.is_err());
+		assert_eq!(started.elapsed(), Duration::from_secs(4));
+
+		// Whichever condition runs out first ends the wait
+		let started = Instant::now();
+		let either = WaitCondition::from(Duration::from_secs(2)).or(attempts);
+		assert!(read.wait_for(either).await.is_err());
+		assert_eq!(started.elapsed(), Duration::from_secs(2));
+
+		let write = state.write();
+		tokio::spawn(async move {
+			sleep(Duration::from_secs(1)).await;
+			write.set(7).await;
+		});
+		let attempts = WaitCondition::Attempts { n: 3, interval: Duration::from_secs(1) };
+		assert_eq!(read.wait_for(attempts).await?, 7);
+
+		// The state is only checked once per attempt, so a value set between attempts is seen at
+		// the next one, and one set after the last attempt is missed
+		let state = State::<u32>::new();
+		let read = state.read();
+		let write = state.write();
+		tokio::spawn(async move {
+			sleep(Duration::from_millis(1500)).await;
+			write.set(7).await;
+		});
+		let started = Instant::now();
+		let attempts = WaitCondition::Attempts { n: 2, interval: Duration::from_secs(1) };
+		assert!(read.wait_for(attempts).await.is_err());
+		assert_eq!(started.elapsed(), Duration::from_secs(1));
+		let attempts = WaitCondition::Attempts { n: 3, interval: Duration::from_secs(1) };
+		assert_eq!(read.wait_for(attempts).await?, 7);
+		assert_eq!(started.elapsed(), Duration::from_secs(2));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_history() -> Result<(), anyhow::Error> {
+		let state = State::with_history(2);
+		let write = state.write();
+		for height in 1..=3u64 {
+			write.set(height).await;
+		}
+		write.reset().await;
+
+		let history = state.read().history();
+		let values: Vec<_> = history.iter().map(|entry| (entry.version, entry.value)).collect();
+		assert_eq!(values, vec![(2, 2), (3, 3)]);
+		assert!(history[0].at <= history[1].at);
+
+		assert!(State::<u64>::new().history().is_empty());
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_atomic_writes() -> Result<(), anyhow::Error> {
+		let state = State::<u64>::new();
+		let write = state.write();
+		let read = state.read();
+
+		// Only the first of several racing writers publishes
+		let results = futures::future::join_all((0..8).map(|id| {
+			let write = write.clone();
+			async move { write.set_if_unset(id).await }
+		}))
+		.await;
+		assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+		let first = read.get().await.expect("state is set");
+
+		// Concurrent updates are not lost
+		futures::future::join_all((0..8).map(|_| {
+			let write = write.clone();
+			async move { write.update(|old| old.copied().unwrap_or(0) + 1).await }
+		}))
+		.await;
+		assert_eq!(read.get().await, Some(first + 8));
+
+		assert_eq!(write.compare_and_set(&0, 100).await, Err(Some(first + 8)));
+		write
+			.compare_and_set(&(first + 8), 100)
+			.await
+			.map_err(|_| anyhow::anyhow!("CAS failed"))?;
+		assert_eq!(read.get().await, Some(100));
+
+		Ok(())
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn test_set_with_ttl() -> Result<(), anyhow::Error> {
+		let state = State::<u16>::new();
+		let write = state.write();
+		let read = state.read();
+
+		write.set_with_ttl(30731, Duration::from_secs(10)).await;
+		assert_eq!(read.get().await, Some(30731));
+		sleep(Duration::from_secs(11)).await;
+		assert_eq!(read.get().await, None);
+
+		// A fresh lease replaces the old one without being expired by it
+		write.set_with_ttl(30732, Duration::from_secs(10)).await;
+		sleep(Duration::from_secs(5)).await;
+		write.set_with_ttl(30733, Duration::from_secs(10)).await;
+		sleep(Duration::from_secs(6)).await;
+		assert_eq!(read.get().await, Some(30733));
+
+		// Once expired, waiting readers block until a fresh value is published
+		sleep(Duration::from_secs(5)).await;
+		assert_eq!(read.get().await, None);
+		let waiter = read.clone();
+		let fresh = tokio::spawn(async move { waiter.wait_forever().await });
+		sleep(Duration::from_secs(1)).await;
+		assert!(!fresh.is_finished());
+		write.set(30734).await;
+		assert_eq!(fresh.await?, 30734);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_take_and_clear() -> Result<(), anyhow::Error> {
+		let state = State::<&'static str>::new();
+		let write = state.write();
+		let read = state.read();
+
+		assert_eq!(write.take().await, None);
+		assert!(!write.clear().await);
+		read.wait_for_clear().await;
+
+		write.set("restart").await;
+		let consumed = read.clone();
+		let cleared = tokio::spawn(async move { consumed.wait_for_clear().await });
+		sleep(Duration::from_millis(20)).await;
+		assert!(!cleared.is_finished());
+
+		assert_eq!(write.take().await, Some("restart"));
+		cleared.await?;
+		assert!(!read.is_set().await);
+
+		write.set("restart").await;
+		assert!(write.clear().await);
+		assert_eq!(read.get().await, None);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_state_map() -> Result<(), anyhow::Error> {
+		let endpoints = StateMap::<u32, String>::new();
+
+		let reader = endpoints.clone();
+		let all = tokio::spawn(async move { reader.wait_for_all([2, 0, 1]).await });
+		let reader = endpoints.clone();
+		let first = tokio::spawn(async move { reader.wait_for_key(&0).await });
+
+		for node in 0..3 {
+			sleep(Duration::from_millis(10)).await;
+			endpoints.set(node, format!("http://node-{}:30731", node)).await;
+		}
+
+		assert_eq!(first.await?, "http://node-0:30731");
+		assert_eq!(
+			all.await?,
+			vec!["http://node-2:30731", "http://node-0:30731", "http://node-1:30731"]
+		);
+
+		assert_eq!(endpoints.remove(&1).await.as_deref(), Some("http://node-1:30731"));
+		assert_eq!(endpoints.get(&1).await, None);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_once_state() -> Result<(), anyhow::Error> {
+		let chain_id = OnceState::<Arc<String>>::new();
+		assert!(chain_id.get().is_none());
+
+		let reader = chain_id.clone();
+		let waiter = tokio::spawn(async move { reader.wait_forever().await.clone() });
+		sleep(Duration::from_millis(10)).await;
+
+		chain_id
+			.set(Arc::new("movement-testnet".to_string()))
+			.map_err(|_| anyhow::anyhow!("set"))?;
+		assert!(chain_id.set(Arc::new("other".to_string())).is_err());
+		assert_eq!(waiter.await?.as_str(), "movement-testnet");
+
+		// The initializer only runs while the state is unset
+		let value = chain_id.get_or_init(|| async { Arc::new("unused".to_string()) }).await;
+		assert_eq!(value.as_str(), "movement-testnet");
+
+		let rpc_url = OnceState::<String>::new();
+		assert!(rpc_url.wait_for(Duration::from_millis(10)).await.is_err());
+		let url = rpc_url.get_or_init(|| async { "http://localhost:30731".to_string() }).await;
+		assert_eq!(url, "http://localhost:30731");
+		assert!(rpc_url.is_set());
+
+		Ok(())
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn test_wait_error_context() -> Result<(), anyhow::Error> {
+		let state = State::<String>::new().named("rpc url");
+		let read = state.read();
+
+		match read.wait_for(Duration::from_secs(5)).await {
+			Err(e @ WaitError::Timeout { .. }) => {
+				assert_eq!(e.to_string(), "timed out after 5s waiting for rpc url");
+			}
+			other => panic!("expected a timeout, got {:?}", other),
+		}
+
+		let cancel = sleep(Duration::from_secs(1));
+		let result = read.wait_for_or_cancel(EVER, cancel).await;
+		assert!(matches!(result, Err(WaitError::Cancelled)));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_persistent() -> Result<(), anyhow::Error> {
+		let dir = tempfile::tempdir()?;
+		let path = dir.path().join("rpc-port.json");
+
+		let state = State::<u16>::persistent(&path)?;
+		assert_eq!(state.read().get().await, None);
+		state.write().set(30731).await;
+
+		// A restarted harness restores the value published before it stopped
+		let restored = State::<u16>::persistent(&path)?;
+		assert_eq!(restored.read().get().await, Some(30731));
+
+		restored.write().reset().await;
+		assert_eq!(State::<u16>::persistent(&path)?.read().get().await, None);
+
+		std::fs::write(&path, "not json")?;
+		assert!(matches!(State::<u16>::persistent(&path), Err(PersistError::Decode(..))));
+
+		Ok(())
+	}
+
+	#[cfg(unix)]
+	#[tokio::test]
+	async fn test_remote_state() -> Result<(), anyhow::Error> {
+		let dir = tempfile::tempdir()?;
+		let state = State::<String>::new();
+		state.write().set("http://localhost:30731".to_string()).await;
+		let server = state.serve(dir.path().join("state.sock"))?;
+
+		// A client sees the current value and publishes back to the serving process
+		let client = RemoteState::<String>::connect(server.path()).await?;
+		assert_eq!(client.read().wait_for(Duration::from_secs(1)).await?, "http://localhost:30731");
+		client.set("http://localhost:30732".to_string()).await?;
+		let read = state.read();
+		read.wait_until(|url| url.ends_with("30732"), Duration::from_secs(1)).await?;
+
+		// Other clients mirror writes made anywhere
+		let other = RemoteState::<String>::connect(server.path()).await?;
+		state.write().set("http://localhost:30733".to_string()).await;
+		for mirror in [client.read(), other.read()] {
+			mirror.wait_until(|url| url.ends_with("30733"), Duration::from_secs(1)).await?;
+		}
+
+		client.reset().await?;
+		other.read().wait_for_clear().await;
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_debug_waiters_and_cycles() -> Result<(), anyhow::Error> {
+		let genesis = State::<u64>::new().named("genesis");
+		let peers = State::<u64>::new().named("peers");
+
+		// Each task claims one state and waits on the other
+		let (genesis_writer, peers_reader) = (genesis.write(), peers.read());
+		let node = tokio::spawn(async move {
+			let _claim = genesis_writer.claim();
+			peers_reader.wait_forever().await
+		});
+		let (peers_writer, genesis_reader) = (peers.write(), genesis.read());
+		let peer = tokio::spawn(async move {
+			let _claim = peers_writer.claim();
+			genesis_reader.wait_forever().await
+		});
+		sleep(Duration::from_millis(20)).await;
+
+		let names: Vec<_> =
+			debug::waiters().into_iter().filter_map(|waiter| waiter.state_name).collect();
+		assert!(names.contains(&"genesis".to_string()));
+		assert!(names.contains(&"peers".to_string()));
+		let (node_id, peer_id) = (node.id(), peer.id());
+		assert!(debug::cycles().iter().any(|cycle| {
+			cycle.len() == 2 && cycle.contains(&node_id) && cycle.contains(&peer_id)
+		}));
+
+		node.abort();
+		peer.abort();
+		let _ = (node.await, peer.await);
+		assert!(!debug::waiters().iter().any(|waiter| waiter.task == Some(node_id)));
+		// Claims end with the tasks holding them
+		assert_eq!(debug::writer(genesis.id), None);
+		assert_eq!(debug::writer(peers.id), None);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_borrowed_reads() -> Result<(), anyhow::Error> {
+		// Values which cannot be cloned can still be shared and read by reference
+		struct Genesis {
+			accounts: Vec<u8>,
+		}
+
+		let state = State::<Genesis>::new();
+		let read = state.read();
+		assert!(read.read_ref().await.is_none());
+
+		let waiter = read.clone();
+		let accounts = tokio::spawn(async move { waiter.wait_ref().await.accounts.len() });
+		state.write().set(Genesis { accounts: vec![0; 4096] }).await;
+		assert_eq!(accounts.await?, 4096);
+
+		assert_eq!(read.with(|genesis| genesis.accounts.len()).await, Some(4096));
+		assert_eq!(read.read_ref().await.map(|genesis| genesis.accounts[0]), Some(0));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_quorum() -> Result<(), anyhow::Error> {
+		let healthy = Quorum::<u32>::new();
+
+		let reader = healthy.clone();
+		let quorum = tokio::spawn(async move { reader.wait_for_quorum(3).await });
+		for validator in 0..2 {
+			healthy.submit(validator).await;
+		}
+		sleep(Duration::from_millis(20)).await;
+		assert!(!quorum.is_finished());
+		let result = healthy.wait_for_quorum_within(3, Duration::from_millis(10)).await;
+		assert!(matches!(result, Err(WaitError::Timeout { .. })));
+
+		healthy.submit(2).await;
+		assert_eq!(quorum.await?, vec![0, 1, 2]);
+		assert_eq!(healthy.len().await, 3);
+
+		Ok(())
+	}
 }
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Arc;
+use tokio::sync::{Notify, RwLock};
+
+/// A map of states keyed by `K`, where readers wait for individual keys to be set
+///
+/// This replaces threading many individual [crate::State]s around, e.g. one per node in a
+/// multi-node test. Clones share the same map.
+pub struct StateMap<K, V> {
+	inner: Arc<RwLock<HashMap<K, V>>>,
+	notify: Arc<Notify>,
+}
+
+impl<K, V> Clone for StateMap<K, V> {
+	fn clone(&self) -> Self {
+		Self { inner: self.inner.clone(), notify: self.notify.clone() }
+	}
+}
+
+impl<K, V> Default for StateMap<K, V> {
+	fn default() -> Self {
+		Self { inner: Arc::new(RwLock::new(HashMap::new())), notify: Arc::new(Notify::new()) }
+	}
+}
+
+impl<K, V> StateMap<K, V>
+where
+	K: Eq + Hash + Clone + Send + Sync + 'static,
+	V: Clone + Send + Sync + 'static,
+{
+	/// Creates a new empty map.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Writes a value for the key and notifies waiting readers.
+	pub async fn set(&self, key: K, value: V) {
+		let mut lock = self.inner.write().await;
+		lock.insert(key, value);
+		self.notify.notify_waiters();
+	}
+
+	/// Unsets the key, returning its value, and notifies waiting readers if it was set.
+	pub async fn remove(&self, key: &K) -> Option<V> {
+		let mut lock = self.inner.write().await;
+		let value = lock.remove(key)?;
+		self.notify.notify_waiters();
+		Some(value)
+	}
+
+	/// Gets a clone of the value for the key if it's set.
+	pub async fn get(&self, key: &K) -> Option<V> {
+		self.inner.read().await.get(key).cloned()
+	}
+
+	/// Returns the keys which are currently set.
+	pub async fn keys(&self) -> Vec<K> {
+		self.inner.read().await.keys().cloned().collect()
+	}
+
+	/// Waits for the key to be set and returns its value.
+	pub async fn wait_for_key(&self, key: &K) -> V {
+		self.wait_with(|map| map.get(key).cloned()).await
+	}
+
+	/// Waits for every key to be set and returns their values, in the order of the keys.
+	pub async fn wait_for_all<I>(&self, keys: I) -> Vec<V>
+	where
+		I: IntoIterator<Item = K>,
+	{
+		let keys: Vec<K> = keys.into_iter().collect();
+		self.wait_with(|map| keys.iter().map(|key| map.get(key).cloned()).collect())
+			.await
+	}
+
+	/// Waits until the check finds what it looks for in the map, re-checking on every write
+	async fn wait_with<F, R>(&self, check: F) -> R
+	where
+		F: Fn(&HashMap<K, V>) -> Option<R>,
+	{
+		loop {
+			// Register for notification before checking to avoid missing a write
+			let notified = self.notify.notified();
+			if let Some(found) = check(&*self.inner.read().await) {
+				return found;
+			}
+			notified.await;
+		}
+	}
+}
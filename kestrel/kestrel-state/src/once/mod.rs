@@ -0,0 +1,91 @@
+use crate::{within, WaitCondition, WaitError};
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::{Notify, OnceCell, SetError};
+
+/// A state which can be set exactly once, for logically write-once dependencies
+///
+/// Readers borrow the value instead of cloning it, which suits large values such as configs.
+/// Clones share the same value.
+pub struct OnceState<T> {
+	cell: Arc<OnceCell<T>>,
+	notify: Arc<Notify>,
+}
+
+impl<T> Clone for OnceState<T> {
+	fn clone(&self) -> Self {
+		Self { cell: self.cell.clone(), notify: self.notify.clone() }
+	}
+}
+
+impl<T> Default for OnceState<T> {
+	fn default() -> Self {
+		Self { cell: Arc::new(OnceCell::new()), notify: Arc::new(Notify::new()) }
+	}
+}
+
+impl<T: Send + Sync + 'static> OnceState<T> {
+	/// Creates a new unset state.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Sets the value and notifies waiting readers.
+	///
+	/// Returns the value back if the state was already set.
+	pub fn set(&self, value: T) -> Result<(), T> {
+		match self.cell.set(value) {
+			Ok(()) => {
+				self.notify.notify_waiters();
+				Ok(())
+			}
+			Err(SetError::AlreadyInitializedError(value) | SetError::InitializingError(value)) => {
+				Err(value)
+			}
+		}
+	}
+
+	/// Returns the value, initializing it with the future if the state is unset.
+	///
+	/// If several callers race, only one future runs and the others wait for its value.
+	pub async fn get_or_init<F, Fut>(&self, f: F) -> &T
+	where
+		F: FnOnce() -> Fut,
+		Fut: Future<Output = T>,
+	{
+		if let Some(value) = self.cell.get() {
+			return value;
+		}
+		let value = self.cell.get_or_init(f).await;
+		// Waking readers when another caller won the race is harmless, as they re-check
+		self.notify.notify_waiters();
+		value
+	}
+
+	/// Gets the value if it's set.
+	pub fn get(&self) -> Option<&T> {
+		self.cell.get()
+	}
+
+	/// Checks if the value is already set.
+	pub fn is_set(&self) -> bool {
+		self.cell.initialized()
+	}
+
+	/// Waits for the state to be set and returns the value.
+	pub async fn wait_forever(&self) -> &T {
+		loop {
+			// Register for notification before checking to avoid missing a set
+			let notified = self.notify.notified();
+			if let Some(value) = self.cell.get() {
+				return value;
+			}
+			notified.await;
+		}
+	}
+
+	/// Waits for the state to be set up to a given condition.
+	pub async fn wait_for(&self, condition: impl Into<WaitCondition>) -> Result<&T, WaitError> {
+		within(condition, self.wait_forever()).await
+	}
+}
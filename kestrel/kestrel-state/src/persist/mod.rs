@@ -0,0 +1,53 @@
+use crate::State;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Errors thrown when restoring a persistent [State]
+#[derive(Debug, thiserror::Error)]
+pub enum PersistError {
+	#[error("failed to access the state file {0:?}: {1}")]
+	Io(PathBuf, #[source] std::io::Error),
+	#[error("failed to decode the state file {0:?}: {1}")]
+	Decode(PathBuf, #[source] serde_json::Error),
+}
+
+/// Writes a snapshot of the state to the path, replacing the previous one
+pub(crate) type Persist<T> = Arc<dyn Fn(Option<&T>) + Send + Sync>;
+
+/// Writes the value as JSON, through a temporary file so that a crash never leaves half a snapshot
+fn snapshot<T: Serialize>(path: &Path, value: Option<&T>) -> Result<(), std::io::Error> {
+	let json = serde_json::to_vec(&value)?;
+	let mut temporary = path.as_os_str().to_owned();
+	temporary.push(".tmp");
+	std::fs::write(&temporary, json)?;
+	std::fs::rename(&temporary, path)
+}
+
+impl<T> State<T>
+where
+	T: Send + Sync + Serialize + DeserializeOwned + 'static,
+{
+	/// Creates a state which is snapshotted to the file at the path on every write.
+	///
+	/// If the file exists, the state is restored from it, so a restarted harness keeps the values
+	/// published before it stopped. Snapshots are written as JSON while holding the write lock;
+	/// a failure to write one is logged rather than failing the write.
+	pub fn persistent(path: impl Into<PathBuf>) -> Result<Self, PersistError> {
+		let path = path.into();
+		let restored = match std::fs::read(&path) {
+			Ok(json) => serde_json::from_slice::<Option<T>>(&json)
+				.map_err(|e| PersistError::Decode(path.clone(), e))?,
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+			Err(e) => return Err(PersistError::Io(path, e)),
+		};
+
+		let persist: Persist<T> = Arc::new(move |value| {
+			if let Err(e) = snapshot(&path, value) {
+				tracing::warn!("failed to snapshot state to {:?}: {}", path, e);
+			}
+		});
+		Ok(Self { persist: Some(persist), ..Self::with_value(restored) })
+	}
+}
@@ -0,0 +1,76 @@
+use crate::{within, WaitCondition, WaitError};
+use std::sync::Arc;
+use tokio::sync::{Notify, RwLock};
+
+/// Collects values submitted by several writers, for readers waiting until enough have reported
+///
+/// For example, each validator submits its health, and a check waits until three of four have.
+/// Clones share the same submissions.
+pub struct Quorum<T> {
+	submissions: Arc<RwLock<Vec<T>>>,
+	notify: Arc<Notify>,
+}
+
+impl<T> Clone for Quorum<T> {
+	fn clone(&self) -> Self {
+		Self { submissions: self.submissions.clone(), notify: self.notify.clone() }
+	}
+}
+
+impl<T> Default for Quorum<T> {
+	fn default() -> Self {
+		Self { submissions: Arc::new(RwLock::new(Vec::new())), notify: Arc::new(Notify::new()) }
+	}
+}
+
+impl<T: Clone + Send + Sync + 'static> Quorum<T> {
+	/// Creates a new quorum without submissions.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Adds a submission and notifies waiting readers.
+	pub async fn submit(&self, value: T) {
+		self.submissions.write().await.push(value);
+		self.notify.notify_waiters();
+	}
+
+	/// Returns the number of submissions so far.
+	pub async fn len(&self) -> usize {
+		self.submissions.read().await.len()
+	}
+
+	/// Checks if nothing has been submitted yet.
+	pub async fn is_empty(&self) -> bool {
+		self.submissions.read().await.is_empty()
+	}
+
+	/// Gets a clone of the submissions so far, in submission order.
+	pub async fn submissions(&self) -> Vec<T> {
+		self.submissions.read().await.clone()
+	}
+
+	/// Waits for at least `n` submissions and returns every submission so far.
+	pub async fn wait_for_quorum(&self, n: usize) -> Vec<T> {
+		loop {
+			// Register for notification before checking to avoid missing a submission
+			let notified = self.notify.notified();
+			{
+				let submissions = self.submissions.read().await;
+				if submissions.len() >= n {
+					return submissions.clone();
+				}
+			}
+			notified.await;
+		}
+	}
+
+	/// Waits for at least `n` submissions up to a given condition.
+	pub async fn wait_for_quorum_within(
+		&self,
+		n: usize,
+		condition: impl Into<WaitCondition>,
+	) -> Result<Vec<T>, WaitError> {
+		within(condition, self.wait_for_quorum(n)).await
+	}
+}
@@ -0,0 +1,205 @@
+use crate::{ReadOnlyState, State};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::unix::OwnedWriteHalf;
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::Mutex;
+use tokio::task::{JoinHandle, JoinSet};
+
+// Each side of a connection sends newline-delimited JSON, one `Option<T>` per write. The serving
+// process holds the authoritative state: it applies what clients send and forwards every write,
+// including their own, back to all of them.
+
+/// Errors thrown by [RemoteState] and [State::serve]
+#[derive(Debug, thiserror::Error)]
+pub enum RemoteError {
+	#[error("remote state io error: {0}")]
+	Io(#[from] std::io::Error),
+	#[error("invalid remote state message: {0}")]
+	Json(#[from] serde_json::Error),
+}
+
+/// Serves a [State] over a Unix socket until dropped, see [State::serve]
+#[derive(Debug)]
+pub struct RemoteStateServer {
+	path: PathBuf,
+	accept: JoinHandle<()>,
+}
+
+impl RemoteStateServer {
+	/// Returns the path of the socket which clients connect to
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+}
+
+impl Drop for RemoteStateServer {
+	fn drop(&mut self) {
+		// Dropping the accept loop also drops, and so aborts, every connection
+		self.accept.abort();
+		let _ = std::fs::remove_file(&self.path);
+	}
+}
+
+impl<T> State<T>
+where
+	T: Send + Sync + Serialize + DeserializeOwned + 'static,
+{
+	/// Serves the state over a Unix socket at the path, for [RemoteState::connect].
+	///
+	/// This lets a child process, e.g. one spawned as a `Bin`, publish and read the state
+	/// directly. Pass it the socket path through an argument or environment variable.
+	///
+	/// # Panics
+	///
+	/// Panics if called outside of a tokio runtime.
+	pub fn serve(&self, path: impl Into<PathBuf>) -> Result<RemoteStateServer, RemoteError> {
+		let path = path.into();
+		let listener = UnixListener::bind(&path)?;
+		let state = self.clone();
+		let accept = tokio::spawn(async move {
+			let mut connections = JoinSet::new();
+			loop {
+				tokio::select! {
+					accepted = listener.accept() => match accepted {
+						Ok((stream, _)) => {
+							connections.spawn(serve_connection(state.clone(), stream));
+						}
+						Err(e) => {
+							tracing::warn!("failed to accept a remote state connection: {}", e);
+							return;
+						}
+					},
+					// Reaps finished connections
+					Some(_) = connections.join_next() => {}
+				}
+			}
+		});
+		Ok(RemoteStateServer { path, accept })
+	}
+}
+
+/// Sends every write of the state to a client, starting with the current value
+async fn forward_writes<T>(state: &State<T>, write: &mut OwnedWriteHalf) -> Result<(), RemoteError>
+where
+	T: Send + Sync + Serialize + 'static,
+{
+	let mut seen = None;
+	loop {
+		let notified = state.notify.notified();
+		// Encoded while holding the read lock, so that the value is not cloned
+		let (version, encoded) = {
+			let lock = state.inner.read().await;
+			(state.version.load(Ordering::SeqCst), serde_json::to_vec(&lock.as_ref()))
+		};
+		if seen == Some(version) {
+			notified.await;
+			continue;
+		}
+
+		seen = Some(version);
+		let mut line = encoded?;
+		line.push(b'\n');
+		write.write_all(&line).await?;
+	}
+}
+
+/// Applies the writes sent by a client and forwards every write to it, until it disconnects
+async fn serve_connection<T>(state: State<T>, stream: UnixStream)
+where
+	T: Send + Sync + Serialize + DeserializeOwned + 'static,
+{
+	let (read, mut write) = stream.into_split();
+
+	let apply = async {
+		let writer = state.write();
+		let mut lines = BufReader::new(read).lines();
+		while let Some(line) = lines.next_line().await? {
+			match serde_json::from_str::<Option<T>>(&line)? {
+				Some(value) => writer.set(value).await,
+				None => writer.reset().await,
+			}
+		}
+		Ok::<_, RemoteError>(())
+	};
+
+	let result: Result<(), RemoteError> = tokio::select! {
+		result = forward_writes(&state, &mut write) => result,
+		result = apply => result,
+	};
+	if let Err(e) = result {
+		tracing::warn!("remote state connection failed: {}", e);
+	}
+}
+
+/// A mirror of a [State] served by another process, see [State::serve]
+///
+/// Writes are sent to the serving process, and every write made there, by any process, is
+/// mirrored into a local state readable through [RemoteState::read].
+pub struct RemoteState<T: Send + Sync + 'static> {
+	mirror: State<T>,
+	write: Mutex<OwnedWriteHalf>,
+	receive: JoinHandle<()>,
+}
+
+impl<T> RemoteState<T>
+where
+	T: Send + Sync + Serialize + DeserializeOwned + 'static,
+{
+	/// Connects to a state served at the socket path.
+	pub async fn connect(path: impl Into<PathBuf>) -> Result<Self, RemoteError> {
+		let stream = UnixStream::connect(path.into()).await?;
+		let (read, write) = stream.into_split();
+
+		let mirror = State::new();
+		let writer = mirror.write();
+		let receive = tokio::spawn(async move {
+			let mut lines = BufReader::new(read).lines();
+			while let Ok(Some(line)) = lines.next_line().await {
+				match serde_json::from_str::<Option<T>>(&line) {
+					Ok(Some(value)) => writer.set(value).await,
+					Ok(None) => writer.reset().await,
+					Err(e) => {
+						tracing::warn!("failed to decode remote state: {}", e);
+						return;
+					}
+				}
+			}
+		});
+
+		Ok(Self { mirror, write: Mutex::new(write), receive })
+	}
+
+	/// Returns the local mirror of the state.
+	pub fn read(&self) -> ReadOnlyState<T> {
+		self.mirror.read()
+	}
+
+	/// Sends a value to the serving process.
+	///
+	/// The value is visible through [RemoteState::read] once the serving process has applied it.
+	pub async fn set(&self, value: T) -> Result<(), RemoteError> {
+		self.send(Some(&value)).await
+	}
+
+	/// Resets the value in the serving process.
+	pub async fn reset(&self) -> Result<(), RemoteError> {
+		self.send(None).await
+	}
+
+	async fn send(&self, value: Option<&T>) -> Result<(), RemoteError> {
+		let mut line = serde_json::to_vec(&value)?;
+		line.push(b'\n');
+		self.write.lock().await.write_all(&line).await?;
+		Ok(())
+	}
+}
+
+impl<T: Send + Sync + 'static> Drop for RemoteState<T> {
+	fn drop(&mut self) {
+		self.receive.abort();
+	}
+}
@@ -3,7 +3,10 @@
 
 #[cfg(test)]
 mod tests {
-	use kestrel::{abort, await_allow_abort, end};
+	use kestrel::{
+		abort, abort_report, await_allow_abort, await_any, end, join_all, select_first, shutdown,
+		try_await_allow_abort,
+	};
 	use tokio::time::sleep;
 	use tokio::time::Duration;
 
@@ -79,4 +82,1196 @@ mod tests {
 		let result = await_allow_abort!(task1, task2, task3);
 		assert!(result.is_ok());
 	}
+
+	#[tokio::test]
+	async fn test_into_parts_detaches_task() -> Result<(), anyhow::Error> {
+		let task = kestrel::task(async {
+			sleep(Duration::from_millis(100)).await;
+			1
+		});
+
+		let (handle, abort_handle) = task.into_parts();
+		assert!(!abort_handle.is_aborted());
+
+		// The task was not aborted when the Task struct was consumed
+		let result = handle.await?;
+		assert_eq!(result.ok(), Some(1));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_spawn_into_join_set() -> Result<(), anyhow::Error> {
+		let mut set = tokio::task::JoinSet::new();
+
+		let task1 = kestrel::task(async {
+			sleep(Duration::from_millis(50)).await;
+			1
+		});
+		let task2 = kestrel::task(async {
+			sleep(Duration::from_secs(10)).await;
+			2
+		});
+
+		task1.spawn_into(&mut set);
+		let abort_handle = task2.spawn_into(&mut set);
+		abort_handle.abort();
+
+		let mut values = Vec::new();
+		let mut aborted = 0;
+		while let Some(result) = set.join_next().await {
+			match result? {
+				Ok(value) => values.push(value),
+				Err(_) => aborted += 1,
+			}
+		}
+
+		assert_eq!(values, vec![1]);
+		assert_eq!(aborted, 1);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_run_to_state() -> Result<(), anyhow::Error> {
+		use kestrel::process::command::Command;
+		use kestrel::ProcessToState;
+
+		// Without captured output, the final view still comes from the accumulated lines
+		let command = Command::line(
+			"sh",
+			["-c", "echo first && sleep 0.2 && echo second"],
+			None,
+			false,
+			vec![],
+			vec![],
+		);
+		let (output, task) = command.run_to_state();
+
+		// The live view has the first line before the process exits
+		let live = output.wait_for(Duration::from_secs(5)).await?;
+		assert_eq!(live, "first\n");
+
+		let result = task.await??;
+		assert_eq!(result, "first\nsecond\n");
+		assert_eq!(output.get().await, Some("first\nsecond\n".to_string()));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_abort_report() {
+		let finished = kestrel::task(async { Ok::<_, ()>(1) });
+		let running = kestrel::task(async {
+			sleep(Duration::from_secs(1)).await;
+			Ok::<_, ()>("hello")
+		});
+
+		// Let the first task finish
+		sleep(Duration::from_millis(50)).await;
+
+		let report = abort_report!(finished, running);
+
+		assert_eq!(report, vec![(finished.id(), false), (running.id(), true)]);
+		assert_ne!(finished.id(), running.id());
+		assert!(running.is_aborted());
+	}
+
+	#[tokio::test]
+	async fn test_task_limited() -> Result<(), anyhow::Error> {
+		use std::sync::atomic::{AtomicUsize, Ordering};
+		use std::sync::Arc;
+
+		let permits = Arc::new(tokio::sync::Semaphore::new(2));
+		let running = Arc::new(AtomicUsize::new(0));
+		let max_running = Arc::new(AtomicUsize::new(0));
+
+		let tasks = (0..5)
+			.map(|_| {
+				let running = running.clone();
+				let max_running = max_running.clone();
+				kestrel::task_limited(&permits, async move {
+					let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+					max_running.fetch_max(now, Ordering::SeqCst);
+					sleep(Duration::from_millis(50)).await;
+					running.fetch_sub(1, Ordering::SeqCst);
+				})
+			})
+			.collect::<Vec<_>>();
+
+		for task in tasks {
+			task.await?;
+		}
+
+		assert_eq!(max_running.load(Ordering::SeqCst), 2);
+		assert_eq!(permits.available_permits(), 2);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_task_catch_panic() -> Result<(), anyhow::Error> {
+		let panicking = kestrel::task_catch_panic(async {
+			if true {
+				panic!("indexer failed at block {}", 42);
+			}
+			1
+		});
+		let result = panicking.await?;
+		assert_eq!(result, Err(kestrel::Panicked("indexer failed at block 42".to_string())));
+
+		let static_panic = kestrel::task_catch_panic(async {
+			panic!("static message");
+		});
+		let result: Result<(), _> = static_panic.await?;
+		assert_eq!(result, Err(kestrel::Panicked("static message".to_string())));
+
+		let fine = kestrel::task_catch_panic(async { 1 });
+		assert_eq!(fine.await?, Ok(1));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_task_group() -> Result<(), anyhow::Error> {
+		let mut group = kestrel::TaskGroup::new();
+		group.spawn("indexer", async {
+			sleep(Duration::from_secs(30)).await;
+			1
+		});
+		group.spawn("migrator", async { 2 });
+		assert_eq!(group.names().collect::<Vec<_>>(), vec!["indexer", "migrator"]);
+
+		sleep(Duration::from_millis(50)).await;
+		assert_eq!(group.status("indexer"), Some(kestrel::TaskStatus::Running));
+		assert_eq!(group.status("migrator"), Some(kestrel::TaskStatus::Finished));
+		assert_eq!(group.status("missing"), None);
+
+		assert!(group.abort("indexer"));
+		assert_eq!(group.status("indexer"), Some(kestrel::TaskStatus::Aborted));
+
+		group.end_all().await?;
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_task_group_await_all_reports_every_error() -> Result<(), anyhow::Error> {
+		let mut group = kestrel::TaskGroup::new();
+		group.spawn("first", async { 1 });
+		group.spawn("second", async { 2 });
+		let values = group.await_all().await?;
+		assert_eq!(values, vec![("first".to_string(), 1), ("second".to_string(), 2)]);
+
+		let mut group = kestrel::TaskGroup::new();
+		group.spawn("ok", async { 1 });
+		group.spawn("a", async { panic!("a failed") });
+		group.spawn("b", async { panic!("b failed") });
+		match group.await_all().await {
+			Err(kestrel::TaskError::MultipleErrors(errors)) => assert_eq!(errors.len(), 2),
+			other => panic!("expected multiple errors, got {:?}", other),
+		}
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_supervisor_restart_policies() -> Result<(), anyhow::Error> {
+		use std::sync::atomic::{AtomicU32, Ordering};
+		use std::sync::Arc;
+
+		let mut supervisor = kestrel::Supervisor::new();
+		supervisor.backoff(Duration::from_millis(1), Duration::from_millis(10));
+
+		// Fails twice, then succeeds
+		let attempts = Arc::new(AtomicU32::new(0));
+		let counter = attempts.clone();
+		supervisor.supervise("flaky", kestrel::RestartPolicy::OnFailure, move || {
+			let attempt = counter.fetch_add(1, Ordering::SeqCst);
+			async move {
+				if attempt < 2 {
+					panic!("crashed on attempt {}", attempt);
+				}
+				Ok::<_, std::io::Error>(attempt)
+			}
+		});
+
+		// Always fails, so gives up after the retries
+		supervisor.supervise("broken", kestrel::RestartPolicy::MaxRetries(3), || async {
+			Err::<u32, _>(std::io::Error::other("broken"))
+		});
+
+		supervisor.supervise("once", kestrel::RestartPolicy::Never, || async {
+			Err::<u32, _>(std::io::Error::other("once"))
+		});
+
+		let results = supervisor.await_all().await?;
+		assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+		let mut results = results.into_iter();
+		assert!(matches!(results.next(), Some((_, Ok(2)))));
+		assert!(matches!(
+			results.next(),
+			Some((_, Err(kestrel::SupervisorError::Failed { restarts: 3, .. })))
+		));
+		assert!(matches!(
+			results.next(),
+			Some((_, Err(kestrel::SupervisorError::Failed { restarts: 0, .. })))
+		));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_supervisor_always_restarts() -> Result<(), anyhow::Error> {
+		let mut supervisor = kestrel::Supervisor::new();
+		supervisor.backoff(Duration::from_millis(1), Duration::from_millis(1));
+		supervisor.supervise("ticker", kestrel::RestartPolicy::Always, || async {
+			Ok::<_, std::io::Error>(())
+		});
+
+		sleep(Duration::from_millis(100)).await;
+		assert!(supervisor.restarts("ticker").unwrap_or_default() > 1);
+		assert_eq!(supervisor.status("ticker"), Some(kestrel::TaskStatus::Running));
+
+		supervisor.end_all().await?;
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_task_graph_runs_in_dependency_order() -> Result<(), anyhow::Error> {
+		use std::sync::{Arc, Mutex};
+
+		let order = Arc::new(Mutex::new(Vec::new()));
+		let config = kestrel::State::new();
+		let config_writer = config.write();
+
+		let mut graph = kestrel::TaskGraph::new();
+		for (name, dependencies) in
+			[("indexer", &["db", "node"][..]), ("db", &[][..]), ("node", &["db"][..])]
+		{
+			let order = order.clone();
+			graph.add(name, dependencies, async move {
+				order.lock().unwrap().push(name);
+				Ok::<_, std::io::Error>(name.len())
+			});
+		}
+		graph.depends_on_state("db", config.read());
+
+		let setter = kestrel::task(async move {
+			sleep(Duration::from_millis(50)).await;
+			config_writer.set("ready").await;
+		});
+
+		let values = graph.run().await?;
+		setter.await?;
+
+		assert_eq!(*order.lock().unwrap(), vec!["db", "node", "indexer"]);
+		assert_eq!(values.get("indexer"), Some(&7));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_task_graph_fails_fast() -> Result<(), anyhow::Error> {
+		let mut graph = kestrel::TaskGraph::new();
+		graph.add("db", &[], async { Err::<(), _>(std::io::Error::other("db down")) });
+		graph.add("indexer", &["db"], async { Ok::<_, std::io::Error>(()) });
+		graph.add("slow", &[], async {
+			sleep(Duration::from_secs(30)).await;
+			Ok::<_, std::io::Error>(())
+		});
+
+		let result = tokio::time::timeout(Duration::from_secs(5), graph.run()).await?;
+		assert!(
+			matches!(result, Err(kestrel::TaskGraphError::Failed { task, .. }) if task == "db")
+		);
+
+		let mut graph = kestrel::TaskGraph::new();
+		graph.add("a", &["b"], async { Ok::<_, std::io::Error>(()) });
+		graph.add("b", &["a"], async { Ok::<_, std::io::Error>(()) });
+		assert!(matches!(graph.run().await, Err(kestrel::TaskGraphError::Cycle(_))));
+
+		let mut graph = kestrel::TaskGraph::new();
+		graph.add("a", &["missing"], async { Ok::<_, std::io::Error>(()) });
+		assert!(matches!(
+			graph.run().await,
+			Err(kestrel::TaskGraphError::UnknownDependency { .. })
+		));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_join_all() -> Result<(), anyhow::Error> {
+		let task1 = kestrel::task(async {
+			sleep(Duration::from_millis(50)).await;
+			1
+		});
+		let task2 = kestrel::task(async { "hello" });
+		let (value1, value2) = join_all!(task1, task2)?;
+		assert_eq!(value1, 1);
+		assert_eq!(value2, "hello");
+
+		// Inline tasks are spawned once each
+		let spawned = std::sync::atomic::AtomicUsize::new(0);
+		let spawn = |value: u32| {
+			spawned.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+			kestrel::task(async move { value })
+		};
+		let (first, second) = join_all!(spawn(1), spawn(2))?;
+		assert_eq!((first, second), (1, 2));
+		assert_eq!(spawned.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+		// Every failure is reported, and the straggler is aborted by the panic
+		let failing1 = kestrel::task(async { panic!("first") });
+		let failing2 = kestrel::task(async {
+			sleep(Duration::from_millis(50)).await;
+			panic!("second")
+		});
+		let straggler = kestrel::task(async {
+			sleep(Duration::from_secs(30)).await;
+			3
+		});
+		let result: Result<((), (), i32), _> =
+			tokio::time::timeout(Duration::from_secs(5), async {
+				join_all!(failing1, failing2, straggler)
+			})
+			.await?;
+		match result {
+			Err(kestrel::TaskError::MultipleErrors(errors)) => assert_eq!(errors.len(), 3),
+			other => panic!("expected multiple errors, got {:?}", other),
+		}
+
+		let values = kestrel::join_all(vec![
+			kestrel::task(async { 1 }),
+			kestrel::task(async { 2 }),
+			kestrel::task(async { 3 }),
+		])
+		.await?;
+		assert_eq!(values, vec![1, 2, 3]);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_select_first() -> Result<(), anyhow::Error> {
+		let slow = kestrel::task(async {
+			sleep(Duration::from_secs(30)).await;
+			"slow"
+		});
+		let slow_abort = slow.abort_handle.clone();
+		let crashed = kestrel::task(async { panic!("endpoint down") });
+		let fast = kestrel::task(async {
+			sleep(Duration::from_millis(50)).await;
+			"fast"
+		});
+
+		let value = select_first!(slow, crashed, fast).await?;
+		assert_eq!(value, "fast");
+		assert!(slow_abort.is_aborted());
+
+		let failing1 = kestrel::task(async { panic!("first") });
+		let failing2 = kestrel::task(async { panic!("second") });
+		let result: Result<(), _> = select_first!(failing1, failing2).await;
+		assert!(
+			matches!(result, Err(kestrel::TaskError::MultipleErrors(errors)) if errors.len() == 2)
+		);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_task_with_timeout() -> Result<(), anyhow::Error> {
+		let fast = kestrel::task_with_timeout(Duration::from_secs(5), async { 1 });
+		assert_eq!(fast.await?, 1);
+
+		let slow = kestrel::task_with_timeout(Duration::from_millis(50), async {
+			sleep(Duration::from_secs(30)).await;
+			1
+		});
+		let abort_handle = slow.abort_handle.clone();
+		let result = slow.await;
+		assert!(
+			matches!(result, Err(kestrel::TaskError::TimedOut(timeout)) if timeout == Duration::from_millis(50))
+		);
+		assert!(abort_handle.is_aborted());
+
+		let slow = kestrel::task(async {
+			sleep(Duration::from_secs(30)).await;
+			1
+		})
+		.with_timeout(Duration::from_millis(50));
+		assert!(matches!(slow.maybe().await, Err(kestrel::TaskError::TimedOut(_))));
+
+		// The deadline applies while the task is held without being awaited
+		let held = kestrel::task_with_timeout(Duration::from_millis(50), async {
+			sleep(Duration::from_secs(30)).await;
+		});
+		sleep(Duration::from_millis(200)).await;
+		assert_eq!(held.status(), kestrel::TaskStatus::Aborted);
+		assert!(matches!(held.await, Err(kestrel::TaskError::TimedOut(_))));
+
+		// The timer is not registered alongside the task, i.e. spawned from within kestrel
+		let timed = kestrel::task_with_timeout(Duration::from_secs(5), async {
+			sleep(Duration::from_secs(30)).await;
+		});
+		let after = kestrel::task(async {});
+		assert!(!kestrel::tasks().iter().any(|info| info.id > timed.id()
+			&& info.id < after.id()
+			&& info.location.file() != file!()));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_shutdown() -> Result<(), anyhow::Error> {
+		use std::sync::atomic::{AtomicBool, Ordering};
+		use std::sync::Arc;
+
+		let token = kestrel::ShutdownToken::new();
+		let cleaned_up = Arc::new(AtomicBool::new(false));
+
+		let cleaned = cleaned_up.clone();
+		let graceful = kestrel::task_with_shutdown(&token, |token| async move {
+			token.wait().await;
+			cleaned.store(true, Ordering::SeqCst);
+		});
+		let stubborn = kestrel::task_with_shutdown(&token, |_token| async move {
+			sleep(Duration::from_secs(30)).await;
+		});
+		let stubborn_abort = stubborn.abort_handle.clone();
+
+		let started = tokio::time::Instant::now();
+		shutdown!(token, Duration::from_millis(100), graceful, stubborn)?;
+
+		assert!(cleaned_up.load(Ordering::SeqCst));
+		assert!(stubborn_abort.is_aborted());
+		assert!(started.elapsed() < Duration::from_secs(5));
+		assert!(token.is_signaled());
+
+		// Waiting after the signal returns immediately
+		token.wait().await;
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_scope() -> Result<(), anyhow::Error> {
+		use std::sync::atomic::{AtomicBool, Ordering};
+		use std::sync::Arc;
+
+		let finished = Arc::new(AtomicBool::new(false));
+		let flag = finished.clone();
+		let value = kestrel::scope(|s| async move {
+			s.spawn(async move {
+				sleep(Duration::from_secs(30)).await;
+				flag.store(true, Ordering::SeqCst);
+				Ok::<_, std::io::Error>(())
+			});
+			s.spawn(async { Ok::<_, std::io::Error>(()) });
+			42
+		})
+		.await?;
+		assert_eq!(value, 42);
+		assert!(!finished.load(Ordering::SeqCst));
+
+		let result = kestrel::scope(|s| async move {
+			s.spawn(async { Err::<(), _>(std::io::Error::other("container failed")) });
+			s.spawn(async {
+				if true {
+					panic!("indexer crashed");
+				}
+				Ok::<_, std::io::Error>(())
+			});
+			sleep(Duration::from_millis(50)).await;
+		})
+		.await;
+		let errors = result.unwrap_err().errors;
+		assert_eq!(errors.len(), 2);
+		assert!(errors[0].to_string().contains("container failed"));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_retry() -> Result<(), anyhow::Error> {
+		use std::sync::atomic::{AtomicU32, Ordering};
+		use std::sync::Arc;
+
+		let policy = kestrel::RetryPolicy::new(5).with_backoff(kestrel::Backoff::Exponential {
+			initial: Duration::from_millis(1),
+			max: Duration::from_millis(4),
+		});
+		assert_eq!(policy.delay(0), Duration::from_millis(1));
+		assert_eq!(policy.delay(1), Duration::from_millis(2));
+		assert_eq!(policy.delay(10), Duration::from_millis(4));
+
+		let attempts = Arc::new(AtomicU32::new(0));
+		let counter = attempts.clone();
+		let value = kestrel::retry(policy, move || {
+			let attempt = counter.fetch_add(1, Ordering::SeqCst);
+			async move {
+				if attempt < 2 {
+					Err("daemon not up yet")
+				} else {
+					Ok(attempt)
+				}
+			}
+		})
+		.await?
+		.map_err(anyhow::Error::msg)?;
+		assert_eq!(value, 2);
+
+		let attempts = Arc::new(AtomicU32::new(0));
+		let counter = attempts.clone();
+		let policy = kestrel::RetryPolicy::new(3)
+			.with_backoff(kestrel::Backoff::Fixed(Duration::from_millis(2)))
+			.with_jitter(true);
+		let result = kestrel::retry(policy, move || {
+			counter.fetch_add(1, Ordering::SeqCst);
+			async { Err::<(), _>("still down") }
+		})
+		.await?;
+		assert_eq!(result, Err("still down"));
+		assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+		let jittered = policy.delay(0);
+		assert!(jittered >= Duration::from_millis(1) && jittered <= Duration::from_millis(2));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_task_registry() -> Result<(), anyhow::Error> {
+		let hung = kestrel::task(async {
+			sleep(Duration::from_secs(30)).await;
+		});
+		let id = hung.id();
+
+		let info = kestrel::tasks()
+			.into_iter()
+			.find(|info| info.id == id)
+			.expect("spawned task should be registered");
+		assert_eq!(info.location.file(), file!());
+		assert_eq!(info.name, None);
+
+		info.abort();
+		hung.await_allow_abort().await?;
+		assert!(kestrel::tasks().iter().all(|info| info.id != id));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_task_named() -> Result<(), anyhow::Error> {
+		let named = kestrel::task_named("validator-follower", async {
+			sleep(Duration::from_secs(30)).await;
+		});
+
+		let info = kestrel::tasks()
+			.into_iter()
+			.find(|info| info.id == named.id())
+			.expect("named task should be registered");
+		assert_eq!(info.name.as_deref(), Some("validator-follower"));
+
+		end!(named)?;
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_panic_report() -> Result<(), anyhow::Error> {
+		let panicking = kestrel::task(async {
+			if true {
+				panic!("block {} has no parent", 7);
+			}
+		});
+
+		match panicking.await {
+			Err(kestrel::TaskError::Panicked { message, backtrace }) => {
+				assert_eq!(message, "block 7 has no parent");
+				// Backtraces follow RUST_BACKTRACE, as for panics outside of tasks
+				let enabled = std::backtrace::Backtrace::capture().status()
+					== std::backtrace::BacktraceStatus::Captured;
+				assert_eq!(backtrace.is_some(), enabled);
+			}
+			other => panic!("expected a panic report, got {:?}", other),
+		}
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_end_concurrent() -> Result<(), anyhow::Error> {
+		let task1 = kestrel::task(async {
+			sleep(Duration::from_secs(30)).await;
+			1
+		});
+		let task2 = kestrel::task(async {
+			sleep(Duration::from_secs(30)).await;
+			"hello"
+		});
+		end!(task1, task2)?;
+
+		let tasks = (0..3)
+			.map(|i| {
+				kestrel::task(async move {
+					sleep(Duration::from_secs(30)).await;
+					i
+				})
+			})
+			.collect();
+		kestrel::end_concurrent(tasks, Some(Duration::from_secs(5))).await?;
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_task_completion() -> Result<(), anyhow::Error> {
+		let finished = kestrel::task(async {
+			sleep(Duration::from_millis(50)).await;
+			1
+		});
+		let mut completion = finished.subscribe();
+		let (sender, receiver) = tokio::sync::oneshot::channel();
+		finished.on_complete(move |status| {
+			let _ = sender.send(status);
+		});
+
+		completion.wait_for(|status| *status != kestrel::TaskStatus::Running).await?;
+		assert_eq!(*completion.borrow(), kestrel::TaskStatus::Finished);
+		assert_eq!(receiver.await?, kestrel::TaskStatus::Finished);
+		assert_eq!(finished.await?, 1);
+
+		let aborted = kestrel::task(async {
+			sleep(Duration::from_secs(30)).await;
+		});
+		let (sender, receiver) = tokio::sync::oneshot::channel();
+		aborted.on_complete(move |status| {
+			let _ = sender.send(status);
+		});
+		drop(aborted);
+		assert_eq!(receiver.await?, kestrel::TaskStatus::Aborted);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_blocking_task() -> Result<(), anyhow::Error> {
+		use std::sync::atomic::{AtomicBool, Ordering};
+		use std::sync::Arc;
+
+		let value = kestrel::blocking_task(|| {
+			std::thread::sleep(Duration::from_millis(10));
+			1
+		})
+		.await?;
+		assert_eq!(value, 1);
+
+		// The current-thread test runtime has not started the task yet, so it never runs
+		let ran = Arc::new(AtomicBool::new(false));
+		let flag = ran.clone();
+		let prevented = kestrel::blocking_task(move || flag.store(true, Ordering::SeqCst));
+		prevented.abort();
+		prevented.await_allow_abort().await?;
+		sleep(Duration::from_millis(50)).await;
+		assert!(!ran.load(Ordering::SeqCst));
+
+		let running = kestrel::blocking_task(|| std::thread::sleep(Duration::from_millis(100)));
+		sleep(Duration::from_millis(20)).await;
+		running.abort();
+		assert_eq!(running.status(), kestrel::TaskStatus::Aborted);
+		running.await_allow_abort().await?;
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_deterministic_runtime() -> Result<(), anyhow::Error> {
+		kestrel::testing::test_runtime()?.block_on(async {
+			let state = kestrel::State::<u32>::new();
+			let read = state.read();
+			let write = state.write();
+
+			// The clock jumps ahead while idle, so an hour-long wait times out immediately
+			let started = std::time::Instant::now();
+			assert!(read.wait_for_duration(Duration::from_secs(3600)).await.is_err());
+			assert!(started.elapsed() < Duration::from_secs(5));
+
+			let setter = kestrel::task(async move {
+				sleep(Duration::from_secs(10)).await;
+				write.set(1).await;
+			});
+			// Let the setter register its timer before moving the clock
+			tokio::task::yield_now().await;
+			kestrel::testing::advance(Duration::from_secs(5)).await;
+			assert!(!read.is_set().await);
+
+			kestrel::testing::advance(Duration::from_secs(5)).await;
+			assert_eq!(read.wait_for_duration(Duration::from_secs(1)).await?, 1);
+			setter.await?;
+
+			Ok(())
+		})
+	}
+
+	#[tokio::test]
+	async fn test_child_tasks_abort_with_parent() -> Result<(), anyhow::Error> {
+		let network = kestrel::task(async {
+			sleep(Duration::from_secs(30)).await;
+		});
+		let node = network.child(async {
+			sleep(Duration::from_secs(30)).await;
+		});
+		let follower = node.child(async {
+			sleep(Duration::from_secs(30)).await;
+		});
+		let finished = network.child(async { 1 });
+		assert_eq!(finished.await?, 1);
+
+		assert_eq!(node.status(), kestrel::TaskStatus::Running);
+		assert_eq!(follower.status(), kestrel::TaskStatus::Running);
+
+		drop(network);
+
+		assert_eq!(node.status(), kestrel::TaskStatus::Aborted);
+		assert_eq!(follower.status(), kestrel::TaskStatus::Aborted);
+		end!(node, follower)?;
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_await_any() -> Result<(), anyhow::Error> {
+		let slow = kestrel::task(async {
+			sleep(Duration::from_millis(200)).await;
+			"slow"
+		});
+		let fast = kestrel::task(async {
+			sleep(Duration::from_millis(20)).await;
+			"fast"
+		});
+
+		let (index, result, mut remaining) = await_any!(slow, fast).await;
+		assert_eq!(index, 1);
+		assert!(matches!(result?, kestrel::Maybe::Value("fast")));
+
+		// The other task is still alive, and the completed one cannot be awaited again
+		assert_eq!(remaining.len(), 1);
+		let slow = remaining.remove(0);
+		assert_eq!(slow.status(), kestrel::TaskStatus::Running);
+		assert_eq!(slow.await?, "slow");
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_task_metrics() -> Result<(), anyhow::Error> {
+		let counted = kestrel::task_named("metrics-counted", async {
+			for _ in 0..3 {
+				tokio::task::yield_now().await;
+			}
+		});
+		let id = counted.id();
+		counted.await?;
+
+		let metrics = kestrel::metrics::snapshot()
+			.into_iter()
+			.find(|metrics| metrics.id == id)
+			.expect("completed task should be in the snapshot");
+		assert_eq!(metrics.name.as_deref(), Some("metrics-counted"));
+		assert_eq!(metrics.status, kestrel::TaskStatus::Finished);
+		assert!(metrics.polls >= 4);
+
+		let aborted = kestrel::task(async {
+			sleep(Duration::from_secs(30)).await;
+		});
+		let id = aborted.id();
+		end!(aborted)?;
+		let status = kestrel::metrics::snapshot()
+			.into_iter()
+			.find(|metrics| metrics.id == id)
+			.map(|metrics| metrics.status);
+		assert_eq!(status, Some(kestrel::TaskStatus::Aborted));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_try_await_allow_abort() -> Result<(), anyhow::Error> {
+		let value = kestrel::task(async { Ok::<_, std::io::Error>(1) });
+		let aborted = kestrel::task(async {
+			sleep(Duration::from_secs(30)).await;
+			Ok::<_, std::io::Error>("never")
+		});
+		aborted.abort();
+		let (value, aborted) = try_await_allow_abort!(value, aborted)?;
+		assert_eq!(value, Some(1));
+		assert_eq!(aborted, None);
+
+		let value = kestrel::task(async { Ok::<_, std::io::Error>(1) });
+		let failed = kestrel::task(async { Err::<(), _>(std::io::Error::other("bad config")) });
+		let result = try_await_allow_abort!(value, failed);
+		assert!(
+			matches!(result, Err(kestrel::TaskError::Failed(e)) if e.to_string() == "bad config")
+		);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_maybe_combinators() -> Result<(), anyhow::Error> {
+		let value = kestrel::task(async { 2 }).maybe().await?;
+		assert!(value.is_value());
+		assert_eq!(value.map(|value| value * 10).ok(), Some(20));
+
+		let aborted = kestrel::task(async {
+			sleep(Duration::from_secs(30)).await;
+			2
+		});
+		aborted.abort_handle.abort();
+		let aborted = aborted.maybe().await?;
+		assert!(aborted.is_aborted());
+		assert_eq!(aborted.map(|value| value * 10).unwrap_or(7), 7);
+
+		let value: Option<i32> = kestrel::task(async { 3 }).maybe().await?.into();
+		assert_eq!(value, Some(3));
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_watchdog() -> Result<(), anyhow::Error> {
+		use std::sync::atomic::{AtomicBool, Ordering};
+		use std::sync::Arc;
+
+		// Keeps pinging, so it is never considered stalled
+		let heartbeat = kestrel::Heartbeat::new();
+		let pinger = heartbeat.clone();
+		let healthy = kestrel::task(async move {
+			for _ in 0..10 {
+				sleep(Duration::from_millis(10)).await;
+				pinger.ping();
+			}
+			1
+		});
+		let healthy = kestrel::watchdog(healthy, &heartbeat, Duration::from_millis(100), || {});
+		assert_eq!(healthy.await?, 1);
+
+		let heartbeat = kestrel::Heartbeat::new();
+		let reported = Arc::new(AtomicBool::new(false));
+		let flag = reported.clone();
+		let deadlocked = kestrel::task(async {
+			sleep(Duration::from_secs(30)).await;
+		});
+		let child = deadlocked.child(async {
+			sleep(Duration::from_secs(30)).await;
+		});
+		let deadlocked =
+			kestrel::watchdog(deadlocked, &heartbeat, Duration::from_millis(50), move || {
+				flag.store(true, Ordering::SeqCst);
+			});
+
+		let result = tokio::time::timeout(Duration::from_secs(5), deadlocked).await?;
+		assert!(matches!(result, Err(kestrel::TaskError::Stalled(_))));
+		assert!(reported.load(Ordering::SeqCst));
+		// The child was aborted on the stall, not when the stalled task was dropped
+		assert_eq!(child.status(), kestrel::TaskStatus::Aborted);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_abort_with_reason() -> Result<(), anyhow::Error> {
+		let parent = kestrel::task(async {
+			sleep(Duration::from_secs(30)).await;
+		});
+		let child = parent.child(async {
+			sleep(Duration::from_secs(30)).await;
+		});
+
+		parent.abort_with_reason("sibling indexer failed");
+		assert_eq!(parent.abort_reason().as_deref(), Some("sibling indexer failed"));
+
+		match parent.await {
+			Err(kestrel::TaskError::Aborted(_, reason)) => {
+				assert_eq!(reason.as_deref(), Some("sibling indexer failed"));
+			}
+			other => panic!("expected an abort, got {:?}", other),
+		}
+		match child.await {
+			Err(kestrel::TaskError::Aborted(_, reason)) => {
+				assert_eq!(reason.as_deref(), Some("parent task aborted"));
+			}
+			other => panic!("expected an abort, got {:?}", other),
+		}
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_task_outside_runtime() -> Result<(), anyhow::Error> {
+		let spawned = kestrel::task(async { 1 });
+		assert_eq!(kestrel::runtime::block_on(spawned)?, 1);
+
+		let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+		let spawned = kestrel::task_on(runtime.handle(), async { 2 });
+		assert_eq!(runtime.block_on(spawned)?, 2);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_nursery_policies() -> Result<(), anyhow::Error> {
+		// Fail fast aborts the slow sibling instead of waiting for it
+		let nursery = kestrel::Nursery::new(kestrel::ErrorPolicy::FailFast);
+		nursery.spawn(async {
+			sleep(Duration::from_secs(30)).await;
+			Ok::<_, std::io::Error>(())
+		});
+		nursery.spawn(async { Err(std::io::Error::other("node crashed")) });
+		let result = tokio::time::timeout(Duration::from_secs(5), nursery.join()).await?;
+		assert!(
+			matches!(result, Err(kestrel::TaskError::Failed(e)) if e.to_string() == "node crashed")
+		);
+
+		// Collect all reports the abort too
+		let nursery = kestrel::Nursery::new(kestrel::ErrorPolicy::CollectAll);
+		nursery.spawn(async { Err(std::io::Error::other("first")) });
+		nursery.spawn(async { Err(std::io::Error::other("second")) });
+		let library = nursery.clone();
+		library.spawn(async {
+			sleep(Duration::from_secs(30)).await;
+			Ok::<_, std::io::Error>(())
+		});
+		library.abort_all();
+		match nursery.join().await {
+			Err(kestrel::TaskError::MultipleErrors(errors)) => assert_eq!(errors.len(), 3),
+			other => panic!("expected multiple errors, got {:?}", other),
+		}
+
+		let nursery = kestrel::Nursery::new(kestrel::ErrorPolicy::IgnoreAborted);
+		nursery.spawn(async { Ok::<_, std::io::Error>(()) });
+		nursery.spawn(async {
+			sleep(Duration::from_secs(30)).await;
+			Ok::<_, std::io::Error>(())
+		});
+		nursery.abort_all();
+		nursery.join().await?;
+
+		Ok(())
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn test_interval_task_overlap_policies() -> Result<(), anyhow::Error> {
+		use std::sync::atomic::{AtomicU32, Ordering};
+		use std::sync::Arc;
+
+		// Each run takes 25ms on a 10ms schedule
+		let periodic = |policy| {
+			let started = Arc::new(AtomicU32::new(0));
+			let finished = Arc::new(AtomicU32::new(0));
+			let (counter_started, counter_finished) = (started.clone(), finished.clone());
+			let task = kestrel::interval_task(Duration::from_millis(10), policy, move || {
+				let (started, finished) = (counter_started.clone(), counter_finished.clone());
+				async move {
+					started.fetch_add(1, Ordering::SeqCst);
+					sleep(Duration::from_millis(25)).await;
+					finished.fetch_add(1, Ordering::SeqCst);
+				}
+			});
+			(task, started, finished)
+		};
+
+		let (skip, skip_started, _) = periodic(kestrel::OverlapPolicy::Skip);
+		let (queue, queue_started, _) = periodic(kestrel::OverlapPolicy::Queue);
+		let (abort, abort_started, abort_finished) =
+			periodic(kestrel::OverlapPolicy::AbortPrevious);
+
+		sleep(Duration::from_millis(105)).await;
+		end!(skip, queue, abort)?;
+
+		// Skip starts at 0, 30, 60, 90; queue runs back to back at 0, 25, 50, 75, 100
+		assert_eq!(skip_started.load(Ordering::SeqCst), 4);
+		assert_eq!(queue_started.load(Ordering::SeqCst), 5);
+		// Abort-previous starts on every tick, but no run lives long enough to finish
+		assert_eq!(abort_started.load(Ordering::SeqCst), 11);
+		assert_eq!(abort_finished.load(Ordering::SeqCst), 0);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_try_task_without_runtime() {
+		assert!(matches!(kestrel::try_task(async {}), Err(kestrel::SpawnError::NoRuntime)));
+	}
+
+	#[tokio::test]
+	async fn test_try_task_max_live_tasks() -> Result<(), anyhow::Error> {
+		// Other tests may spawn concurrently, so only a limit of zero refuses deterministically
+		kestrel::set_max_live_tasks(Some(0));
+		let refused = kestrel::try_task(async { 1 });
+		kestrel::set_max_live_tasks(None);
+		assert!(matches!(refused, Err(kestrel::SpawnError::TooManyTasks(0))));
+
+		let spawned = kestrel::try_task(async { 1 })?;
+		assert_eq!(spawned.await?, 1);
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_task_then_pipeline() -> Result<(), anyhow::Error> {
+		let pipeline = kestrel::task(async { 2 })
+			.then(|built| async move { built * 10 })
+			.then(|deployed| async move { format!("ran {}", deployed) });
+		assert_eq!(pipeline.await?, "ran 20");
+
+		// Aborting the last stage cancels the upstream stage
+		let compile = kestrel::task(async {
+			sleep(Duration::from_secs(30)).await;
+			1
+		});
+		let mut upstream = compile.subscribe();
+		let deploy = compile.then(|built| async move { built + 1 });
+		deploy.abort();
+		assert!(matches!(deploy.await, Err(kestrel::TaskError::Aborted(..))));
+		upstream.wait_for(|status| *status == kestrel::TaskStatus::Aborted).await?;
+
+		// An aborted upstream stage aborts the next stage with a reason
+		let compile = kestrel::task(async {
+			sleep(Duration::from_secs(30)).await;
+			1
+		});
+		compile.abort_with_reason("compiler crashed");
+		let deploy = compile.then(|built| async move { built + 1 });
+		match deploy.await {
+			Err(kestrel::TaskError::Aborted(_, Some(reason))) => {
+				assert!(reason.contains("compiler crashed"));
+			}
+			other => panic!("expected an abort with a reason, got {:?}", other),
+		}
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_cancel_graceful() -> Result<(), anyhow::Error> {
+		use std::sync::atomic::{AtomicBool, Ordering};
+		use std::sync::Arc;
+
+		// A cooperative task cleans up as soon as it is asked to cancel
+		let flushed = Arc::new(AtomicBool::new(false));
+		let flag = flushed.clone();
+		let cooperative = kestrel::task(async move {
+			tokio::select! {
+				_ = sleep(Duration::from_secs(30)) => {}
+				_ = kestrel::cancelled() => {
+					assert!(kestrel::is_cancelled());
+					flag.store(true, Ordering::SeqCst);
+				}
+			}
+		});
+		let started = tokio::time::Instant::now();
+		cooperative.cancel_graceful(Duration::from_secs(5)).await?;
+		assert!(flushed.load(Ordering::SeqCst));
+		assert!(started.elapsed() < Duration::from_secs(5));
+
+		// A task which ignores the request is aborted after the grace period
+		let stubborn = kestrel::task(async {
+			sleep(Duration::from_secs(30)).await;
+		});
+		let mut status = stubborn.subscribe();
+		stubborn.cancel_graceful(Duration::from_millis(50)).await?;
+		status.wait_for(|status| *status == kestrel::TaskStatus::Aborted).await?;
+
+		assert!(!kestrel::is_cancelled());
+
+		Ok(())
+	}
+
+	#[derive(Debug, Clone, PartialEq, kestrel::KestrelState)]
+	struct HarnessConfig {
+		rpc_url: String,
+		chain_id: u64,
+	}
+
+	#[tokio::test]
+	async fn test_derive_kestrel_state() -> Result<(), anyhow::Error> {
+		let states = HarnessConfigStates::new();
+		assert_eq!(states.chain_id.name(), Some("HarnessConfig.chain_id"));
+
+		let rpc_url = states.rpc_url_writer();
+		let chain_id = states.chain_id_writer();
+		let writer = kestrel::task(async move {
+			rpc_url.set("http://localhost:30731".to_string()).await;
+			sleep(Duration::from_millis(50)).await;
+			chain_id.set(27).await;
+		});
+
+		let config = states.wait_all().await;
+		assert_eq!(
+			config,
+			HarnessConfig { rpc_url: "http://localhost:30731".to_string(), chain_id: 27 }
+		);
+		assert_eq!(states.chain_id_reader().get().await, Some(27));
+		writer.await?;
+
+		// Waiting on states which are never set times out
+		let unset = HarnessConfigStates::default();
+		let error = unset.wait_all_for(Duration::from_millis(20)).await.unwrap_err();
+		assert!(matches!(error, kestrel::WaitError::Timeout { .. }));
+
+		Ok(())
+	}
+
+	#[derive(kestrel::BinArgs)]
+	struct NodeArgs {
+		rpc_port: u16,
+		verbose: bool,
+		quiet: bool,
+		log_file: Option<String>,
+		data_dir: Option<String>,
+		peer: Vec<String>,
+		#[bin_args(rename = "id")]
+		node_id: u64,
+		#[bin_args(positional)]
+		command: String,
+	}
+
+	#[test]
+	fn test_derive_bin_args() {
+		use kestrel::BinArgs;
+
+		let args = NodeArgs {
+			rpc_port: 30731,
+			verbose: true,
+			quiet: false,
+			log_file: Some("node.log".to_string()),
+			data_dir: None,
+			peer: vec!["10.0.0.2".to_string(), "10.0.0.3".to_string()],
+			node_id: 7,
+			command: "run".to_string(),
+		};
+
+		assert_eq!(
+			args.to_args(),
+			vec![
+				"--rpc-port",
+				"30731",
+				"--verbose",
+				"--log-file",
+				"node.log",
+				"--peer",
+				"10.0.0.2",
+				"--peer",
+				"10.0.0.3",
+				"--id",
+				"7",
+				"run",
+			]
+		);
+	}
 }
@@ -0,0 +1,32 @@
+use crate::{task, Task};
+
+/// Aborts a blocking task when dropped, which prevents it from starting if it has not yet
+struct AbortOnDrop(tokio::task::AbortHandle);
+
+impl Drop for AbortOnDrop {
+	fn drop(&mut self) {
+		self.0.abort();
+	}
+}
+
+/// Spawns a closure on tokio's blocking pool as an abortable [Task]
+///
+/// Aborting the task before the closure starts prevents it from running at all. A closure which
+/// is already running cannot be interrupted and runs to completion in the background, but the
+/// task is still reported as aborted.
+#[track_caller]
+pub fn blocking_task<F, T>(f: F) -> Task<T>
+where
+	F: FnOnce() -> T + Send + 'static,
+	T: Send + 'static,
+{
+	task(async move {
+		let handle = tokio::task::spawn_blocking(f);
+		let _abort_on_drop = AbortOnDrop(handle.abort_handle());
+		match handle.await {
+			Ok(value) => value,
+			Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+			Err(e) => panic!("blocking task was cancelled: {}", e),
+		}
+	})
+}
@@ -0,0 +1,212 @@
+use crate::{task, ReadOnlyState, TaskError};
+use futures::future::{join_all, BoxFuture};
+use futures::stream::{FuturesUnordered, StreamExt};
+use futures::FutureExt;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Errors thrown while scheduling or running a [TaskGraph]
+#[derive(Debug, thiserror::Error)]
+pub enum TaskGraphError {
+	#[error("task {0} is registered more than once")]
+	DuplicateTask(String),
+
+	#[error("task {task} depends on unknown task {dependency}")]
+	UnknownDependency { task: String, dependency: String },
+
+	#[error("tasks form a dependency cycle: {0:?}")]
+	Cycle(Vec<String>),
+
+	#[error("task {task} failed: {source}")]
+	Failed {
+		task: String,
+		#[source]
+		source: BoxError,
+	},
+
+	#[error("task {task} did not complete: {source}")]
+	Task {
+		task: String,
+		#[source]
+		source: TaskError,
+	},
+}
+
+struct Node<T> {
+	name: String,
+	dependencies: Vec<String>,
+	gates: Vec<BoxFuture<'static, ()>>,
+	run: BoxFuture<'static, Result<T, BoxError>>,
+}
+
+/// A set of named tasks which start once the tasks and states they depend on are ready
+///
+/// Each task is spawned as soon as all of its upstream tasks have completed successfully and all
+/// of its [ReadOnlyState] dependencies are set. If any task fails, the tasks still running are
+/// aborted and the graph fails fast.
+pub struct TaskGraph<T> {
+	nodes: Vec<Node<T>>,
+}
+
+impl<T> TaskGraph<T>
+where
+	T: Send + 'static,
+{
+	pub fn new() -> Self {
+		Self { nodes: Vec::new() }
+	}
+
+	/// Adds a task which starts after the named tasks complete
+	pub fn add<F, E>(&mut self, name: impl Into<String>, dependencies: &[&str], f: F) -> &mut Self
+	where
+		F: Future<Output = Result<T, E>> + Send + 'static,
+		E: Into<BoxError>,
+	{
+		self.nodes.push(Node {
+			name: name.into(),
+			dependencies: dependencies.iter().map(|dependency| dependency.to_string()).collect(),
+			gates: Vec::new(),
+			run: f.map(|result| result.map_err(Into::into)).boxed(),
+		});
+		self
+	}
+
+	/// Delays the named task until the state is set
+	///
+	/// Has no effect if no task with the name has been added.
+	pub fn depends_on_state<S>(&mut self, name: &str, state: ReadOnlyState<S>) -> &mut Self
+	where
+		S: Clone + Send + Sync + 'static,
+	{
+		if let Some(node) = self.nodes.iter_mut().find(|node| node.name == name) {
+			node.gates.push(
+				async move {
+					state.wait_forever().await;
+				}
+				.boxed(),
+			);
+		}
+		self
+	}
+
+	/// Returns the task names in an order which respects their dependencies
+	fn schedule(&self) -> Result<Vec<usize>, TaskGraphError> {
+		let mut indices = HashMap::new();
+		for (index, node) in self.nodes.iter().enumerate() {
+			if indices.insert(node.name.as_str(), index).is_some() {
+				return Err(TaskGraphError::DuplicateTask(node.name.clone()));
+			}
+		}
+
+		let mut in_degree = vec![0; self.nodes.len()];
+		let mut dependents = vec![Vec::new(); self.nodes.len()];
+		for (index, node) in self.nodes.iter().enumerate() {
+			for dependency in &node.dependencies {
+				let upstream = *indices.get(dependency.as_str()).ok_or_else(|| {
+					TaskGraphError::UnknownDependency {
+						task: node.name.clone(),
+						dependency: dependency.clone(),
+					}
+				})?;
+				in_degree[index] += 1;
+				dependents[upstream].push(index);
+			}
+		}
+
+		let mut ready: VecDeque<usize> =
+			(0..self.nodes.len()).filter(|&i| in_degree[i] == 0).collect();
+		let mut order = Vec::with_capacity(self.nodes.len());
+		while let Some(index) = ready.pop_front() {
+			order.push(index);
+			for &dependent in &dependents[index] {
+				in_degree[dependent] -= 1;
+				if in_degree[dependent] == 0 {
+					ready.push_back(dependent);
+				}
+			}
+		}
+
+		if order.len() != self.nodes.len() {
+			let cycle = (0..self.nodes.len())
+				.filter(|&i| in_degree[i] > 0)
+				.map(|i| self.nodes[i].name.clone())
+				.collect();
+			return Err(TaskGraphError::Cycle(cycle));
+		}
+
+		Ok(order)
+	}
+
+	/// Validates the graph, then runs every task, returning the values by name
+	pub async fn run(self) -> Result<HashMap<String, T>, TaskGraphError> {
+		self.schedule()?;
+
+		let mut remaining: HashMap<String, usize> = HashMap::new();
+		let mut dependents: HashMap<String, Vec<String>> = HashMap::new();
+		let mut pending = HashMap::new();
+		for node in self.nodes {
+			remaining.insert(node.name.clone(), node.dependencies.len());
+			for dependency in &node.dependencies {
+				dependents.entry(dependency.clone()).or_default().push(node.name.clone());
+			}
+			pending.insert(node.name.clone(), node);
+		}
+
+		let spawn = |node: Node<T>| {
+			let name = node.name;
+			let gates = node.gates;
+			let run = node.run;
+			let handle = task(async move {
+				join_all(gates).await;
+				run.await
+			});
+			handle.map(move |result| (name, result))
+		};
+
+		let mut running = FuturesUnordered::new();
+		let ready: Vec<String> = remaining
+			.iter()
+			.filter(|(_, count)| **count == 0)
+			.map(|(name, _)| name.clone())
+			.collect();
+		for name in ready {
+			if let Some(node) = pending.remove(&name) {
+				running.push(spawn(node));
+			}
+		}
+
+		let mut values = HashMap::new();
+		while let Some((name, result)) = running.next().await {
+			// Returning early drops the remaining tasks, which aborts them
+			let value = match result {
+				Ok(Ok(value)) => value,
+				Ok(Err(source)) => return Err(TaskGraphError::Failed { task: name, source }),
+				Err(source) => return Err(TaskGraphError::Task { task: name, source }),
+			};
+
+			for dependent in dependents.remove(&name).unwrap_or_default() {
+				let count = remaining.entry(dependent.clone()).or_default();
+				*count -= 1;
+				if *count == 0 {
+					if let Some(node) = pending.remove(&dependent) {
+						running.push(spawn(node));
+					}
+				}
+			}
+			values.insert(name, value);
+		}
+
+		Ok(values)
+	}
+}
+
+impl<T> Default for TaskGraph<T>
+where
+	T: Send + 'static,
+{
+	fn default() -> Self {
+		Self::new()
+	}
+}
@@ -0,0 +1,133 @@
+use crate::{Task, TaskError, TaskStatus};
+use std::future::Future;
+
+/// A set of tasks registered under names and managed as a unit
+///
+/// Tasks are kept in registration order. Like [Task], dropping the group aborts every task in it.
+#[derive(Debug)]
+pub struct TaskGroup<T> {
+	tasks: Vec<(String, Task<T>)>,
+}
+
+impl<T> TaskGroup<T> {
+	pub fn new() -> Self {
+		Self { tasks: Vec::new() }
+	}
+
+	/// Registers a task under a name, returning the task previously registered under it
+	pub fn insert(&mut self, name: impl Into<String>, task: Task<T>) -> Option<Task<T>> {
+		let name = name.into();
+		match self.tasks.iter_mut().find(|(existing, _)| *existing == name) {
+			Some((_, existing)) => Some(std::mem::replace(existing, task)),
+			None => {
+				self.tasks.push((name, task));
+				None
+			}
+		}
+	}
+
+	/// Spawns a task and registers it under a name
+	pub fn spawn<F>(&mut self, name: impl Into<String>, f: F) -> Option<Task<T>>
+	where
+		F: Future<Output = T> + Send + 'static,
+		T: Send + 'static,
+	{
+		self.insert(name, crate::task(f))
+	}
+
+	/// Removes a task from the group without aborting it
+	pub fn remove(&mut self, name: &str) -> Option<Task<T>> {
+		let index = self.tasks.iter().position(|(existing, _)| existing == name)?;
+		Some(self.tasks.remove(index).1)
+	}
+
+	/// Returns the task registered under a name
+	pub fn get(&self, name: &str) -> Option<&Task<T>> {
+		self.tasks.iter().find(|(existing, _)| existing == name).map(|(_, task)| task)
+	}
+
+	/// Returns the names of the registered tasks in registration order
+	pub fn names(&self) -> impl Iterator<Item = &str> {
+		self.tasks.iter().map(|(name, _)| name.as_str())
+	}
+
+	/// Returns the number of registered tasks
+	pub fn len(&self) -> usize {
+		self.tasks.len()
+	}
+
+	/// Returns whether no tasks are registered
+	pub fn is_empty(&self) -> bool {
+		self.tasks.is_empty()
+	}
+
+	/// Returns the status of the task registered under a name
+	pub fn status(&self, name: &str) -> Option<TaskStatus> {
+		self.get(name).map(Task::status)
+	}
+
+	/// Returns the status of every task in registration order
+	pub fn statuses(&self) -> Vec<(&str, TaskStatus)> {
+		self.tasks.iter().map(|(name, task)| (name.as_str(), task.status())).collect()
+	}
+
+	/// Aborts the task registered under a name, returning whether it was still running
+	pub fn abort(&self, name: &str) -> bool {
+		self.get(name).map(Task::try_abort).unwrap_or(false)
+	}
+
+	/// Aborts every task in the group
+	pub fn abort_all(&self) {
+		for (_, task) in &self.tasks {
+			task.abort();
+		}
+	}
+
+	/// Awaits every task, returning the values by name
+	///
+	/// Unlike [crate::await_allow_abort], every task is awaited even after a failure. All
+	/// failures are reported, wrapped in [TaskError::MultipleErrors] if there is more than one.
+	pub async fn await_all(self) -> Result<Vec<(String, T)>, TaskError> {
+		let mut values = Vec::with_capacity(self.tasks.len());
+		let mut errors = Vec::new();
+		for (name, task) in self.tasks {
+			match task.await {
+				Ok(value) => values.push((name, value)),
+				Err(e) => errors.push(e),
+			}
+		}
+
+		if errors.is_empty() {
+			Ok(values)
+		} else {
+			Err(TaskError::aggregate(errors))
+		}
+	}
+
+	/// Aborts every task and waits for all of them to stop
+	///
+	/// Aborts are not errors. All other failures, such as panics, are reported as in
+	/// [TaskGroup::await_all].
+	pub async fn end_all(self) -> Result<(), TaskError> {
+		self.abort_all();
+
+		let mut errors = Vec::new();
+		for (_, task) in self.tasks {
+			if let Err(e) = task.await_allow_abort().await {
+				errors.push(e);
+			}
+		}
+
+		if errors.is_empty() {
+			Ok(())
+		} else {
+			Err(TaskError::aggregate(errors))
+		}
+	}
+}
+
+impl<T> Default for TaskGroup<T> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
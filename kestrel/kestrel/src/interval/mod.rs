@@ -0,0 +1,56 @@
+use crate::{task, Task};
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::MissedTickBehavior;
+
+/// What a periodic task does when a run is still going at the next tick
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlapPolicy {
+	/// Skips the ticks missed by a slow run and waits for the next one on schedule
+	Skip,
+	/// Runs once for every missed tick, back to back, until caught up with the schedule
+	Queue,
+	/// Aborts the slow run and starts a new one on schedule
+	AbortPrevious,
+}
+
+/// Spawns a task which calls the closure on a fixed schedule until aborted
+///
+/// Ticks are scheduled relative to when the task started rather than to when the previous run
+/// ended, so the schedule does not drift. The first run starts immediately.
+#[track_caller]
+pub fn interval_task<F, Fut>(period: Duration, policy: OverlapPolicy, mut f: F) -> Task<()>
+where
+	F: FnMut() -> Fut + Send + 'static,
+	Fut: Future<Output = ()> + Send + 'static,
+{
+	task(async move {
+		let mut interval = tokio::time::interval(period);
+		match policy {
+			OverlapPolicy::Skip => {
+				interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+				loop {
+					interval.tick().await;
+					f().await;
+				}
+			}
+			OverlapPolicy::Queue => {
+				interval.set_missed_tick_behavior(MissedTickBehavior::Burst);
+				loop {
+					interval.tick().await;
+					f().await;
+				}
+			}
+			OverlapPolicy::AbortPrevious => {
+				let mut current: Option<Task<()>> = None;
+				loop {
+					interval.tick().await;
+					if let Some(previous) = current.take() {
+						previous.abort();
+					}
+					current = Some(task(f()));
+				}
+			}
+		}
+	})
+}
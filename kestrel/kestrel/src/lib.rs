@@ -1,23 +1,141 @@
-use futures::future::{AbortHandle, Abortable, Aborted};
+pub mod blocking;
+pub mod graph;
+pub mod group;
+pub mod interval;
+pub mod metrics;
+pub mod nursery;
+mod panic;
+pub mod registry;
+pub mod retry;
+pub mod runtime;
+pub mod scope;
+pub mod shutdown;
+pub mod supervisor;
+#[cfg(feature = "test-util")]
+pub mod testing;
+pub mod watchdog;
+
+pub use blocking::blocking_task;
+use futures::future::{AbortHandle, AbortRegistration, Abortable, Aborted};
+use futures::stream::{FuturesUnordered, StreamExt};
+use futures::FutureExt;
+pub use graph::{TaskGraph, TaskGraphError};
+pub use group::TaskGroup;
+pub use interval::{interval_task, OverlapPolicy};
 pub use kestrel_macro::*;
 pub use kestrel_process::*;
 pub use kestrel_state::*;
+pub use nursery::{ErrorPolicy, Nursery};
+pub use registry::{set_max_live_tasks, tasks, TaskInfo};
+pub use retry::{retry, Backoff, RetryPolicy};
+pub use scope::{scope, Scope, ScopeError};
+pub use shutdown::{cancelled, end_by, is_cancelled, task_with_shutdown, ShutdownToken};
+use std::any::Any;
 use std::future::Future;
+use std::panic::{AssertUnwindSafe, Location};
 use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
-use tokio::task::JoinHandle;
+use std::time::Duration;
+pub use supervisor::{RestartPolicy, Supervisor, SupervisorError};
+use tokio::runtime::Handle;
+use tokio::sync::{watch, Semaphore};
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::Instrument;
+pub use watchdog::{watchdog, Heartbeat};
+
+/// Re-exports used by the macros in this crate and by the code derived with `kestrel-macro`.
+#[doc(hidden)]
+pub mod reexport {
+	pub use tokio;
+}
 
 /// Errors thrown by the Task struct.
 #[derive(Debug, thiserror::Error)]
 pub enum TaskError {
-	#[error("task aborted: {0}")]
-	Aborted(#[source] Aborted),
+	#[error("task aborted ({}): {0}", .1.as_deref().unwrap_or("no reason given"))]
+	Aborted(#[source] Aborted, Option<String>),
 	#[error("join error: {0}")]
 	Join(#[source] tokio::task::JoinError),
+	#[error("task panicked: {message}")]
+	Panicked { message: String, backtrace: Option<String> },
+	#[error("task failed: {0}")]
+	Failed(#[source] Box<dyn std::error::Error + Send + Sync>),
+	#[error("task stalled without a heartbeat for {0:?}")]
+	Stalled(Duration),
+	#[error("task timed out after {0:?}")]
+	TimedOut(Duration),
 	#[error("multiple errors encountered across tasks: {0:?}")]
 	MultipleErrors(Vec<TaskError>),
 }
 
+impl TaskError {
+	/// Converts a join error, extracting the message and backtrace of a panic
+	fn from_join(e: tokio::task::JoinError) -> TaskError {
+		if !e.is_panic() {
+			return TaskError::Join(e);
+		}
+
+		match e.into_panic().downcast::<panic::PanicReport>() {
+			Ok(report) => {
+				TaskError::Panicked { backtrace: report.backtrace(), message: report.message }
+			}
+			Err(payload) => {
+				TaskError::Panicked { message: Panicked::from_payload(payload).0, backtrace: None }
+			}
+		}
+	}
+
+	/// Combines errors from several tasks, keeping a single error as is
+	pub fn aggregate(mut errors: Vec<TaskError>) -> TaskError {
+		if errors.len() == 1 {
+			errors.remove(0)
+		} else {
+			TaskError::MultipleErrors(errors)
+		}
+	}
+
+	/// Returns whether the error is caused by an abort, through either the task or its join handle
+	pub fn is_abort(&self) -> bool {
+		match self {
+			TaskError::Aborted(..) => true,
+			TaskError::Join(e) => e.is_cancelled(),
+			_ => false,
+		}
+	}
+}
+
+/// Errors thrown when a task cannot be spawned, see [try_task]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SpawnError {
+	#[error("no tokio runtime is running")]
+	NoRuntime,
+	#[error("too many live tasks: the limit is {0}")]
+	TooManyTasks(usize),
+	#[error("the task registry is poisoned")]
+	RegistryPoisoned,
+}
+
+/// The message of a panic caught within a task
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("task panicked: {0}")]
+pub struct Panicked(pub String);
+
+impl Panicked {
+	/// Extracts the message from a panic payload
+	pub(crate) fn from_payload(payload: Box<dyn Any + Send>) -> Self {
+		let message = match payload.downcast::<String>() {
+			Ok(message) => *message,
+			Err(payload) => match payload.downcast::<&'static str>() {
+				Ok(message) => message.to_string(),
+				Err(_) => "Box<dyn Any>".to_string(),
+			},
+		};
+		Panicked(message)
+	}
+}
+
 /// A value that may be aborted
 #[derive(Debug)]
 pub enum Maybe<T> {
@@ -25,17 +143,186 @@ pub enum Maybe<T> {
 	Aborted(Aborted),
 }
 
+impl<T> Maybe<T> {
+	/// Returns whether the value was aborted
+	pub fn is_aborted(&self) -> bool {
+		matches!(self, Maybe::Aborted(_))
+	}
+
+	/// Returns whether there is a value
+	pub fn is_value(&self) -> bool {
+		matches!(self, Maybe::Value(_))
+	}
+
+	/// Maps the value, keeping an abort as is
+	pub fn map<U, F>(self, f: F) -> Maybe<U>
+	where
+		F: FnOnce(T) -> U,
+	{
+		match self {
+			Maybe::Value(value) => Maybe::Value(f(value)),
+			Maybe::Aborted(aborted) => Maybe::Aborted(aborted),
+		}
+	}
+
+	/// Returns the value, or None if it was aborted
+	pub fn ok(self) -> Option<T> {
+		match self {
+			Maybe::Value(value) => Some(value),
+			Maybe::Aborted(_) => None,
+		}
+	}
+
+	/// Returns the value, or the default if it was aborted
+	pub fn unwrap_or(self, default: T) -> T {
+		self.ok().unwrap_or(default)
+	}
+
+	/// Returns the value, or computes one if it was aborted
+	pub fn unwrap_or_else<F>(self, f: F) -> T
+	where
+		F: FnOnce() -> T,
+	{
+		self.ok().unwrap_or_else(f)
+	}
+}
+
+impl<T> From<Maybe<T>> for Option<T> {
+	fn from(maybe: Maybe<T>) -> Self {
+		maybe.ok()
+	}
+}
+
+/// The lifecycle status of a task
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskStatus {
+	/// The task has not finished yet
+	Running,
+	/// The task finished on its own, successfully or with a panic
+	Finished,
+	/// The task was aborted
+	Aborted,
+}
+
 /// A unique identifier for tasks
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct TaskId(u64);
 
+impl TaskId {
+	/// Generates the next unique task identifier
+	fn next() -> Self {
+		static NEXT_TASK_ID: AtomicU64 = AtomicU64::new(0);
+		TaskId(NEXT_TASK_ID.fetch_add(1, Ordering::Relaxed))
+	}
+}
+
 /// A task that can be spawned, aborted, and awaited
+///
+/// In contrast to tokio's task, this task will abort when dropped. This means you have to hold
+/// the task handle to ensure the task is not aborted when the task handle is dropped.
 #[derive(Debug)]
 pub struct Task<T> {
+	/// The unique identifier of the task
+	pub id: TaskId,
 	/// The join handle for awaiting the task
 	pub handle: JoinHandle<Result<T, Aborted>>,
 	/// The abort handle for cancelling the task
 	pub abort_handle: AbortHandle,
+	/// The timeout and whether the timer aborted the task, if any
+	timeout: Option<(Duration, Arc<AtomicBool>)>,
+	/// Receives the status of the task once it completes
+	completion: watch::Receiver<TaskStatus>,
+	/// The child tasks aborted along with this task
+	children: Children,
+	/// The watchdog interval and whether the watchdog aborted the task, if watched
+	stall: Option<(Duration, Arc<AtomicBool>)>,
+	/// Why the task was aborted, if a reason was given
+	abort_reason: AbortReason,
+	/// Signals the task to cancel itself, as observed through [cancelled]
+	cancellation: ShutdownToken,
+	/// Aborts the task when it is dropped, unless taken apart by [Task::into_parts]
+	abort_on_drop: AbortOnDrop,
+}
+
+/// Aborts a task and its children when dropped, unless disarmed
+#[derive(Debug)]
+struct AbortOnDrop {
+	handles: Option<(AbortHandle, tokio::task::AbortHandle, Children)>,
+}
+
+impl AbortOnDrop {
+	/// Keeps the task running when the guard is dropped
+	fn disarm(mut self) {
+		self.handles = None;
+	}
+}
+
+impl Drop for AbortOnDrop {
+	fn drop(&mut self) {
+		if let Some((abort_handle, join_abort_handle, children)) = self.handles.take() {
+			abort_handle.abort();
+			join_abort_handle.abort();
+			abort_children(&children);
+		}
+	}
+}
+
+/// Why a task was aborted, shared with its parent so that it can abort children with a reason
+type AbortReason = Arc<Mutex<Option<String>>>;
+
+/// Records the reason for an abort, keeping the first reason given
+fn set_abort_reason(abort_reason: &AbortReason, reason: String) {
+	if let Ok(mut abort_reason) = abort_reason.lock() {
+		abort_reason.get_or_insert(reason);
+	}
+}
+
+/// The child tasks of a task, shared so that they can be aborted recursively
+type Children = Arc<Mutex<Vec<ChildHandle>>>;
+
+/// Aborts a child task and, recursively, its own children
+#[derive(Debug)]
+struct ChildHandle {
+	abort_handle: AbortHandle,
+	join_abort_handle: tokio::task::AbortHandle,
+	children: Children,
+	abort_reason: AbortReason,
+}
+
+impl ChildHandle {
+	fn abort(&self) {
+		set_abort_reason(&self.abort_reason, "parent task aborted".to_string());
+		self.abort_handle.abort();
+		self.join_abort_handle.abort();
+		abort_children(&self.children);
+	}
+}
+
+/// Aborts every child in the list, recursively
+fn abort_children(children: &Children) {
+	if let Ok(children) = children.lock() {
+		for child in children.iter() {
+			child.abort();
+		}
+	}
+}
+
+/// Publishes the status of a task once its future completes or is dropped
+struct Completion {
+	sender: watch::Sender<TaskStatus>,
+	finished: bool,
+}
+
+impl Drop for Completion {
+	fn drop(&mut self) {
+		// A future dropped while unwinding has panicked rather than been aborted
+		let status = if self.finished || std::thread::panicking() {
+			TaskStatus::Finished
+		} else {
+			TaskStatus::Aborted
+		};
+		self.sender.send_replace(status);
+	}
 }
 
 impl<T> Task<T> {
@@ -43,6 +330,194 @@ impl<T> Task<T> {
 	pub fn abort(&self) {
 		self.abort_handle.abort();
 		self.handle.abort();
+		abort_children(&self.children);
+	}
+
+	/// Spawns a child task which is aborted whenever this task is aborted or dropped
+	///
+	/// Children of the child are aborted recursively. The child can still be awaited, aborted,
+	/// or dropped independently of this task.
+	#[track_caller]
+	pub fn child<F, U>(&self, f: F) -> Task<U>
+	where
+		F: Future<Output = U> + Send + 'static,
+		U: Send + 'static,
+	{
+		let child =
+			spawn_registered(&runtime::handle(), TaskId::next(), None, Location::caller(), f);
+		if let Ok(mut children) = self.children.lock() {
+			children.retain(|existing| !existing.join_abort_handle.is_finished());
+			children.push(ChildHandle {
+				abort_handle: child.abort_handle.clone(),
+				join_abort_handle: child.handle.abort_handle(),
+				children: child.children.clone(),
+				abort_reason: child.abort_reason.clone(),
+			});
+		}
+		child
+	}
+
+	/// Spawns a task which runs the closure on this task's value, forming a pipeline stage
+	///
+	/// Aborting or dropping the returned task also aborts this one, so a whole pipeline is
+	/// cancelled through its last stage. If this task panics, the panic is propagated to the next
+	/// stage; if it fails in any other way, the next stage is aborted with a reason describing
+	/// the failure.
+	#[track_caller]
+	pub fn then<F, Fut, U>(self, f: F) -> Task<U>
+	where
+		F: FnOnce(T) -> Fut + Send + 'static,
+		Fut: Future<Output = U> + Send + 'static,
+		T: Send + 'static,
+		U: Send + 'static,
+	{
+		let upstream = ChildHandle {
+			abort_handle: self.abort_handle.clone(),
+			join_abort_handle: self.handle.abort_handle(),
+			children: self.children.clone(),
+			abort_reason: self.abort_reason.clone(),
+		};
+		let (abort_handle, abort_reg) = AbortHandle::new_pair();
+		let abort_reason = AbortReason::default();
+		let (stage_abort_handle, stage_abort_reason) = (abort_handle.clone(), abort_reason.clone());
+		let mut stage = spawn_abortable(
+			&runtime::handle(),
+			TaskId::next(),
+			None,
+			Location::caller(),
+			abort_handle,
+			abort_reg,
+			async move {
+				match self.await {
+					Ok(value) => f(value).await,
+					Err(TaskError::Panicked { message, .. }) => {
+						std::panic::resume_unwind(Box::new(message))
+					}
+					Err(e) => {
+						set_abort_reason(
+							&stage_abort_reason,
+							format!("upstream stage failed: {e}"),
+						);
+						stage_abort_handle.abort();
+						// The abort is observed as soon as this future yields
+						std::future::pending().await
+					}
+				}
+			},
+		);
+		stage.abort_reason = abort_reason;
+		if let Ok(mut children) = stage.children.lock() {
+			children.push(upstream);
+		}
+		stage
+	}
+
+	/// Aborts the task, recording why
+	///
+	/// Awaiting the task returns [TaskError::Aborted] carrying the reason. Children are aborted
+	/// with a reason pointing at their parent. Only the first reason given is kept.
+	pub fn abort_with_reason(&self, reason: impl Into<String>) {
+		set_abort_reason(&self.abort_reason, reason.into());
+		self.abort();
+	}
+
+	/// Returns why the task was aborted, if a reason was given
+	pub fn abort_reason(&self) -> Option<String> {
+		self.abort_reason.lock().ok().and_then(|reason| reason.clone())
+	}
+
+	/// Aborts the task, returning whether it was still running
+	///
+	/// A task which had already finished is reported as not running.
+	pub fn try_abort(&self) -> bool {
+		let was_running = !self.handle.is_finished();
+		self.abort();
+		was_running
+	}
+
+	/// Aborts the task if it has not completed within the given duration from now
+	///
+	/// The deadline is enforced by a timer which stops once the task completes, so it applies even
+	/// if the task is never awaited. The timer is not registered, so it is not listed by [tasks].
+	/// Awaiting a task which the timer aborted returns [TaskError::TimedOut]; a task which
+	/// completed before the abort took effect still returns its own outcome.
+	pub fn with_timeout(mut self, timeout: Duration) -> Self {
+		let timed_out = Arc::new(AtomicBool::new(false));
+		self.timeout = Some((timeout, timed_out.clone()));
+
+		let abort_handle = self.abort_handle.clone();
+		let join_abort_handle = self.handle.abort_handle();
+		let children = self.children.clone();
+		let mut completion = self.subscribe();
+		// The completion is published when the task is aborted or dropped, which stops the timer
+		runtime::handle().spawn(async move {
+			tokio::select! {
+				_ = completion.wait_for(|status| *status != TaskStatus::Running) => return,
+				_ = tokio::time::sleep(timeout) => {}
+			}
+			if !join_abort_handle.is_finished() {
+				timed_out.store(true, Ordering::SeqCst);
+				abort_handle.abort();
+				join_abort_handle.abort();
+				abort_children(&children);
+			}
+		});
+
+		self
+	}
+
+	/// Subscribes to the completion of the task without taking ownership of it
+	///
+	/// The receiver holds [TaskStatus::Running] until the task finishes or is aborted.
+	pub fn subscribe(&self) -> watch::Receiver<TaskStatus> {
+		self.completion.clone()
+	}
+
+	/// Calls the callback with the final status of the task once it finishes or is aborted
+	///
+	/// The callback runs on a separate task, which keeps waiting even if this task is dropped.
+	pub fn on_complete<F>(&self, f: F)
+	where
+		F: FnOnce(TaskStatus) + Send + 'static,
+	{
+		let mut completion = self.subscribe();
+		runtime::handle().spawn(async move {
+			let status = match completion.wait_for(|status| *status != TaskStatus::Running).await {
+				Ok(status) => *status,
+				Err(_) => TaskStatus::Aborted,
+			};
+			f(status);
+		});
+	}
+
+	/// Asks the task to cancel itself, aborting it if it is still running after the grace period
+	///
+	/// The task observes the request through [cancelled], which gives it a chance to flush logs
+	/// or stop subprocesses before returning. Aborts are not errors, as in [end_by].
+	pub async fn cancel_graceful(self, grace: Duration) -> Result<(), TaskError> {
+		self.cancellation.signal();
+		end_by(self, tokio::time::Instant::now() + grace).await
+	}
+
+	/// Returns the timeout if the timer aborted the task
+	fn timed_out_after(&self) -> Option<Duration> {
+		match &self.timeout {
+			Some((timeout, timed_out)) if timed_out.load(Ordering::SeqCst) => Some(*timeout),
+			_ => None,
+		}
+	}
+
+	/// Returns the watchdog interval if the watchdog aborted the task
+	fn stalled_after(&self) -> Option<Duration> {
+		match &self.stall {
+			Some((interval, stalled)) if stalled.load(Ordering::SeqCst) => Some(*interval),
+			_ => None,
+		}
+	}
+
+	/// Returns the unique identifier of the task
+	pub fn id(&self) -> TaskId {
+		self.id
 	}
 
 	/// Returns whether the task has been aborted
@@ -50,69 +525,568 @@ impl<T> Task<T> {
 		self.abort_handle.is_aborted()
 	}
 
+	/// Returns the lifecycle status of the task
+	pub fn status(&self) -> TaskStatus {
+		if self.is_aborted() {
+			TaskStatus::Aborted
+		} else if self.handle.is_finished() {
+			TaskStatus::Finished
+		} else {
+			TaskStatus::Running
+		}
+	}
+
+	/// Consumes the task and returns the underlying join and abort handles
+	///
+	/// After this, the abort-on-drop guarantee no longer applies: dropping the returned
+	/// [JoinHandle] detaches the task, and the caller owns its lifecycle.
+	pub fn into_parts(self) -> (JoinHandle<Result<T, Aborted>>, AbortHandle) {
+		let Task { handle, abort_handle, abort_on_drop, .. } = self;
+		abort_on_drop.disarm();
+		(handle, abort_handle)
+	}
+
+	/// Moves the task into a tokio [JoinSet], returning its abort handle
+	///
+	/// The task stays abortable through the returned [AbortHandle], in which case the set yields
+	/// `Err(Aborted)`. Aborting or dropping the set still aborts the task.
+	pub fn spawn_into(self, set: &mut JoinSet<Result<T, Aborted>>) -> AbortHandle
+	where
+		T: Send + 'static,
+	{
+		let abort_handle = self.abort_handle.clone();
+		set.spawn(async move {
+			match self.await {
+				Ok(result) => Ok(result),
+				Err(TaskError::Panicked { message, .. }) => {
+					std::panic::resume_unwind(Box::new(message))
+				}
+				Err(_) => Err(Aborted),
+			}
+		});
+		abort_handle
+	}
+
 	/// Awaits a task, but allows an abort by wrapping as a [Maybe]
 	pub async fn maybe(self) -> Result<Maybe<T>, TaskError> {
-		match self.await {
-			Ok(result) => Ok(Maybe::Value(result)),
-			Err(e) => match e {
-				TaskError::Aborted(e) => Ok(Maybe::Aborted(e)),
-				TaskError::Join(e) => Err(TaskError::Join(e)),
-				TaskError::MultipleErrors(e) => Err(TaskError::MultipleErrors(e)),
-			},
-		}
+		into_maybe(self.await)
 	}
 
 	/// Awaits a task, but allows an abort
 	pub async fn await_allow_abort(self) -> Result<(), TaskError> {
-		match self.maybe().await {
+		match self.await {
 			Ok(_) => Ok(()),
-			Err(TaskError::Join(join_error)) if join_error.is_cancelled() => {
-				// If the task was cancelled via its JoinHandle (which our Task::abort now does),
-				// consider it a successful "end" for the purposes of this function.
-				Ok(())
-			}
+			Err(e) if e.is_abort() => Ok(()),
 			Err(e) => Err(e), // Other errors (like panics or non-cancellation JoinErrors) are still errors.
 		}
 	}
 }
 
-/// In contrast to tokio's task, this task will abort when dropped
-///
-/// This means you have to hold the task handle to ensure the task is not aborted
-/// when the task handle is dropped.
-impl<T> Drop for Task<T> {
-	fn drop(&mut self) {
-		self.abort();
-	}
-}
-
 impl<T> Future for Task<T> {
 	type Output = Result<T, TaskError>;
 
 	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-		match Pin::new(&mut self.handle).poll(cx) {
-			Poll::Pending => Poll::Pending,
-			Poll::Ready(Ok(result)) => match result {
+		let result = match Pin::new(&mut self.handle).poll(cx) {
+			Poll::Pending => return Poll::Pending,
+			Poll::Ready(result) => result,
+		};
+
+		// The timer and the watchdog only decide the outcome if their abort won over the task
+		let aborted = match &result {
+			Ok(result) => result.is_err(),
+			Err(e) => e.is_cancelled(),
+		};
+		if aborted {
+			if let Some(timeout) = self.timed_out_after() {
+				return Poll::Ready(Err(TaskError::TimedOut(timeout)));
+			}
+			if let Some(interval) = self.stalled_after() {
+				return Poll::Ready(Err(TaskError::Stalled(interval)));
+			}
+		}
+
+		match result {
+			Ok(result) => match result {
 				Ok(result) => Poll::Ready(Ok(result)),
-				Err(e) => Poll::Ready(Err(TaskError::Aborted(e))),
+				Err(e) => Poll::Ready(Err(TaskError::Aborted(e, self.abort_reason()))),
 			},
-			Poll::Ready(Err(e)) => Poll::Ready(Err(TaskError::Join(e))),
+			// An abort with a reason is reported as such even though it also cancelled the join
+			Err(e) if e.is_cancelled() && self.abort_reason().is_some() => {
+				Poll::Ready(Err(TaskError::Aborted(Aborted, self.abort_reason())))
+			}
+			Err(e) => Poll::Ready(Err(TaskError::from_join(e))),
 		}
 	}
 }
 
+/// Wraps an abort as a [Maybe], keeping every other error
+fn into_maybe<T>(result: Result<T, TaskError>) -> Result<Maybe<T>, TaskError> {
+	match result {
+		Ok(result) => Ok(Maybe::Value(result)),
+		Err(e) => match e {
+			TaskError::Aborted(e, _) => Ok(Maybe::Aborted(e)),
+			TaskError::Join(e) => Err(TaskError::Join(e)),
+			TaskError::Panicked { message, backtrace } => {
+				Err(TaskError::Panicked { message, backtrace })
+			}
+			TaskError::Failed(e) => Err(TaskError::Failed(e)),
+			TaskError::Stalled(e) => Err(TaskError::Stalled(e)),
+			TaskError::TimedOut(e) => Err(TaskError::TimedOut(e)),
+			TaskError::MultipleErrors(e) => Err(TaskError::MultipleErrors(e)),
+		},
+	}
+}
+
+/// Waits for whichever task completes first, returning its index, its result, and the other tasks
+///
+/// The other tasks keep running and are handed back in their original order, without the
+/// completed task. Aborts are wrapped as a [Maybe].
+///
+/// # Panics
+///
+/// Panics if no tasks are given.
+pub async fn await_any<T>(
+	mut tasks: Vec<Task<T>>,
+) -> (usize, Result<Maybe<T>, TaskError>, Vec<Task<T>>) {
+	assert!(!tasks.is_empty(), "await_any requires at least one task");
+	let (index, result) = std::future::poll_fn(|cx| {
+		for (index, task) in tasks.iter_mut().enumerate() {
+			if let Poll::Ready(result) = Pin::new(task).poll(cx) {
+				return Poll::Ready((index, result));
+			}
+		}
+		Poll::Pending
+	})
+	.await;
+	tasks.remove(index);
+	(index, into_maybe(result), tasks)
+}
+
 /// Spawns an abortable task and returns a Task struct
+///
+/// Outside of a tokio runtime, the task is spawned on kestrel's fallback runtime; see
+/// [runtime::handle]. The task is listed in [tasks] with the caller's location until it completes.
+#[track_caller]
 pub fn task<F, T>(f: F) -> Task<T>
+where
+	F: Future<Output = T> + Send + 'static,
+	T: Send + 'static,
+{
+	spawn_registered(&runtime::handle(), TaskId::next(), None, Location::caller(), f)
+}
+
+/// Spawns an abortable task, returning an error instead of panicking if it cannot be spawned
+///
+/// Unlike [task], this does not fall back to kestrel's fallback runtime: spawning outside of a
+/// tokio runtime fails with [SpawnError::NoRuntime]. Spawning fails with
+/// [SpawnError::TooManyTasks] if the live tasks have reached the limit set with
+/// [set_max_live_tasks].
+#[track_caller]
+pub fn try_task<F, T>(f: F) -> Result<Task<T>, SpawnError>
+where
+	F: Future<Output = T> + Send + 'static,
+	T: Send + 'static,
+{
+	let handle = Handle::try_current().map_err(|_| SpawnError::NoRuntime)?;
+	let id = TaskId::next();
+	let (abort_handle, abort_reg) = AbortHandle::new_pair();
+	let (sender, completion) = watch::channel(TaskStatus::Running);
+	let registered = registry::try_register(
+		id,
+		None,
+		Location::caller(),
+		abort_handle.clone(),
+		completion.clone(),
+	)?;
+	Ok(spawn_with_registration(
+		&handle,
+		id,
+		abort_handle,
+		abort_reg,
+		(sender, completion),
+		registered,
+		f,
+	))
+}
+
+/// Spawns an abortable task on the given runtime
+///
+/// Unlike [task], this does not depend on the runtime of the calling context.
+#[track_caller]
+pub fn task_on<F, T>(handle: &Handle, f: F) -> Task<T>
+where
+	F: Future<Output = T> + Send + 'static,
+	T: Send + 'static,
+{
+	spawn_registered(handle, TaskId::next(), None, Location::caller(), f)
+}
+
+/// Spawns an abortable task within a tracing span carrying its name and [TaskId]
+///
+/// Log lines emitted by the task are attributed to it, and the name is listed in [tasks].
+#[track_caller]
+pub fn task_named<F, T>(name: impl Into<String>, f: F) -> Task<T>
+where
+	F: Future<Output = T> + Send + 'static,
+	T: Send + 'static,
+{
+	let name = name.into();
+	let id = TaskId::next();
+	let span = tracing::info_span!("task", name = %name, id = id.0);
+	spawn_registered(&runtime::handle(), id, Some(name), Location::caller(), f.instrument(span))
+}
+
+/// Spawns an abortable task and registers it under the given name and location
+fn spawn_registered<F, T>(
+	handle: &Handle,
+	id: TaskId,
+	name: Option<String>,
+	location: &'static Location<'static>,
+	f: F,
+) -> Task<T>
 where
 	F: Future<Output = T> + Send + 'static,
 	T: Send + 'static,
 {
 	let (abort_handle, abort_reg) = AbortHandle::new_pair();
-	let handle = tokio::task::spawn(Abortable::new(f, abort_reg));
+	spawn_abortable(handle, id, name, location, abort_handle, abort_reg, f)
+}
+
+/// Spawns a task aborted through the given handle and registers it, see [spawn_registered]
+fn spawn_abortable<F, T>(
+	handle: &Handle,
+	id: TaskId,
+	name: Option<String>,
+	location: &'static Location<'static>,
+	abort_handle: AbortHandle,
+	abort_reg: AbortRegistration,
+	f: F,
+) -> Task<T>
+where
+	F: Future<Output = T> + Send + 'static,
+	T: Send + 'static,
+{
+	let (sender, completion) = watch::channel(TaskStatus::Running);
+	let registered =
+		registry::register(id, name, location, abort_handle.clone(), completion.clone());
+	spawn_with_registration(
+		handle,
+		id,
+		abort_handle,
+		abort_reg,
+		(sender, completion),
+		registered,
+		f,
+	)
+}
+
+/// Spawns a task which has already been registered, see [spawn_abortable]
+fn spawn_with_registration<F, T>(
+	handle: &Handle,
+	id: TaskId,
+	abort_handle: AbortHandle,
+	abort_reg: AbortRegistration,
+	(sender, completion): (watch::Sender<TaskStatus>, watch::Receiver<TaskStatus>),
+	(registration, recorder): (registry::Registration, Arc<metrics::Recorder>),
+	f: F,
+) -> Task<T>
+where
+	F: Future<Output = T> + Send + 'static,
+	T: Send + 'static,
+{
+	let cancellation = ShutdownToken::new();
+	// Created outside the future so that it is also dropped if the future never runs
+	let completion_guard = Completion { sender, finished: false };
+	let cancellation_token = cancellation.clone();
+	let handle = handle.spawn(Abortable::new(
+		metrics::Measured::new(
+			async move {
+				let _registration = registration;
+				let mut completion_guard = completion_guard;
+				let value =
+					panic::report_panics(shutdown::with_cancellation(cancellation_token, f)).await;
+				completion_guard.finished = true;
+				value
+			},
+			recorder,
+		),
+		abort_reg,
+	));
+
+	let children = Children::default();
+	let abort_on_drop = AbortOnDrop {
+		handles: Some((abort_handle.clone(), handle.abort_handle(), children.clone())),
+	};
+	Task {
+		id,
+		handle,
+		abort_handle,
+		timeout: None,
+		completion,
+		children,
+		stall: None,
+		abort_reason: AbortReason::default(),
+		cancellation,
+		abort_on_drop,
+	}
+}
+
+/// Spawns an abortable task which is aborted if it does not complete within the timeout
+///
+/// See [Task::with_timeout].
+#[track_caller]
+pub fn task_with_timeout<F, T>(timeout: Duration, f: F) -> Task<T>
+where
+	F: Future<Output = T> + Send + 'static,
+	T: Send + 'static,
+{
+	task(f).with_timeout(timeout)
+}
+
+/// Spawns an abortable task which waits for a permit before it starts executing
+///
+/// The permit is held until the task completes or is aborted, so no more tasks sharing the
+/// semaphore run at once than it has permits. If the semaphore is closed, the task runs
+/// without a permit.
+#[track_caller]
+pub fn task_limited<F, T>(permits: &Arc<Semaphore>, f: F) -> Task<T>
+where
+	F: Future<Output = T> + Send + 'static,
+	T: Send + 'static,
+{
+	let permits = permits.clone();
+	task(async move {
+		let _permit = permits.acquire_owned().await.ok();
+		f.await
+	})
+}
+
+/// Spawns an abortable task which catches a panic and returns it as a [Panicked] value
+///
+/// The panic is still reported by the panic hook, but does not propagate through the join handle.
+/// This requires the default `panic = "unwind"` strategy.
+#[track_caller]
+pub fn task_catch_panic<F, T>(f: F) -> Task<Result<T, Panicked>>
+where
+	F: Future<Output = T> + Send + 'static,
+	T: Send + 'static,
+{
+	task(
+		AssertUnwindSafe(f)
+			.catch_unwind()
+			.map(|result| result.map_err(Panicked::from_payload)),
+	)
+}
+
+/// Awaits a task returning a `Result`, treating an abort as no value and an `Err` as a failure
+#[doc(hidden)]
+pub async fn try_allow_abort<T, E>(task: Task<Result<T, E>>) -> Result<Option<T>, TaskError>
+where
+	E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+	match task.await {
+		Ok(Ok(value)) => Ok(Some(value)),
+		Ok(Err(e)) => Err(TaskError::Failed(e.into())),
+		Err(e) if e.is_abort() => Ok(None),
+		Err(e) => Err(e),
+	}
+}
+
+/// Awaits a task, aborting the other tasks if it panicked
+#[doc(hidden)]
+pub async fn await_aborting_on_panic<T>(
+	task: Task<T>,
+	others: &[AbortHandle],
+) -> Result<T, TaskError> {
+	let result = task.await;
+	if let Err(TaskError::Panicked { .. }) = &result {
+		for other in others {
+			other.abort();
+		}
+	}
+	result
+}
+
+/// Awaits every task concurrently, reporting every failure instead of stopping at the first
+///
+/// If a task panics, the tasks still running are aborted. Failures are wrapped in
+/// [TaskError::MultipleErrors] if there is more than one.
+pub async fn join_all<T>(tasks: Vec<Task<T>>) -> Result<Vec<T>, TaskError> {
+	let handles: Vec<AbortHandle> = tasks.iter().map(|task| task.abort_handle.clone()).collect();
+	let results = futures::future::join_all(
+		tasks.into_iter().map(|task| await_aborting_on_panic(task, &handles)),
+	)
+	.await;
+
+	let mut values = Vec::with_capacity(results.len());
+	let mut errors = Vec::new();
+	for result in results {
+		match result {
+			Ok(value) => values.push(value),
+			Err(e) => errors.push(e),
+		}
+	}
+
+	if errors.is_empty() {
+		Ok(values)
+	} else {
+		Err(TaskError::aggregate(errors))
+	}
+}
+
+/// A boxed future which ends a task, as produced by [Task::await_allow_abort]
+#[doc(hidden)]
+pub type Ending<'a> = Pin<Box<dyn Future<Output = Result<(), TaskError>> + Send + 'a>>;
 
-	Task { handle, abort_handle }
+/// Awaits the endings concurrently, reporting every failure
+///
+/// Fails with [TaskError::TimedOut] if the endings do not complete within the timeout.
+#[doc(hidden)]
+pub async fn await_endings(
+	endings: Vec<Ending<'_>>,
+	timeout: Option<Duration>,
+) -> Result<(), TaskError> {
+	let all = futures::future::join_all(endings);
+	let results = match timeout {
+		Some(timeout) => tokio::time::timeout(timeout, all)
+			.await
+			.map_err(|_| TaskError::TimedOut(timeout))?,
+		None => all.await,
+	};
+
+	let errors: Vec<TaskError> = results.into_iter().filter_map(Result::err).collect();
+	if errors.is_empty() {
+		Ok(())
+	} else {
+		Err(TaskError::aggregate(errors))
+	}
+}
+
+/// Aborts every task, then waits for all of them to stop concurrently
+///
+/// A slow-to-cancel task does not delay the others. Aborts are not errors; every other failure
+/// is reported. If given, the wait is bounded by the timeout.
+pub async fn end_concurrent<T>(
+	tasks: Vec<Task<T>>,
+	timeout: Option<Duration>,
+) -> Result<(), TaskError>
+where
+	T: Send + 'static,
+{
+	for task in &tasks {
+		task.abort();
+	}
+
+	let endings = tasks
+		.into_iter()
+		.map(|task| -> Ending<'static> { Box::pin(task.await_allow_abort()) })
+		.collect();
+	await_endings(endings, timeout).await
+}
+
+/// Races the tasks, returning the value of the first to complete successfully
+///
+/// The remaining tasks are aborted. Tasks which panic or are aborted are skipped; if every task
+/// fails, all failures are reported as in [join_all].
+pub async fn select_first<T>(tasks: Vec<Task<T>>) -> Result<T, TaskError> {
+	let mut running: FuturesUnordered<Task<T>> = tasks.into_iter().collect();
+	let mut errors = Vec::new();
+	while let Some(result) = running.next().await {
+		match result {
+			// Dropping the remaining tasks aborts them
+			Ok(value) => return Ok(value),
+			Err(e) => errors.push(e),
+		}
+	}
+
+	Err(TaskError::aggregate(errors))
+}
+
+/// Converts a tuple of task results into a tuple of values, aggregating every error
+#[doc(hidden)]
+pub trait JoinResults {
+	type Output;
+
+	fn collect_results(self) -> Result<Self::Output, TaskError>;
 }
 
+macro_rules! impl_join_results {
+	($($value:ident),+) => {
+		impl<$($value),+> JoinResults for ($(Result<$value, TaskError>,)+) {
+			type Output = ($($value,)+);
+
+			#[allow(non_snake_case)]
+			fn collect_results(self) -> Result<Self::Output, TaskError> {
+				let ($($value,)+) = self;
+				let mut errors = Vec::new();
+				$(
+					let $value = match $value {
+						Ok(value) => Some(value),
+						Err(e) => {
+							errors.push(e);
+							None
+						}
+					};
+				)+
+				match ($($value,)+) {
+					($(Some($value),)+) => Ok(($($value,)+)),
+					_ => Err(TaskError::aggregate(errors)),
+				}
+			}
+		}
+	};
+}
+
+impl_join_results!(A);
+impl_join_results!(A, B);
+impl_join_results!(A, B, C);
+impl_join_results!(A, B, C, D);
+impl_join_results!(A, B, C, D, E);
+impl_join_results!(A, B, C, D, E, F);
+impl_join_results!(A, B, C, D, E, F, G);
+impl_join_results!(A, B, C, D, E, F, G, H);
+impl_join_results!(A, B, C, D, E, F, G, H, I);
+impl_join_results!(A, B, C, D, E, F, G, H, I, J);
+impl_join_results!(A, B, C, D, E, F, G, H, I, J, K);
+impl_join_results!(A, B, C, D, E, F, G, H, I, J, K, L);
+
+/// Runs a process in a [Task] while streaming its output to a [State]
+pub trait ProcessToState: process::ProcessOperations {
+	/// Runs the process, returning a live view and a final view of its standard output
+	///
+	/// The [ReadOnlyState] is updated with the accumulated output as each line arrives.
+	/// The [Task] resolves with the full accumulated output once the process exits, whether or
+	/// not the process captures its output, or with the error of the process.
+	fn run_to_state(
+		mut self,
+	) -> (ReadOnlyState<String>, Task<Result<String, process::ProcessError>>) {
+		let state = State::new();
+		let writer = state.write();
+		let (sender, mut receiver) = tokio::sync::mpsc::channel::<String>(100);
+		let piped = self.pipe(process::Pipe::STDOUT, sender);
+
+		let task = task(async move {
+			piped?;
+
+			let accumulate = async {
+				while let Some(line) = receiver.recv().await {
+					writer
+						.modify(|output| output.get_or_insert_with(String::new).push_str(&line))
+						.await;
+				}
+			};
+
+			let (result, _) = tokio::join!(self.run(), accumulate);
+			result?;
+			Ok(writer.get().await.unwrap_or_default())
+		});
+
+		(state.read(), task)
+	}
+}
+
+impl<P> ProcessToState for P where P: process::ProcessOperations {}
+
 /// Awaits multiple tasks but allows them to abort
 #[macro_export]
 macro_rules! await_allow_abort {
@@ -127,6 +1101,19 @@ macro_rules! await_allow_abort {
     }};
 }
 
+/// Awaits multiple tasks returning `Result`s, allowing aborts but not application errors
+///
+/// Evaluates to a `Result` with a tuple of `Option`s, holding the value of each task or `None`
+/// if it was aborted. An `Err` returned by a task is reported as [TaskError::Failed], and every
+/// failure is reported as in [join_all]. Supports up to twelve tasks.
+#[macro_export]
+macro_rules! try_await_allow_abort {
+    ($($task:expr),+ $(,)?) => {{
+        let results = $crate::reexport::tokio::join!($($crate::try_allow_abort($task)),+);
+        $crate::JoinResults::collect_results(results)
+    }};
+}
+
 /// Aborts multiple tasks
 #[macro_export]
 macro_rules! abort {
@@ -139,18 +1126,103 @@ macro_rules! abort {
     };
 }
 
+/// Aborts multiple tasks, reporting which were still running
+///
+/// Evaluates to a `Vec<(TaskId, bool)>` where the flag is true if the task was interrupted
+/// and false if it had already finished.
 #[macro_export]
-macro_rules! end {
+macro_rules! abort_report {
     ($($task:expr),* $(,)?) => {{
-        let mut result = Ok(());
+        let mut report: Vec<($crate::TaskId, bool)> = Vec::new();
         $(
-            $task.abort();
+            report.push(($task.id(), $task.try_abort()));
         )*
+        report
+    }};
+}
+
+/// Awaits multiple tasks concurrently, reporting every failure
+///
+/// Evaluates to a `Result` with a tuple of the task values. Unlike [await_allow_abort], every
+/// task is awaited even after a failure, and a panic aborts the tasks still running. Supports
+/// up to twelve tasks of different types; see [join_all] for any number of tasks of one type.
+#[macro_export]
+macro_rules! join_all {
+    // Binds each task once, as hygiene keeps every `task` binding distinct
+    (@bind [$($bound:ident)*] $task:expr $(, $rest:expr)*) => {{
+        let task = $task;
+        $crate::join_all!(@bind [$($bound)* task] $($rest),*)
+    }};
+    (@bind [$($bound:ident)+]) => {{
+        let handles = vec![$($bound.abort_handle.clone()),+];
+        let results = $crate::reexport::tokio::join!(
+            $($crate::await_aborting_on_panic($bound, &handles)),+
+        );
+        $crate::JoinResults::collect_results(results)
+    }};
+    ($($task:expr),+ $(,)?) => {
+        $crate::join_all!(@bind [] $($task),+)
+    };
+}
+
+/// Waits for whichever of multiple tasks completes first, keeping the others alive
+///
+/// Evaluates to a future of `(index, result, remaining)`; see [await_any]. The tasks are moved
+/// in, and those still running are handed back in `remaining`.
+#[macro_export]
+macro_rules! await_any {
+    ($($task:expr),+ $(,)?) => {
+        $crate::await_any(vec![$($task),+])
+    };
+}
+
+/// Races multiple tasks of the same type, returning the first successful value
+///
+/// Evaluates to a future; see [select_first].
+#[macro_export]
+macro_rules! select_first {
+    ($($task:expr),+ $(,)?) => {
+        $crate::select_first(vec![$($task),+])
+    };
+}
+
+/// Signals shutdown, then waits for multiple tasks until the grace period elapses
+///
+/// Tasks still running after the grace period are aborted. Evaluates to a `Result` reporting
+/// every failure other than aborts.
+#[macro_export]
+macro_rules! shutdown {
+    ($token:expr, $grace:expr $(, $task:expr)* $(,)?) => {{
+        $token.signal();
+        let deadline = $crate::reexport::tokio::time::Instant::now() + $grace;
+        let mut errors: Vec<$crate::TaskError> = Vec::new();
         $(
-            if result.is_ok() {
-                result = $task.await_allow_abort().await;
+            if let Err(e) = $crate::end_by($task, deadline).await {
+                errors.push(e);
             }
         )*
-        result
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err($crate::TaskError::aggregate(errors))
+        }
+    }};
+}
+
+/// Aborts multiple tasks, then waits for all of them to stop concurrently
+///
+/// Evaluates to a `Result` reporting every failure other than aborts. See [end_concurrent] to
+/// bound the wait by a timeout.
+#[macro_export]
+macro_rules! end {
+    ($($task:expr),* $(,)?) => {{
+        $(
+            $task.abort();
+        )*
+        $crate::await_endings(
+            vec![$(Box::pin($task.await_allow_abort()) as $crate::Ending<'_>),*],
+            None,
+        )
+        .await
     }};
 }
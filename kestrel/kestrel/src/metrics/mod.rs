@@ -0,0 +1,92 @@
+use crate::registry::{registry, TaskInfo};
+use crate::{TaskId, TaskStatus};
+use std::future::Future;
+use std::panic::Location;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+/// Runtime metrics of a single task
+#[derive(Debug, Clone)]
+pub struct TaskMetrics {
+	/// The unique identifier of the task
+	pub id: TaskId,
+	/// The name of the task, if it was given one
+	pub name: Option<String>,
+	/// Where the task was spawned
+	pub location: &'static Location<'static>,
+	/// When the task was spawned
+	pub spawned_at: Instant,
+	/// How many times the task has been polled
+	pub polls: u64,
+	/// The total time spent polling the task
+	pub busy: Duration,
+	/// The lifecycle status of the task
+	pub status: TaskStatus,
+}
+
+impl From<&TaskInfo> for TaskMetrics {
+	fn from(info: &TaskInfo) -> Self {
+		Self {
+			id: info.id,
+			name: info.name.clone(),
+			location: info.location,
+			spawned_at: info.spawned_at,
+			polls: info.recorder.polls.load(Ordering::Relaxed),
+			busy: Duration::from_nanos(info.recorder.busy_nanos.load(Ordering::Relaxed)),
+			status: *info.completion.borrow(),
+		}
+	}
+}
+
+/// Returns the metrics of every live task and of the most recently completed ones
+///
+/// Tasks are ordered by [TaskId]. Only the last 1024 completed tasks are kept.
+pub fn snapshot() -> Vec<TaskMetrics> {
+	let Ok(registry) = registry().lock() else {
+		return Vec::new();
+	};
+
+	let mut metrics: Vec<TaskMetrics> = registry
+		.completed
+		.iter()
+		.chain(registry.live.values())
+		.map(TaskMetrics::from)
+		.collect();
+	metrics.sort_by_key(|metrics| metrics.id);
+	metrics
+}
+
+/// Accumulates the poll count and busy time of a task
+#[derive(Debug, Default)]
+pub(crate) struct Recorder {
+	polls: AtomicU64,
+	busy_nanos: AtomicU64,
+}
+
+/// Records every poll of the inner future
+pub(crate) struct Measured<F> {
+	inner: Pin<Box<F>>,
+	recorder: Arc<Recorder>,
+}
+
+impl<F> Measured<F> {
+	pub(crate) fn new(inner: F, recorder: Arc<Recorder>) -> Self {
+		Self { inner: Box::pin(inner), recorder }
+	}
+}
+
+impl<F: Future> Future for Measured<F> {
+	type Output = F::Output;
+
+	fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+		let started = Instant::now();
+		let poll = self.inner.as_mut().poll(cx);
+		let busy = u64::try_from(started.elapsed().as_nanos()).unwrap_or(u64::MAX);
+		self.recorder.polls.fetch_add(1, Ordering::Relaxed);
+		self.recorder.busy_nanos.fetch_add(busy, Ordering::Relaxed);
+		poll
+	}
+}
@@ -0,0 +1,100 @@
+use crate::{task, Task, TaskError};
+use futures::stream::{FuturesUnordered, StreamExt};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// How a [Nursery] reacts to failing tasks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorPolicy {
+	/// Aborts the other tasks on the first failure and reports it
+	FailFast,
+	/// Awaits every task and reports every failure, including aborts
+	CollectAll,
+	/// Awaits every task and reports every failure other than aborts
+	IgnoreAborted,
+}
+
+/// A clonable set of tasks joined under an [ErrorPolicy]
+///
+/// Clones share the same tasks, so a nursery can be passed into library code which spawns into
+/// it. Tasks report an `Err` as [TaskError::Failed]. Dropping the last clone aborts every task
+/// which has not been joined.
+#[derive(Debug, Clone)]
+pub struct Nursery {
+	policy: ErrorPolicy,
+	tasks: Arc<Mutex<Vec<Task<Result<(), BoxError>>>>>,
+}
+
+impl Nursery {
+	pub fn new(policy: ErrorPolicy) -> Self {
+		Self { policy, tasks: Arc::new(Mutex::new(Vec::new())) }
+	}
+
+	/// Returns the error policy
+	pub fn policy(&self) -> ErrorPolicy {
+		self.policy
+	}
+
+	/// Spawns a task into the nursery
+	pub fn spawn<F, E>(&self, f: F)
+	where
+		F: Future<Output = Result<(), E>> + Send + 'static,
+		E: Into<BoxError>,
+	{
+		let spawned = task(async move { f.await.map_err(Into::into) });
+		self.tasks.lock().expect("nursery task lock poisoned").push(spawned);
+	}
+
+	/// Aborts every task which has not been joined
+	pub fn abort_all(&self) {
+		for task in self.tasks.lock().expect("nursery task lock poisoned").iter() {
+			task.abort();
+		}
+	}
+
+	/// Removes the tasks spawned so far for joining
+	fn take(&self) -> Vec<Task<Result<(), BoxError>>> {
+		std::mem::take(&mut *self.tasks.lock().expect("nursery task lock poisoned"))
+	}
+
+	/// Awaits every task according to the policy, including tasks spawned while joining
+	pub async fn join(&self) -> Result<(), TaskError> {
+		let mut running: FuturesUnordered<_> = self.take().into_iter().collect();
+		let mut errors = Vec::new();
+
+		loop {
+			running.extend(self.take());
+			let Some(result) = running.next().await else {
+				break;
+			};
+
+			let error = match result {
+				Ok(Ok(())) => continue,
+				Ok(Err(e)) => TaskError::Failed(e),
+				Err(e) => e,
+			};
+			if self.policy == ErrorPolicy::IgnoreAborted && error.is_abort() {
+				continue;
+			}
+
+			if self.policy == ErrorPolicy::FailFast {
+				self.abort_all();
+				for task in running.iter() {
+					task.abort();
+				}
+				// Wait for the other tasks to stop before reporting
+				while running.next().await.is_some() {}
+				return Err(error);
+			}
+			errors.push(error);
+		}
+
+		if errors.is_empty() {
+			Ok(())
+		} else {
+			Err(TaskError::aggregate(errors))
+		}
+	}
+}
@@ -0,0 +1,88 @@
+use crate::Panicked;
+use futures::FutureExt;
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::Once;
+
+thread_local! {
+	/// The backtrace of the latest panic in a task on this thread
+	static LAST_BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
+	/// How many task futures are being polled on this thread
+	static POLLING_TASKS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// A panic caught within a task, carried as the payload of the re-raised panic
+#[derive(Debug)]
+pub(crate) struct PanicReport {
+	pub(crate) message: String,
+	/// Left unresolved until the report is read, as symbolizing is slow
+	pub(crate) backtrace: Option<Backtrace>,
+}
+
+impl PanicReport {
+	/// Formats the backtrace, if one was captured
+	pub(crate) fn backtrace(&self) -> Option<String> {
+		self.backtrace
+			.as_ref()
+			.filter(|backtrace| backtrace.status() == BacktraceStatus::Captured)
+			.map(ToString::to_string)
+	}
+}
+
+/// Marks the current thread as polling a task until dropped
+struct Polling;
+
+impl Polling {
+	fn enter() -> Self {
+		POLLING_TASKS.with(|polling| polling.set(polling.get() + 1));
+		Polling
+	}
+}
+
+impl Drop for Polling {
+	fn drop(&mut self) {
+		POLLING_TASKS.with(|polling| polling.set(polling.get().saturating_sub(1)));
+	}
+}
+
+/// Installs a panic hook which records the backtrace of panics in tasks, once per process
+///
+/// Backtraces are only captured if enabled through `RUST_BACKTRACE`, and only for panics raised
+/// while polling a task. The previously installed hook still runs afterwards, so panics are
+/// reported as before.
+fn install_hook() {
+	static INSTALL: Once = Once::new();
+	INSTALL.call_once(|| {
+		let previous = std::panic::take_hook();
+		std::panic::set_hook(Box::new(move |info| {
+			if POLLING_TASKS.with(Cell::get) > 0 {
+				let backtrace = Backtrace::capture();
+				LAST_BACKTRACE.with(|last| *last.borrow_mut() = Some(backtrace));
+			}
+			previous(info);
+		}));
+	});
+}
+
+/// Runs the future, re-raising any panic with a [PanicReport] as its payload
+pub(crate) async fn report_panics<F>(f: F) -> F::Output
+where
+	F: Future,
+{
+	install_hook();
+	let mut caught = std::pin::pin!(AssertUnwindSafe(f).catch_unwind());
+	let caught = std::future::poll_fn(|cx| {
+		let _polling = Polling::enter();
+		caught.as_mut().poll(cx)
+	});
+	match caught.await {
+		Ok(value) => value,
+		Err(payload) => {
+			let message = Panicked::from_payload(payload).0;
+			let backtrace = LAST_BACKTRACE.with(|last| last.borrow_mut().take());
+			std::panic::resume_unwind(Box::new(PanicReport { message, backtrace }))
+		}
+	}
+}
@@ -0,0 +1,145 @@
+use crate::metrics::Recorder;
+use crate::{SpawnError, TaskId, TaskStatus};
+use futures::future::AbortHandle;
+use std::collections::{BTreeMap, VecDeque};
+use std::panic::Location;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
+use tokio::sync::watch;
+
+/// How many completed tasks are kept for [crate::metrics::snapshot]
+const MAX_COMPLETED: usize = 1024;
+
+/// A snapshot of a live task in the global registry
+#[derive(Debug, Clone)]
+pub struct TaskInfo {
+	/// The unique identifier of the task
+	pub id: TaskId,
+	/// The name of the task, if it was given one
+	pub name: Option<String>,
+	/// Where the task was spawned
+	pub location: &'static Location<'static>,
+	/// When the task was spawned
+	pub spawned_at: Instant,
+	abort_handle: AbortHandle,
+	pub(crate) recorder: Arc<Recorder>,
+	pub(crate) completion: watch::Receiver<TaskStatus>,
+}
+
+impl TaskInfo {
+	/// Aborts the task
+	pub fn abort(&self) {
+		self.abort_handle.abort();
+	}
+}
+
+/// Live tasks, and the most recently completed ones for metrics
+#[derive(Debug, Default)]
+pub(crate) struct Registry {
+	pub(crate) live: BTreeMap<TaskId, TaskInfo>,
+	pub(crate) completed: VecDeque<TaskInfo>,
+	/// The most live tasks [crate::try_task] allows, if limited
+	pub(crate) max_live: Option<usize>,
+}
+
+pub(crate) fn registry() -> &'static Mutex<Registry> {
+	static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+	REGISTRY.get_or_init(|| Mutex::new(Registry::default()))
+}
+
+/// Removes a task from the live tasks when its future is dropped
+#[derive(Debug)]
+pub(crate) struct Registration(TaskId);
+
+impl Drop for Registration {
+	fn drop(&mut self) {
+		if let Ok(mut registry) = registry().lock() {
+			if let Some(info) = registry.live.remove(&self.0) {
+				registry.completed.push_back(info);
+				if registry.completed.len() > MAX_COMPLETED {
+					registry.completed.pop_front();
+				}
+			}
+		}
+	}
+}
+
+/// Adds a task to the registry until the returned registration is dropped
+pub(crate) fn register(
+	id: TaskId,
+	name: Option<String>,
+	location: &'static Location<'static>,
+	abort_handle: AbortHandle,
+	completion: watch::Receiver<TaskStatus>,
+) -> (Registration, Arc<Recorder>) {
+	let (info, recorder) = task_info(id, name, location, abort_handle, completion);
+	if let Ok(mut registry) = registry().lock() {
+		registry.live.insert(id, info);
+	}
+	(Registration(id), recorder)
+}
+
+/// Adds a task to the registry, failing if the live tasks have reached the configured limit
+///
+/// The limit is checked and the task inserted under a single lock, so that concurrent spawns
+/// cannot go over it.
+pub(crate) fn try_register(
+	id: TaskId,
+	name: Option<String>,
+	location: &'static Location<'static>,
+	abort_handle: AbortHandle,
+	completion: watch::Receiver<TaskStatus>,
+) -> Result<(Registration, Arc<Recorder>), SpawnError> {
+	let mut registry = registry().lock().map_err(|_| SpawnError::RegistryPoisoned)?;
+	if let Some(limit) = registry.max_live {
+		if registry.live.len() >= limit {
+			return Err(SpawnError::TooManyTasks(limit));
+		}
+	}
+	let (info, recorder) = task_info(id, name, location, abort_handle, completion);
+	registry.live.insert(id, info);
+	Ok((Registration(id), recorder))
+}
+
+/// Describes a task being spawned, along with the recorder for its metrics
+fn task_info(
+	id: TaskId,
+	name: Option<String>,
+	location: &'static Location<'static>,
+	abort_handle: AbortHandle,
+	completion: watch::Receiver<TaskStatus>,
+) -> (TaskInfo, Arc<Recorder>) {
+	let recorder = Arc::new(Recorder::default());
+	let info = TaskInfo {
+		id,
+		name,
+		location,
+		spawned_at: Instant::now(),
+		abort_handle,
+		recorder: recorder.clone(),
+		completion,
+	};
+	(info, recorder)
+}
+
+/// Returns every live task spawned through kestrel, ordered by [TaskId]
+///
+/// A task is live until its future completes or is dropped after an abort. This is meant for
+/// debugging hung test runs, e.g. printing what is still running before a timeout.
+pub fn tasks() -> Vec<TaskInfo> {
+	registry()
+		.lock()
+		.map(|registry| registry.live.values().cloned().collect())
+		.unwrap_or_default()
+}
+
+/// Limits how many tasks may be live when spawning through [crate::try_task], or lifts the limit
+///
+/// Spawns beyond the limit fail with [SpawnError::TooManyTasks], which catches runaway spawns in
+/// stress tests. The limit is global; tasks spawned through [crate::task] are counted but are
+/// never refused.
+pub fn set_max_live_tasks(limit: Option<usize>) {
+	if let Ok(mut registry) = registry().lock() {
+		registry.max_live = limit;
+	}
+}
@@ -0,0 +1,91 @@
+use crate::{task, Task};
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// How long to wait between attempts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backoff {
+	/// Waits the same duration before every retry
+	Fixed(Duration),
+	/// Doubles the wait before every retry, starting at `initial` and capped at `max`
+	Exponential { initial: Duration, max: Duration },
+}
+
+/// Configures how often and how fast an operation is retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+	max_attempts: u32,
+	backoff: Backoff,
+	jitter: bool,
+}
+
+impl RetryPolicy {
+	/// Creates a policy making at most the given number of attempts, 100ms apart
+	pub fn new(max_attempts: u32) -> Self {
+		Self { max_attempts, backoff: Backoff::Fixed(Duration::from_millis(100)), jitter: false }
+	}
+
+	/// Sets the backoff between attempts
+	pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+		self.backoff = backoff;
+		self
+	}
+
+	/// Randomizes each wait to between half and all of the backoff
+	///
+	/// This keeps several retrying clients from hammering a daemon in lockstep.
+	pub fn with_jitter(mut self, jitter: bool) -> Self {
+		self.jitter = jitter;
+		self
+	}
+
+	/// Returns the maximum number of attempts
+	pub fn max_attempts(&self) -> u32 {
+		self.max_attempts
+	}
+
+	/// Returns the wait before the given retry, counting from zero
+	pub fn delay(&self, retry: u32) -> Duration {
+		let delay = match self.backoff {
+			Backoff::Fixed(delay) => delay,
+			Backoff::Exponential { initial, max } => {
+				initial.saturating_mul(2u32.saturating_pow(retry)).min(max)
+			}
+		};
+
+		if self.jitter {
+			let random = RandomState::new().build_hasher().finish();
+			let half = delay / 2;
+			half + half.mul_f64((random % 1024) as f64 / 1024.0)
+		} else {
+			delay
+		}
+	}
+}
+
+/// Spawns a task which calls the operation until it succeeds or the policy runs out of attempts
+///
+/// Resolves with the first success or the last error.
+pub fn retry<F, Fut, T, E>(policy: RetryPolicy, mut f: F) -> Task<Result<T, E>>
+where
+	F: FnMut() -> Fut + Send + 'static,
+	Fut: Future<Output = Result<T, E>> + Send + 'static,
+	T: Send + 'static,
+	E: Send + 'static,
+{
+	task(async move {
+		let mut retry = 0;
+		loop {
+			match f().await {
+				Ok(value) => return Ok(value),
+				Err(e) if retry + 1 >= policy.max_attempts => return Err(e),
+				Err(_) => {
+					tokio::time::sleep(policy.delay(retry)).await;
+					retry += 1;
+				}
+			}
+		}
+	})
+}
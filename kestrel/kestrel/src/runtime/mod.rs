@@ -0,0 +1,35 @@
+use std::future::Future;
+use std::sync::OnceLock;
+use tokio::runtime::{Builder, Handle, Runtime};
+
+/// Returns kestrel's fallback runtime, creating it on first use
+fn fallback() -> &'static Runtime {
+	static FALLBACK: OnceLock<Runtime> = OnceLock::new();
+	FALLBACK.get_or_init(|| {
+		Builder::new_multi_thread()
+			.enable_all()
+			.thread_name("kestrel-fallback")
+			.build()
+			.expect("failed to build the kestrel fallback runtime")
+	})
+}
+
+/// Returns the handle of the current runtime, or of kestrel's fallback runtime outside of one
+///
+/// The fallback runtime is created lazily and lives for the rest of the process. This lets
+/// tasks be spawned from synchronous contexts such as build scripts or sync test setup.
+pub fn handle() -> Handle {
+	Handle::try_current().unwrap_or_else(|_| fallback().handle().clone())
+}
+
+/// Runs a future to completion on kestrel's fallback runtime
+///
+/// # Panics
+///
+/// Panics if called from within an asynchronous context.
+pub fn block_on<F>(f: F) -> F::Output
+where
+	F: Future,
+{
+	fallback().block_on(f)
+}
@@ -0,0 +1,74 @@
+use crate::{task, Task};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Errors returned by tasks spawned within a [scope]
+#[derive(Debug, thiserror::Error)]
+#[error("{} scoped task(s) failed: {errors:?}", errors.len())]
+pub struct ScopeError {
+	/// The failures of each task, in spawn order
+	pub errors: Vec<BoxError>,
+}
+
+/// A handle for spawning tasks which end with the enclosing [scope]
+///
+/// Clones share the same set of tasks.
+#[derive(Debug, Clone, Default)]
+pub struct Scope {
+	tasks: Arc<Mutex<Vec<Task<Result<(), BoxError>>>>>,
+}
+
+impl Scope {
+	/// Spawns a task which is aborted and awaited when the scope exits
+	pub fn spawn<F, E>(&self, f: F)
+	where
+		F: Future<Output = Result<(), E>> + Send + 'static,
+		E: Into<BoxError>,
+	{
+		let spawned = task(async move { f.await.map_err(Into::into) });
+		self.tasks.lock().expect("scope task lock poisoned").push(spawned);
+	}
+
+	/// Aborts every task and waits for all of them to stop, collecting their failures
+	async fn end(&self) -> Vec<BoxError> {
+		let tasks = std::mem::take(&mut *self.tasks.lock().expect("scope task lock poisoned"));
+		for task in &tasks {
+			task.abort();
+		}
+
+		let mut errors = Vec::new();
+		for task in tasks {
+			match task.await {
+				Ok(Ok(())) => {}
+				Ok(Err(e)) => errors.push(e),
+				Err(e) if e.is_abort() => {}
+				Err(e) => errors.push(Box::new(e)),
+			}
+		}
+		errors
+	}
+}
+
+/// Runs the body with a [Scope], then aborts and awaits every task spawned within it
+///
+/// Unlike dropping a [Task], this guarantees the tasks have stopped before the scope returns, so
+/// resources such as temp dirs can be torn down safely afterwards. Failures of tasks which
+/// completed before the scope exited are aggregated into a [ScopeError]. Tasks spawned through a
+/// clone which outlives the scope are only aborted once the last clone is dropped.
+pub async fn scope<F, Fut, T>(f: F) -> Result<T, ScopeError>
+where
+	F: FnOnce(Scope) -> Fut,
+	Fut: Future<Output = T>,
+{
+	let scope = Scope::default();
+	let value = f(scope.clone()).await;
+
+	let errors = scope.end().await;
+	if errors.is_empty() {
+		Ok(value)
+	} else {
+		Err(ScopeError { errors })
+	}
+}
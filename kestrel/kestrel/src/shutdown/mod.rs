@@ -0,0 +1,106 @@
+use crate::{task, Task, TaskError};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+#[derive(Debug, Default)]
+struct ShutdownInner {
+	signaled: AtomicBool,
+	notify: Notify,
+}
+
+/// A clonable signal asking tasks to shut down gracefully
+///
+/// Tasks await [ShutdownToken::wait] alongside their work and clean up once it resolves. All
+/// clones share the same signal.
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownToken {
+	inner: Arc<ShutdownInner>,
+}
+
+impl ShutdownToken {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Signals shutdown, waking every waiting task
+	pub fn signal(&self) {
+		self.inner.signaled.store(true, Ordering::SeqCst);
+		self.inner.notify.notify_waiters();
+	}
+
+	/// Returns whether shutdown has been signaled
+	pub fn is_signaled(&self) -> bool {
+		self.inner.signaled.load(Ordering::SeqCst)
+	}
+
+	/// Waits until shutdown is signaled, returning immediately if it already was
+	pub async fn wait(&self) {
+		loop {
+			// Register for notification before checking to avoid missing a signal
+			let notified = self.inner.notify.notified();
+			if self.is_signaled() {
+				return;
+			}
+			notified.await;
+		}
+	}
+}
+
+tokio::task_local! {
+	/// The cancellation token of the kestrel task being polled
+	static CANCELLATION: ShutdownToken;
+}
+
+/// Runs the future with the given token as its cancellation token, see [cancelled]
+pub(crate) fn with_cancellation<F>(token: ShutdownToken, f: F) -> impl Future<Output = F::Output>
+where
+	F: Future,
+{
+	CANCELLATION.scope(token, f)
+}
+
+/// Waits until the current task is asked to cancel through [Task::cancel_graceful]
+///
+/// Outside of a kestrel task, this never resolves.
+pub async fn cancelled() {
+	match CANCELLATION.try_with(ShutdownToken::clone) {
+		Ok(token) => token.wait().await,
+		Err(_) => std::future::pending().await,
+	}
+}
+
+/// Returns whether the current task has been asked to cancel through [Task::cancel_graceful]
+pub fn is_cancelled() -> bool {
+	CANCELLATION.try_with(ShutdownToken::is_signaled).unwrap_or(false)
+}
+
+/// Spawns an abortable task which receives a clone of the shutdown token
+#[track_caller]
+pub fn task_with_shutdown<F, Fut, T>(token: &ShutdownToken, f: F) -> Task<T>
+where
+	F: FnOnce(ShutdownToken) -> Fut,
+	Fut: Future<Output = T> + Send + 'static,
+	T: Send + 'static,
+{
+	task(f(token.clone()))
+}
+
+/// Waits for a task to finish until the deadline, then aborts it and waits for it to stop
+///
+/// Aborts are not errors.
+pub async fn end_by<T>(mut task: Task<T>, deadline: Instant) -> Result<(), TaskError> {
+	tokio::select! {
+		result = &mut task => match result {
+			Ok(_) => Ok(()),
+			Err(e) if e.is_abort() => Ok(()),
+			Err(e) => Err(e),
+		},
+		_ = tokio::time::sleep_until(deadline) => {
+			task.abort();
+			task.await_allow_abort().await
+		}
+	}
+}
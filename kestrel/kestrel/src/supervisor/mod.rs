@@ -0,0 +1,156 @@
+use crate::{task, Panicked, TaskError, TaskGroup, TaskStatus};
+use futures::FutureExt;
+use std::collections::HashMap;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// When a supervised child is restarted after it exits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestartPolicy {
+	/// The child is never restarted
+	Never,
+	/// The child is restarted whenever it exits, successfully or not
+	Always,
+	/// The child is restarted whenever it fails or panics
+	OnFailure,
+	/// The child is restarted on failure at most the given number of times
+	MaxRetries(u32),
+}
+
+/// Errors returned by supervised children which are not restarted
+#[derive(Debug, thiserror::Error)]
+pub enum SupervisorError {
+	#[error("child failed after {restarts} restarts: {source}")]
+	Failed {
+		restarts: u32,
+		#[source]
+		source: Box<dyn std::error::Error + Send + Sync>,
+	},
+}
+
+/// Owns a set of named tasks and restarts them according to a [RestartPolicy]
+///
+/// A child is created from a factory which is called again on every restart. Panics count as
+/// failures. Restarts are delayed by an exponential backoff which resets after a successful run.
+#[derive(Debug)]
+pub struct Supervisor<T> {
+	children: TaskGroup<Result<T, SupervisorError>>,
+	restarts: HashMap<String, Arc<AtomicU32>>,
+	initial_backoff: Duration,
+	max_backoff: Duration,
+}
+
+impl<T> Supervisor<T>
+where
+	T: Send + 'static,
+{
+	pub fn new() -> Self {
+		Self {
+			children: TaskGroup::new(),
+			restarts: HashMap::new(),
+			initial_backoff: Duration::from_millis(100),
+			max_backoff: Duration::from_secs(5),
+		}
+	}
+
+	/// Sets the delay before the first restart and the cap it doubles up to
+	pub fn backoff(&mut self, initial: Duration, max: Duration) -> &mut Self {
+		self.initial_backoff = initial;
+		self.max_backoff = max;
+		self
+	}
+
+	/// Spawns a child under a name, restarting it according to the policy
+	///
+	/// Replaces and aborts any child previously supervised under the same name.
+	pub fn supervise<F, Fut, E>(
+		&mut self,
+		name: impl Into<String>,
+		policy: RestartPolicy,
+		factory: F,
+	) where
+		F: Fn() -> Fut + Send + 'static,
+		Fut: Future<Output = Result<T, E>> + Send + 'static,
+		E: Into<Box<dyn std::error::Error + Send + Sync>>,
+	{
+		let name = name.into();
+		let restarts = Arc::new(AtomicU32::new(0));
+		let counter = restarts.clone();
+		let initial_backoff = self.initial_backoff;
+		let max_backoff = self.max_backoff;
+
+		let child = task(async move {
+			let mut backoff = initial_backoff;
+			loop {
+				let result: Result<T, Box<dyn std::error::Error + Send + Sync>> =
+					match AssertUnwindSafe(factory()).catch_unwind().await {
+						Ok(result) => result.map_err(Into::into),
+						Err(payload) => Err(Panicked::from_payload(payload).into()),
+					};
+
+				let restart = match (&result, policy) {
+					(_, RestartPolicy::Always) | (Err(_), RestartPolicy::OnFailure) => true,
+					(Err(_), RestartPolicy::MaxRetries(max)) => {
+						counter.load(Ordering::SeqCst) < max
+					}
+					_ => false,
+				};
+				if !restart {
+					return result.map_err(|source| SupervisorError::Failed {
+						restarts: counter.load(Ordering::SeqCst),
+						source,
+					});
+				}
+
+				if result.is_ok() {
+					backoff = initial_backoff;
+				}
+				tokio::time::sleep(backoff).await;
+				backoff = (backoff * 2).min(max_backoff);
+				counter.fetch_add(1, Ordering::SeqCst);
+			}
+		});
+
+		self.children.insert(name.clone(), child);
+		self.restarts.insert(name, restarts);
+	}
+
+	/// Returns how many times the named child has been restarted
+	pub fn restarts(&self, name: &str) -> Option<u32> {
+		self.restarts.get(name).map(|restarts| restarts.load(Ordering::SeqCst))
+	}
+
+	/// Returns the status of the named child
+	///
+	/// A child which is waiting to be restarted is reported as running.
+	pub fn status(&self, name: &str) -> Option<TaskStatus> {
+		self.children.status(name)
+	}
+
+	/// Aborts every child without restarting it
+	pub fn abort_all(&self) {
+		self.children.abort_all();
+	}
+
+	/// Waits for every child to stop on its own, returning the final result of each by name
+	pub async fn await_all(self) -> Result<Vec<(String, Result<T, SupervisorError>)>, TaskError> {
+		self.children.await_all().await
+	}
+
+	/// Aborts every child and waits for all of them to stop
+	pub async fn end_all(self) -> Result<(), TaskError> {
+		self.children.end_all().await
+	}
+}
+
+impl<T> Default for Supervisor<T>
+where
+	T: Send + 'static,
+{
+	fn default() -> Self {
+		Self::new()
+	}
+}
@@ -0,0 +1,30 @@
+use std::time::Duration;
+use tokio::runtime::{Builder, Runtime};
+
+/// Builds a single-threaded runtime whose clock starts paused
+///
+/// Time only moves when [advance] is called or when every task is idle, in which case the clock
+/// jumps straight to the next timer. Waits such as `wait_for_duration` therefore time out
+/// deterministically, regardless of how loaded the machine running the test is.
+pub fn test_runtime() -> Result<Runtime, std::io::Error> {
+	Builder::new_current_thread().enable_all().start_paused(true).build()
+}
+
+/// Moves the paused clock forward, firing every timer which elapses along the way
+///
+/// Must be called from within a runtime whose clock is paused, such as [test_runtime].
+pub async fn advance(duration: Duration) {
+	tokio::time::advance(duration).await;
+}
+
+/// Pauses the clock of the current runtime
+///
+/// Must be called from within a single-threaded runtime, such as `#[tokio::test]`.
+pub fn pause() {
+	tokio::time::pause();
+}
+
+/// Resumes the clock of the current runtime
+pub fn resume() {
+	tokio::time::resume();
+}
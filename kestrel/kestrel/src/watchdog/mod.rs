@@ -0,0 +1,81 @@
+use crate::{abort_children, Task};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// A clonable handle which a watched task pings to show it is making progress
+#[derive(Debug, Clone)]
+pub struct Heartbeat {
+	last: Arc<Mutex<Instant>>,
+}
+
+impl Heartbeat {
+	pub fn new() -> Self {
+		Self { last: Arc::new(Mutex::new(Instant::now())) }
+	}
+
+	/// Records that the task is making progress
+	pub fn ping(&self) {
+		if let Ok(mut last) = self.last.lock() {
+			*last = Instant::now();
+		}
+	}
+
+	/// Returns when the heartbeat was last pinged
+	pub fn last(&self) -> Instant {
+		self.last.lock().map(|last| *last).unwrap_or_else(|_| Instant::now())
+	}
+}
+
+impl Default for Heartbeat {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Aborts the task if the heartbeat is not pinged at least once per interval
+///
+/// The heartbeat is pinged once when the watchdog starts. On a stall, the callback is called and
+/// awaiting the task returns [crate::TaskError::Stalled]. The children of a stalled task are
+/// aborted along with it, as with [Task::with_timeout]. The watchdog runs as a child of the task,
+/// so it stops when the task completes, is aborted, or is dropped.
+pub fn watchdog<T, F>(
+	mut task: Task<T>,
+	heartbeat: &Heartbeat,
+	interval: Duration,
+	on_stall: F,
+) -> Task<T>
+where
+	F: FnOnce() + Send + 'static,
+{
+	let stalled = Arc::new(AtomicBool::new(false));
+	task.stall = Some((interval, stalled.clone()));
+
+	let abort_handle = task.abort_handle.clone();
+	let join_abort_handle = task.handle.abort_handle();
+	let children = task.children.clone();
+	let heartbeat = heartbeat.clone();
+	heartbeat.ping();
+
+	let monitor = task.child(async move {
+		loop {
+			tokio::time::sleep_until(heartbeat.last() + interval).await;
+			if join_abort_handle.is_finished() {
+				return;
+			}
+			if heartbeat.last() + interval <= Instant::now() {
+				stalled.store(true, Ordering::SeqCst);
+				abort_handle.abort();
+				join_abort_handle.abort();
+				abort_children(&children);
+				on_stall();
+				return;
+			}
+		}
+	});
+	// The task keeps the monitor's abort handles as a child, so its join handle can be detached
+	drop(monitor.into_parts());
+
+	task
+}
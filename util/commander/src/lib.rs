@@ -1,28 +1,47 @@
+pub mod pipeline;
+pub mod secret;
+pub mod stdin;
+
 use anyhow::Result;
 use futures::future::try_join;
-use std::ffi::OsStr;
-use std::path::Path;
-use std::process::Stdio;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
 use tokio::io::{self, AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
 use tokio::process::Command as InnerCommand;
 use tokio::signal::unix::{signal, SignalKind};
 use tokio::sync::mpsc::Sender;
+use tokio::time::{Duration, Instant};
 use tracing::info;
 
+pub use pipeline::Pipeline;
+pub use secret::Placeholder;
+use secret::Secret;
+pub use stdin::Source;
+
+/// Default capacity of the buffers used to read from and write the command's output.
+pub const DEFAULT_BUFFER_SIZE: usize = 8 * 1024;
+
+/// How long to wait for already-buffered output after the child is killed.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
 /// Pipes output to stdout/stderr and broadcasts it via multiple channels.
 async fn pipe_output<R, O>(
 	reader: R,
 	mut default_writer: BufWriter<O>, // Default stdout/stderr
 	senders: &Vec<Sender<String>>,    // Multiple fanout receivers
+	timestamped_senders: &[Sender<(Instant, String)>],
 	capture_output: bool,
 	mut output: Option<&mut String>, // Optional in-memory capture
+	buffer_size: usize,
 ) -> Result<()>
 where
 	R: tokio::io::AsyncRead + Unpin + Send + 'static,
 	O: tokio::io::AsyncWrite + Unpin + Send + 'static,
 {
-	let mut reader = BufReader::new(reader).lines();
+	let mut reader = BufReader::with_capacity(buffer_size, reader).lines();
 	while let Ok(Some(line)) = reader.next_line().await {
+		let read_at = Instant::now();
 		let formatted_line = format!("{}\n", line);
 		let line_bytes = formatted_line.as_bytes();
 
@@ -34,6 +53,9 @@ where
 		for sender in senders {
 			let _ = sender.send(formatted_line.clone()).await; // Clone per receiver
 		}
+		for sender in timestamped_senders {
+			let _ = sender.send((read_at, formatted_line.clone())).await;
+		}
 
 		// Capture in memory if needed
 		if capture_output {
@@ -45,6 +67,41 @@ where
 	Ok(())
 }
 
+/// Sends SIGKILL to every process in the process group led by the given pid.
+///
+/// Commands run with [Command::process_group] lead their own process group, so this also kills
+/// any processes the child spawned.
+pub fn kill_process_tree(pid: u32) -> std::io::Result<()> {
+	let pid = libc::pid_t::try_from(pid)
+		.map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+	// SAFETY: killpg has no memory safety requirements
+	if unsafe { libc::killpg(pid, libc::SIGKILL) } != 0 {
+		return Err(std::io::Error::last_os_error());
+	}
+	Ok(())
+}
+
+/// Kills the process tree of a child when dropped, unless the child has been reaped.
+///
+/// This cleans up grandchildren when a run is aborted, which `kill_on_drop` alone does not.
+struct ProcessTreeGuard {
+	pid: Option<u32>,
+}
+
+impl ProcessTreeGuard {
+	fn kill(&mut self) {
+		if let Some(pid) = self.pid.take() {
+			let _ = kill_process_tree(pid);
+		}
+	}
+}
+
+impl Drop for ProcessTreeGuard {
+	fn drop(&mut self) {
+		self.kill();
+	}
+}
+
 /// Runs a command with full stdout/stderr fanout.
 pub async fn run_command_with_fanout<C, I, S>(
 	command: C,
@@ -68,11 +125,28 @@ where
 }
 
 /// Builder for running commands
+///
+/// The command is built afresh for every run, so secret placeholders can be substituted into its
+/// arguments.
 pub struct Command {
-	inner: InnerCommand,
+	program: OsString,
+	args: Vec<OsString>,
+	envs: Vec<(OsString, OsString)>,
+	env_cleared: bool,
+	current_dir: Option<PathBuf>,
+	process_group: bool,
 	capture_output: bool,
 	stdout_senders: Vec<Sender<String>>,
 	stderr_senders: Vec<Sender<String>>,
+	stdout_timestamped_senders: Vec<Sender<(Instant, String)>>,
+	stderr_timestamped_senders: Vec<Sender<(Instant, String)>>,
+	buffer_size: usize,
+	secrets: Vec<Secret>,
+	timeout: Option<Duration>,
+	pid_sender: Option<tokio::sync::oneshot::Sender<u32>>,
+	exit_sender: Option<tokio::sync::watch::Sender<bool>>,
+	stdin: Option<Source>,
+	allow_nonzero_exit: bool,
 }
 
 impl Command {
@@ -103,9 +177,26 @@ impl Command {
 		stdout_senders: Vec<Sender<String>>,
 		stderr_senders: Vec<Sender<String>>,
 	) -> Self {
-		let mut inner = InnerCommand::new(program);
-		inner.kill_on_drop(true);
-		Self { inner, capture_output, stdout_senders, stderr_senders }
+		Self {
+			program: program.as_ref().to_os_string(),
+			args: Vec::new(),
+			envs: Vec::new(),
+			env_cleared: false,
+			current_dir: None,
+			process_group: false,
+			capture_output,
+			stdout_senders,
+			stderr_senders,
+			stdout_timestamped_senders: Vec::new(),
+			stderr_timestamped_senders: Vec::new(),
+			buffer_size: DEFAULT_BUFFER_SIZE,
+			secrets: Vec::new(),
+			timeout: None,
+			pid_sender: None,
+			exit_sender: None,
+			stdin: None,
+			allow_nonzero_exit: false,
+		}
 	}
 
 	pub fn set_capture_output(&mut self, capture_output: bool) -> &mut Self {
@@ -113,11 +204,72 @@ impl Command {
 		self
 	}
 
+	/// Sets the capacity of the buffers used to read and write the command's output.
+	///
+	/// Larger buffers reduce the number of reads for very chatty processes. A size of zero is
+	/// raised to one byte, as an empty buffer would read nothing.
+	pub fn buffer_size(&mut self, bytes: usize) -> &mut Self {
+		self.buffer_size = bytes.max(1);
+		self
+	}
+
+	/// Sets whether [Command::run] returns the output of a command which exits with a nonzero code
+	/// instead of failing.
+	pub fn allow_nonzero_exit(&mut self, allow: bool) -> &mut Self {
+		self.allow_nonzero_exit = allow;
+		self
+	}
+
+	/// Runs the command as the leader of a new process group, so that aborting or killing the run
+	/// also kills every process the command spawned.
+	///
+	/// The group runs in the background of the terminal: it does not receive Ctrl-C, and reads
+	/// standard input from `/dev/null` unless [Command::stdin] is given a source.
+	pub fn process_group(&mut self) -> &mut Self {
+		self.process_group = true;
+		self
+	}
+
+	/// Kills the command if it runs for longer than the given duration.
+	pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+		self.timeout = Some(timeout);
+		self
+	}
+
+	/// Sends the OS process id of the child once it has been spawned.
+	///
+	/// Only the next run reports its pid. The sender is dropped if the child fails to spawn.
+	pub fn pid_sender(&mut self, sender: tokio::sync::oneshot::Sender<u32>) -> &mut Self {
+		self.pid_sender = Some(sender);
+		self
+	}
+
+	/// Sends `true` once the child has exited and been reaped, after which its pid may belong to
+	/// another process.
+	///
+	/// Only the next run reports its exit. The sender is also dropped once the child can no longer
+	/// be signalled, e.g. if it fails to spawn or the run is aborted.
+	pub fn exit_sender(&mut self, sender: tokio::sync::watch::Sender<bool>) -> &mut Self {
+		self.exit_sender = Some(sender);
+		self
+	}
+
+	/// Registers secret contents to be passed to the command via a file.
+	///
+	/// Returns a [Placeholder] to use in the arguments. Before spawning, the contents are written
+	/// to a temp file with 0600 permissions and the placeholder is replaced with the file's path.
+	/// The file is deleted once the command completes. This keeps credentials out of `ps` and logs.
+	pub fn secret_file(&mut self, contents: Vec<u8>) -> Placeholder {
+		let placeholder = Placeholder::new();
+		self.secrets.push(Secret { placeholder: placeholder.clone(), contents });
+		placeholder
+	}
+
 	pub fn arg<S>(&mut self, arg: S) -> &mut Self
 	where
 		S: AsRef<OsStr>,
 	{
-		self.inner.arg(arg);
+		self.args.push(arg.as_ref().to_os_string());
 		self
 	}
 
@@ -126,7 +278,7 @@ impl Command {
 		I: IntoIterator<Item = S>,
 		S: AsRef<OsStr>,
 	{
-		self.inner.args(args);
+		self.args.extend(args.into_iter().map(|arg| arg.as_ref().to_os_string()));
 		self
 	}
 
@@ -140,29 +292,151 @@ impl Command {
 		self
 	}
 
+	/// Appends a sender receiving each standard output line with the instant it was read.
+	pub fn append_stdout_timestamped(&mut self, sender: Sender<(Instant, String)>) -> &mut Self {
+		self.stdout_timestamped_senders.push(sender);
+		self
+	}
+
+	/// Appends a sender receiving each standard error line with the instant it was read.
+	pub fn append_stderr_timestamped(&mut self, sender: Sender<(Instant, String)>) -> &mut Self {
+		self.stderr_timestamped_senders.push(sender);
+		self
+	}
+
+	/// Sets an environment variable for the command.
+	pub fn env<K, V>(&mut self, key: K, value: V) -> &mut Self
+	where
+		K: AsRef<OsStr>,
+		V: AsRef<OsStr>,
+	{
+		self.envs.push((key.as_ref().to_os_string(), value.as_ref().to_os_string()));
+		self
+	}
+
+	/// Sets several environment variables for the command.
+	pub fn envs<I, K, V>(&mut self, vars: I) -> &mut Self
+	where
+		I: IntoIterator<Item = (K, V)>,
+		K: AsRef<OsStr>,
+		V: AsRef<OsStr>,
+	{
+		for (key, value) in vars {
+			self.env(key, value);
+		}
+		self
+	}
+
+	/// Clears the inherited environment, keeping only variables set afterwards.
+	pub fn env_clear(&mut self) -> &mut Self {
+		self.envs.clear();
+		self.env_cleared = true;
+		self
+	}
+
+	/// Feeds the standard input of the command from the source.
+	///
+	/// Without a source, the command inherits the standard input of the current process, unless
+	/// it runs in its own [Command::process_group].
+	pub fn stdin(&mut self, source: impl Into<Source>) -> &mut Self {
+		self.stdin = Some(source.into());
+		self
+	}
+
 	pub fn current_dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Self {
-		self.inner.current_dir(dir);
+		self.current_dir = Some(dir.as_ref().to_path_buf());
 		self
 	}
 
 	pub fn get_current_dir(&self) -> Option<&Path> {
-		self.inner.as_std().get_current_dir()
+		self.current_dir.as_deref()
 	}
 
 	/// Runs the command and captures its output while streaming it.
 	pub async fn run(&mut self) -> Result<String> {
-		let cmd_display = self.inner.as_std().get_program().to_string_lossy().into_owned();
-		let args_display = self
-			.inner
-			.as_std()
-			.get_args()
-			.map(|s| s.to_string_lossy())
-			.collect::<Vec<_>>()
-			.join(" ");
+		let output = self.run_to_completion().await?;
+		if !output.status.success() && !self.allow_nonzero_exit {
+			return Err(anyhow::anyhow!(
+				"Command {} failed with args {}\nError  {}",
+				output.cmd_display,
+				output.args_display,
+				output.stderr.unwrap_or_else(|| "Unknown error".to_string())
+			));
+		}
+
+		Ok(output.stdout.unwrap_or_default())
+	}
+
+	/// Runs the command and errors unless it exits with the expected code.
+	///
+	/// Returns the captured standard output on a match, so table-driven tests can assert e.g.
+	/// that bad input exits with code 2.
+	pub async fn run_expect_code(&mut self, code: i32) -> Result<String> {
+		let output = self.run_to_completion().await?;
+		if output.status.code() != Some(code) {
+			return Err(anyhow::anyhow!(
+				"Command {} with args {} exited with {} but expected code {}\nError  {}",
+				output.cmd_display,
+				output.args_display,
+				output.status,
+				code,
+				output.stderr.unwrap_or_else(|| "Unknown error".to_string())
+			));
+		}
+
+		Ok(output.stdout.unwrap_or_default())
+	}
+
+	/// Runs the command and returns its exit code and output, regardless of its exit status.
+	///
+	/// Output is always captured for this run, even if capturing is otherwise disabled.
+	pub async fn run_with_output(&mut self) -> Result<CommandOutput> {
+		let capture_output = std::mem::replace(&mut self.capture_output, true);
+		let output = self.run_to_completion().await;
+		self.capture_output = capture_output;
+		let output = output?;
+
+		Ok(CommandOutput {
+			exit_code: output.status.code(),
+			stdout: output.stdout.unwrap_or_default(),
+			stderr: output.stderr.unwrap_or_default(),
+			duration: output.duration,
+		})
+	}
+
+	/// Returns the program and its space-joined arguments for logs and errors.
+	fn display(&self) -> (String, String) {
+		let cmd_display = self.program.to_string_lossy().into_owned();
+		let args_display =
+			self.args.iter().map(|s| s.to_string_lossy()).collect::<Vec<_>>().join(" ");
+		(cmd_display, args_display)
+	}
+
+	/// Builds the command to spawn, replacing each secret placeholder in the arguments with the
+	/// path of its file.
+	fn build(&self, secret_files: &[(Placeholder, tempfile::NamedTempFile)]) -> InnerCommand {
+		let mut inner = InnerCommand::new(&self.program);
+		inner.kill_on_drop(true);
+		if self.process_group {
+			inner.process_group(0);
+		}
+		inner.args(self.args.iter().map(|arg| secret::substitute_secrets(arg, secret_files)));
+		if let Some(dir) = &self.current_dir {
+			inner.current_dir(dir);
+		}
+		if self.env_cleared {
+			inner.env_clear();
+		}
+		inner.envs(self.envs.iter().map(|(key, value)| (key, value)));
+		inner
+	}
+
+	/// Runs the command until it exits, streaming its output, regardless of its exit status.
+	async fn run_to_completion(&mut self) -> Result<RunOutput> {
+		let (cmd_display, args_display) = self.display();
 		let working_dir = self
-			.inner
-			.as_std()
-			.get_current_dir()
+			.current_dir
+			.as_ref()
 			.map(|p| p.to_string_lossy().into_owned())
 			.unwrap_or_else(|| "default".to_string());
 
@@ -183,7 +457,32 @@ impl Command {
 			}
 		});
 
-		let mut child = self.inner.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+		// Secret files live until the end of the run and are deleted on drop
+		let secret_files = secret::write_secret_files(&self.secrets)?;
+		let mut inner = self.build(&secret_files);
+		let stdin_source = stdin::take_for_run(&mut self.stdin);
+		// A background process group reading the terminal would be stopped by SIGTTIN
+		let stdin = match (&stdin_source, self.process_group) {
+			(Some(_), _) => Stdio::piped(),
+			(None, true) => Stdio::null(),
+			(None, false) => Stdio::inherit(),
+		};
+
+		// Dropped after the child, so the exit is not reported while the child can still run
+		let exit_sender = self.exit_sender.take();
+		let started = Instant::now();
+		let mut child = inner.stdin(stdin).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+		// Without a process group of its own, killing the group would kill the caller too
+		let mut process_tree = ProcessTreeGuard { pid: child.id().filter(|_| self.process_group) };
+		if let (Some(source), Some(child_stdin)) = (stdin_source, child.stdin.take()) {
+			tokio::spawn(async move {
+				// The child may exit without reading all of its input
+				let _ = stdin::feed(source, child_stdin).await;
+			});
+		}
+		if let (Some(sender), Some(pid)) = (self.pid_sender.take(), child.id()) {
+			let _ = sender.send(pid);
+		}
 
 		let stdout = child.stdout.take().ok_or_else(|| {
 			anyhow::anyhow!("Failed to capture standard output from command {cmd_display}")
@@ -195,48 +494,109 @@ impl Command {
 		let mut stdout_output = if self.capture_output { Some(String::new()) } else { None };
 		let mut stderr_output = if self.capture_output { Some(String::new()) } else { None };
 
-		let stdout_writer = BufWriter::new(io::stdout());
-		let stderr_writer = BufWriter::new(io::stderr());
+		let stdout_writer = BufWriter::with_capacity(self.buffer_size, io::stdout());
+		let stderr_writer = BufWriter::with_capacity(self.buffer_size, io::stderr());
 
 		let stdout_future = pipe_output(
 			stdout,
 			stdout_writer,
 			&self.stdout_senders,
+			&self.stdout_timestamped_senders,
 			self.capture_output,
 			stdout_output.as_mut(),
+			self.buffer_size,
 		);
 		let stderr_future = pipe_output(
 			stderr,
 			stderr_writer,
 			&self.stderr_senders,
+			&self.stderr_timestamped_senders,
 			self.capture_output,
 			stderr_output.as_mut(),
+			self.buffer_size,
 		);
 
-		let combined_future = try_join(stdout_future, stderr_future);
-
-		tokio::select! {
-			output = combined_future => {
-				output?;
+		let timeout = self.timeout;
+		let deadline = async move {
+			match timeout {
+				Some(timeout) => tokio::time::sleep(timeout).await,
+				None => std::future::pending().await,
 			}
-			_ = rx => {
+		};
+
+		let killed_reason = {
+			let combined_future = try_join(stdout_future, stderr_future);
+			tokio::pin!(combined_future);
+
+			let killed_reason = tokio::select! {
+				output = &mut combined_future => {
+					output?;
+					None
+				}
+				_ = rx => Some("was terminated by signal".to_string()),
+				_ = deadline => Some(format!("timed out after {:?}", timeout.unwrap_or_default())),
+			};
+
+			if killed_reason.is_some() {
+				process_tree.kill();
 				let _ = child.kill().await;
-				return Err(anyhow::anyhow!("Command {cmd_display} was terminated by signal"));
+				if let Some(sender) = &exit_sender {
+					sender.send_replace(true);
+				}
+				// Drain what the child already wrote, bounded in case a grandchild holds the pipes
+				let _ = tokio::time::timeout(DRAIN_TIMEOUT, &mut combined_future).await;
 			}
-		}
 
-		let status = child.wait().await?;
-		if !status.success() {
+			killed_reason
+		};
+
+		if let Some(reason) = killed_reason {
 			return Err(anyhow::anyhow!(
-				"Command {cmd_display} failed with args {args_display}\nError  {}",
-				stderr_output.unwrap_or_else(|| "Unknown error".to_string())
+				"Command {cmd_display} {reason}\nOutput  {}\nError  {}",
+				stdout_output.unwrap_or_default(),
+				stderr_output.unwrap_or_default()
 			));
 		}
 
-		Ok(stdout_output.unwrap_or_default())
+		let status = child.wait().await?;
+		// The child has been reaped, so its pid may be reused and must not be signalled
+		process_tree.pid = None;
+		if let Some(sender) = &exit_sender {
+			sender.send_replace(true);
+		}
+
+		Ok(RunOutput {
+			cmd_display,
+			args_display,
+			status,
+			stdout: stdout_output,
+			stderr: stderr_output,
+			duration: started.elapsed(),
+		})
 	}
 }
 
+/// The result of a command which ran to completion.
+struct RunOutput {
+	cmd_display: String,
+	args_display: String,
+	status: ExitStatus,
+	stdout: Option<String>,
+	stderr: Option<String>,
+	duration: Duration,
+}
+
+/// The exit code and output of a command which ran to completion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandOutput {
+	/// The exit code, or `None` if the command was terminated by a signal.
+	pub exit_code: Option<i32>,
+	pub stdout: String,
+	pub stderr: String,
+	/// How long the command ran for.
+	pub duration: Duration,
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -329,6 +689,249 @@ mod tests {
 		Ok(())
 	}
 
+	#[tokio::test]
+	async fn test_run_command_with_small_buffer() -> Result<()> {
+		let mut command = Command::line("sh", &["-c", "seq 1 1000"], None, true, vec![], vec![]);
+		let output = command.buffer_size(16).run().await?;
+
+		let expected = (1..=1000).map(|i| format!("{}\n", i)).collect::<String>();
+		assert_eq!(output, expected);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_run_command_with_zero_buffer() -> Result<()> {
+		let mut command = Command::line("sh", &["-c", "seq 1 3"], None, true, vec![], vec![]);
+		let output = command.buffer_size(0).run().await?;
+
+		assert_eq!(output, "1\n2\n3\n");
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_run_command_with_secret_file() -> Result<()> {
+		let mut command = Command::new("sh", true, vec![], vec![]);
+		let placeholder = command.secret_file(b"s3cr3t".to_vec());
+		command
+			.args(["-c", "cat \"$1\" && echo && echo \"$1\"", "sh"])
+			.arg(&placeholder);
+
+		let output = command.run().await?;
+		let mut lines = output.lines();
+
+		assert_eq!(lines.next(), Some("s3cr3t"));
+		let path = lines.next().expect("secret file path");
+		assert_ne!(path, placeholder.as_str());
+
+		// The secret file is removed once the command completes
+		assert!(!Path::new(path).exists());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_run_command_with_secret_file_keeps_settings() -> Result<()> {
+		use std::os::unix::ffi::OsStrExt;
+
+		let temp_dir = tempfile::tempdir()?;
+		let script = "cat \"${1#--key-file=}\" && echo && echo \"$GREETING $HOME\" \
+			&& pwd -P && printf %s \"$2\" | od -An -tx1";
+		let mut command = Command::new("/bin/sh", true, vec![], vec![]);
+		let placeholder = command.secret_file(b"s3cr3t".to_vec());
+		command
+			.env_clear()
+			.env("GREETING", "hello")
+			.current_dir(temp_dir.path())
+			.args(["-c", script, "sh"])
+			.arg(format!("--key-file={}", placeholder))
+			.arg(OsStr::from_bytes(b"\xff\xfe"));
+
+		let output = command.run().await?;
+		let mut lines = output.lines();
+
+		assert_eq!(lines.next(), Some("s3cr3t"));
+		assert_eq!(lines.next(), Some("hello "));
+		assert_eq!(lines.next().map(Path::new), Some(temp_dir.path().canonicalize()?.as_path()));
+		assert_eq!(lines.next().map(str::trim), Some("ff fe"));
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_run_command_with_timestamped_lines() -> Result<()> {
+		let (stdout_tx, mut stdout_rx) = mpsc::channel(10);
+
+		let mut command = Command::line(
+			"sh",
+			&["-c", "echo first && sleep 0.2 && echo second"],
+			None,
+			true,
+			vec![],
+			vec![],
+		);
+		command.append_stdout_timestamped(stdout_tx);
+		let before = Instant::now();
+		command.run().await?;
+
+		let (first_at, first) = stdout_rx.recv().await.expect("first line");
+		let (second_at, second) = stdout_rx.recv().await.expect("second line");
+
+		assert_eq!(first, "first\n");
+		assert_eq!(second, "second\n");
+		assert!(first_at >= before);
+		assert!(second_at.duration_since(first_at) >= std::time::Duration::from_millis(150));
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_pid_sender() -> Result<()> {
+		let (sender, receiver) = tokio::sync::oneshot::channel();
+		let mut command = Command::line("sh", &["-c", "echo $$"], None, true, vec![], vec![]);
+		command.pid_sender(sender);
+
+		let output = command.run().await?;
+		assert_eq!(output.trim(), receiver.await?.to_string());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_exit_sender() -> Result<()> {
+		let (sender, receiver) = tokio::sync::watch::channel(false);
+		let mut command = Command::line("true", Vec::<&str>::new(), None, false, vec![], vec![]);
+		command.exit_sender(sender);
+
+		assert!(!*receiver.borrow());
+		command.run().await?;
+		assert!(*receiver.borrow());
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_run_command_with_env() -> Result<()> {
+		let mut command =
+			Command::line("sh", &["-c", "echo $GREETING-$HOME"], None, true, vec![], vec![]);
+		command.env_clear().envs([("GREETING", "hello")]);
+		assert_eq!(command.run().await?, "hello-\n");
+
+		command.env("HOME", "/nowhere");
+		assert_eq!(command.run().await?, "hello-/nowhere\n");
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_run_command_with_stdin() -> Result<()> {
+		let mut command = Command::line("cat", Vec::<&str>::new(), None, true, vec![], vec![]);
+		command.stdin("from a string\n");
+		assert_eq!(command.run().await?, "from a string\n");
+
+		let temp_dir = tempfile::tempdir()?;
+		let path = temp_dir.path().join("input");
+		std::fs::write(&path, "from a file\n")?;
+		command.stdin(path);
+		assert_eq!(command.run().await?, "from a file\n");
+
+		let (sender, receiver) = mpsc::channel(10);
+		command.stdin(receiver);
+		let run = tokio::spawn(async move { command.run().await });
+		sender.send("first\n".to_string()).await?;
+		sender.send("second\n".to_string()).await?;
+		drop(sender);
+		assert_eq!(run.await??, "first\nsecond\n");
+		Ok(())
+	}
+
+	/// Returns whether the process is alive, treating zombies as dead.
+	fn is_alive(pid: &str) -> bool {
+		let output = std::process::Command::new("ps")
+			.args(["-o", "stat=", "-p", pid])
+			.output()
+			.expect("failed to run ps");
+		let stat = String::from_utf8_lossy(&output.stdout);
+		let stat = stat.trim();
+		!stat.is_empty() && !stat.starts_with('Z')
+	}
+
+	#[tokio::test]
+	async fn test_abort_kills_process_tree() -> Result<()> {
+		let (stdout_tx, mut stdout_rx) = mpsc::channel(10);
+		let mut command =
+			Command::line("sh", &["-c", "sleep 30 & echo $!; wait"], None, false, vec![], vec![]);
+		command.process_group().append_stdout(stdout_tx);
+		let run = tokio::spawn(async move { command.run().await });
+
+		let grandchild = stdout_rx.recv().await.expect("grandchild pid");
+		let grandchild = grandchild.trim();
+		assert!(is_alive(grandchild));
+
+		run.abort();
+		let _ = run.await;
+
+		let mut alive = true;
+		for _ in 0..100 {
+			alive = is_alive(grandchild);
+			if !alive {
+				break;
+			}
+			tokio::time::sleep(Duration::from_millis(10)).await;
+		}
+		assert!(!alive, "grandchild {} is still running after abort", grandchild);
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_run_with_output() -> Result<()> {
+		let mut command = Command::line(
+			"sh",
+			&["-c", "echo out; echo err >&2; exit 3"],
+			None,
+			false,
+			vec![],
+			vec![],
+		);
+		let output = command.run_with_output().await?;
+		assert_eq!(output.exit_code, Some(3));
+		assert_eq!(output.stdout, "out\n");
+		assert_eq!(output.stderr, "err\n");
+		assert!(output.duration > Duration::ZERO);
+
+		assert!(command.run().await.is_err());
+		command.allow_nonzero_exit(true);
+		assert_eq!(command.set_capture_output(true).run().await?, "out\n");
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_run_expect_code() -> Result<()> {
+		let mut command =
+			Command::line("sh", &["-c", "echo bad input >&2; exit 2"], None, true, vec![], vec![]);
+		command.run_expect_code(2).await?;
+
+		let error = command.run_expect_code(0).await.unwrap_err();
+		assert!(error.to_string().contains("expected code 0"));
+		assert!(error.to_string().contains("bad input"));
+
+		let mut command = Command::line("echo", &["ok"], None, true, vec![], vec![]);
+		assert_eq!(command.run_expect_code(0).await?, "ok\n");
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_killed_command_includes_drained_output() -> Result<()> {
+		let mut command = Command::line(
+			"sh",
+			&["-c", "echo useful line && echo diagnostic >&2 && exec sleep 10"],
+			None,
+			true,
+			vec![],
+			vec![],
+		);
+		command.timeout(Duration::from_millis(500));
+
+		let error = command.run().await.unwrap_err().to_string();
+		assert!(error.contains("timed out"), "unexpected error: {}", error);
+		assert!(error.contains("useful line"), "missing stdout in error: {}", error);
+		assert!(error.contains("diagnostic"), "missing stderr in error: {}", error);
+		Ok(())
+	}
+
 	#[tokio::test]
 	async fn test_run_command_with_working_dir() -> Result<(), anyhow::Error> {
 		let temp_dir = tempfile::tempdir()?;
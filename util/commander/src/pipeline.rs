@@ -0,0 +1,188 @@
+use crate::{pipe_output, secret, Command};
+use anyhow::Result;
+use futures::future::{try_join, try_join_all};
+use std::process::Stdio;
+use tokio::io::{self, BufWriter};
+use tracing::info;
+
+/// Runs commands with the standard output of each stage piped into the standard input of the next.
+///
+/// Stages are connected with OS pipes, so a slow stage applies backpressure to the one before it.
+/// Only the final stage's standard output is streamed and captured. Standard error of every stage
+/// is streamed to that stage's senders as usual.
+pub struct Pipeline {
+	stages: Vec<Command>,
+}
+
+impl Pipeline {
+	pub fn new() -> Self {
+		Self { stages: Vec::new() }
+	}
+
+	/// Appends a stage to the end of the pipeline.
+	pub fn stage(&mut self, command: Command) -> &mut Self {
+		self.stages.push(command);
+		self
+	}
+
+	/// Runs all stages and returns the final stage's standard output.
+	///
+	/// All children are reaped before returning. Fails if any stage exits with a nonzero status.
+	pub async fn run(&mut self) -> Result<String> {
+		if self.stages.is_empty() {
+			return Err(anyhow::anyhow!("Pipeline has no stages"));
+		}
+
+		let last = self.stages.len() - 1;
+		let mut secret_files = Vec::new();
+		let mut children = Vec::new();
+		let mut stderrs = Vec::new();
+		let mut previous_stdout: Option<Stdio> = None;
+		let mut final_stdout = None;
+
+		for (i, stage) in self.stages.iter_mut().enumerate() {
+			let (cmd_display, args_display) = stage.display();
+			info!("Running pipeline stage {i}: {cmd_display} {args_display}");
+
+			// Secret files live until the end of the run and are deleted on drop
+			let files = secret::write_secret_files(&stage.secrets)?;
+			let mut inner = stage.build(&files);
+			secret_files.push(files);
+
+			// A background process group reading the terminal would be stopped by SIGTTIN
+			let stdin = match previous_stdout.take() {
+				Some(stdin) => stdin,
+				None if stage.process_group => Stdio::null(),
+				None => Stdio::inherit(),
+			};
+			let spawned = inner.stdin(stdin).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn();
+			// Release our copy of the previous stage's pipe so it sees a broken pipe on early exit
+			drop(inner);
+			let mut child = spawned?;
+
+			let stdout = child.stdout.take().ok_or_else(|| {
+				anyhow::anyhow!("Failed to capture standard output from command {cmd_display}")
+			})?;
+			let stderr = child.stderr.take().ok_or_else(|| {
+				anyhow::anyhow!("Failed to capture standard error from command {cmd_display}")
+			})?;
+
+			if i == last {
+				final_stdout = Some(stdout);
+			} else {
+				previous_stdout = Some(stdout.try_into()?);
+			}
+			children.push(child);
+			stderrs.push(stderr);
+		}
+
+		let final_stage = &self.stages[last];
+		let mut stdout_output = if final_stage.capture_output { Some(String::new()) } else { None };
+		let mut stderr_outputs: Vec<Option<String>> = self
+			.stages
+			.iter()
+			.map(|stage| if stage.capture_output { Some(String::new()) } else { None })
+			.collect();
+
+		let stdout_future = pipe_output(
+			final_stdout.ok_or_else(|| anyhow::anyhow!("Pipeline has no final stage"))?,
+			BufWriter::with_capacity(final_stage.buffer_size, io::stdout()),
+			&final_stage.stdout_senders,
+			&final_stage.stdout_timestamped_senders,
+			final_stage.capture_output,
+			stdout_output.as_mut(),
+			final_stage.buffer_size,
+		);
+		let stderr_futures = self.stages.iter().zip(stderrs).zip(stderr_outputs.iter_mut()).map(
+			|((stage, stderr), output)| {
+				pipe_output(
+					stderr,
+					BufWriter::with_capacity(stage.buffer_size, io::stderr()),
+					&stage.stderr_senders,
+					&stage.stderr_timestamped_senders,
+					stage.capture_output,
+					output.as_mut(),
+					stage.buffer_size,
+				)
+			},
+		);
+
+		try_join(stdout_future, try_join_all(stderr_futures)).await?;
+
+		let mut statuses = Vec::with_capacity(children.len());
+		for child in children.iter_mut() {
+			statuses.push(child.wait().await?);
+		}
+
+		for (i, ((stage, status), stderr)) in
+			self.stages.iter().zip(statuses).zip(stderr_outputs).enumerate()
+		{
+			if !status.success() {
+				let (cmd_display, args_display) = stage.display();
+				return Err(anyhow::anyhow!(
+					"Pipeline stage {i} {cmd_display} failed with args {args_display} and {status}\nError  {}",
+					stderr.unwrap_or_else(|| "Unknown error".to_string())
+				));
+			}
+		}
+
+		Ok(stdout_output.unwrap_or_default())
+	}
+}
+
+impl Default for Pipeline {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn sh(script: &str) -> Command {
+		let mut command = Command::new("sh", true, vec![], vec![]);
+		command.args(["-c", script]);
+		command
+	}
+
+	#[tokio::test]
+	async fn test_pipeline_chains_stages() -> Result<()> {
+		let output = Pipeline::new()
+			.stage(sh("printf 'banana\\napple\\n'"))
+			.stage(Command::new("sort", false, vec![], vec![]))
+			.stage(sh("tr a-z A-Z"))
+			.run()
+			.await?;
+
+		assert_eq!(output, "APPLE\nBANANA\n");
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_pipeline_handles_output_larger_than_pipe_buffer() -> Result<()> {
+		let output = Pipeline::new()
+			.stage(sh("seq 1 200000"))
+			.stage(sh("wc -l | tr -d ' '"))
+			.run()
+			.await?;
+
+		assert_eq!(output, "200000\n");
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_pipeline_fails_if_any_stage_fails() -> Result<()> {
+		let result = Pipeline::new()
+			.stage(sh("echo hello"))
+			.stage(sh("cat; echo broken >&2; exit 3"))
+			.stage(sh("cat"))
+			.run()
+			.await;
+
+		let error = result.unwrap_err().to_string();
+		assert!(error.contains("stage 1"), "{error}");
+		assert!(error.contains("broken"), "{error}");
+		Ok(())
+	}
+}
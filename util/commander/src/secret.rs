@@ -0,0 +1,89 @@
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::io::Write;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tempfile::NamedTempFile;
+
+static NEXT_PLACEHOLDER: AtomicU64 = AtomicU64::new(0);
+
+/// A token standing in for the path of a secret file in a command's arguments.
+///
+/// The token may be passed as a whole argument or embedded in one (e.g. `--key-file={token}`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Placeholder(String);
+
+impl Placeholder {
+	pub(crate) fn new() -> Self {
+		let id = NEXT_PLACEHOLDER.fetch_add(1, Ordering::Relaxed);
+		Self(format!("__COMMANDER_SECRET_{}_{}__", std::process::id(), id))
+	}
+
+	/// Returns the token as a string.
+	pub fn as_str(&self) -> &str {
+		&self.0
+	}
+}
+
+impl fmt::Display for Placeholder {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.0)
+	}
+}
+
+impl AsRef<OsStr> for Placeholder {
+	fn as_ref(&self) -> &OsStr {
+		OsStr::new(&self.0)
+	}
+}
+
+/// Secret contents to be written to a file before the command is spawned.
+pub(crate) struct Secret {
+	pub(crate) placeholder: Placeholder,
+	pub(crate) contents: Vec<u8>,
+}
+
+/// Writes each secret to its own temp file.
+///
+/// Temp files are created with 0600 permissions and deleted when dropped.
+pub(crate) fn write_secret_files(
+	secrets: &[Secret],
+) -> Result<Vec<(Placeholder, NamedTempFile)>, std::io::Error> {
+	secrets
+		.iter()
+		.map(|secret| {
+			let mut file = NamedTempFile::new()?;
+			file.write_all(&secret.contents)?;
+			file.flush()?;
+			Ok((secret.placeholder.clone(), file))
+		})
+		.collect()
+}
+
+/// Replaces each placeholder in the argument with the path of its file.
+///
+/// Arguments are compared as raw bytes, so those which are not valid UTF-8 are passed on intact.
+pub(crate) fn substitute_secrets(arg: &OsStr, files: &[(Placeholder, NamedTempFile)]) -> OsString {
+	let mut arg = arg.as_bytes().to_vec();
+	for (placeholder, file) in files {
+		arg = replace_bytes(
+			&arg,
+			placeholder.as_str().as_bytes(),
+			file.path().as_os_str().as_bytes(),
+		);
+	}
+	OsString::from_vec(arg)
+}
+
+/// Replaces every occurrence of a non-empty pattern in the bytes.
+fn replace_bytes(bytes: &[u8], pattern: &[u8], replacement: &[u8]) -> Vec<u8> {
+	let mut replaced = Vec::with_capacity(bytes.len());
+	let mut rest = bytes;
+	while let Some(index) = rest.windows(pattern.len()).position(|window| window == pattern) {
+		replaced.extend_from_slice(&rest[..index]);
+		replaced.extend_from_slice(replacement);
+		rest = &rest[index + pattern.len()..];
+	}
+	replaced.extend_from_slice(rest);
+	replaced
+}
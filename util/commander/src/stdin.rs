@@ -0,0 +1,68 @@
+use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
+use tokio::process::ChildStdin;
+use tokio::sync::mpsc::Receiver;
+
+/// Where the standard input of a command is read from.
+pub enum Source {
+	/// Writes the string and then closes standard input.
+	Text(String),
+	/// Copies the contents of the file and then closes standard input.
+	File(PathBuf),
+	/// Writes each received string as is, closing standard input once the sender is dropped.
+	///
+	/// A receiver can only be consumed once, so only the next run of the command is fed.
+	Channel(Receiver<String>),
+}
+
+impl From<String> for Source {
+	fn from(text: String) -> Self {
+		Source::Text(text)
+	}
+}
+
+impl From<&str> for Source {
+	fn from(text: &str) -> Self {
+		Source::Text(text.to_string())
+	}
+}
+
+impl From<PathBuf> for Source {
+	fn from(path: PathBuf) -> Self {
+		Source::File(path)
+	}
+}
+
+impl From<Receiver<String>> for Source {
+	fn from(receiver: Receiver<String>) -> Self {
+		Source::Channel(receiver)
+	}
+}
+
+/// Returns the source to feed to the next run, leaving reusable sources in place.
+pub(crate) fn take_for_run(slot: &mut Option<Source>) -> Option<Source> {
+	match slot {
+		Some(Source::Text(text)) => Some(Source::Text(text.clone())),
+		Some(Source::File(path)) => Some(Source::File(path.clone())),
+		Some(Source::Channel(_)) => slot.take(),
+		None => None,
+	}
+}
+
+/// Writes the source to the standard input of the child, closing it once done.
+pub(crate) async fn feed(source: Source, mut stdin: ChildStdin) -> Result<(), std::io::Error> {
+	match source {
+		Source::Text(text) => stdin.write_all(text.as_bytes()).await?,
+		Source::File(path) => {
+			let mut file = tokio::fs::File::open(path).await?;
+			tokio::io::copy(&mut file, &mut stdin).await?;
+		}
+		Source::Channel(mut receiver) => {
+			while let Some(text) = receiver.recv().await {
+				stdin.write_all(text.as_bytes()).await?;
+				stdin.flush().await?;
+			}
+		}
+	}
+	stdin.shutdown().await
+}
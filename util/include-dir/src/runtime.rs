@@ -1,8 +1,10 @@
 use std::ffi::OsStr;
 use std::fs::File;
+use std::future::Future;
 use std::io::Cursor;
 use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
+use std::pin::Pin;
 use tempfile::TempDir;
 use zip::read::ZipArchive;
 
@@ -40,6 +42,37 @@ impl Workspace {
 		Ok(Workspace { contracts_zip, workspace_path: WorkspacePath::TempDir(temp_dir) })
 	}
 
+	/// Creates a new temporary contract workspace under the given base directory.
+	///
+	/// Use this instead of [Workspace::try_temp] when the system temp directory is too small or
+	/// lives on a different filesystem than the files the workspace will interact with. Hardlinks
+	/// and renames fail across filesystem boundaries, so keeping the workspace on the same device
+	/// avoids cross-device errors.
+	pub fn try_temp_in(contracts_zip: &'static [u8], base: &Path) -> Result<Self, std::io::Error> {
+		let temp_dir = TempDir::new_in(base)?;
+		Ok(Workspace { contracts_zip, workspace_path: WorkspacePath::TempDir(temp_dir) })
+	}
+
+	/// Runs a closure with a prepared temporary workspace, cleaning it up afterward.
+	///
+	/// The workspace is removed when the closure's future completes, errors, or panics, as the
+	/// underlying [TempDir] is dropped in every case.
+	///
+	/// ```ignore
+	/// Workspace::with_temp(ZIP, |workspace| Box::pin(async move {
+	///     workspace.run_command("ls", ["-la"]).await
+	/// }))
+	/// .await??;
+	/// ```
+	pub async fn with_temp<F, T>(contracts_zip: &'static [u8], f: F) -> Result<T, std::io::Error>
+	where
+		F: for<'a> FnOnce(&'a Workspace) -> Pin<Box<dyn Future<Output = T> + Send + 'a>> + Send,
+	{
+		let workspace = Workspace::try_temp(contracts_zip)?;
+		workspace.prepare_directory()?;
+		Ok(f(&workspace).await)
+	}
+
 	/// Generates a new workspaces in .debug/{uid}
 	pub fn try_debug(contracts_zip: &'static [u8]) -> Result<Self, std::io::Error> {
 		let uid = uuid::Uuid::new_v4();
@@ -96,6 +129,20 @@ impl Workspace {
 		Ok(())
 	}
 
+	/// Removes the workspace directory (if a [WorkspacePath::PathBuf]) before unzipping.
+	///
+	/// This guarantees a clean state when reusing a named debug directory. For
+	/// [WorkspacePath::TempDir] this behaves like [Workspace::prepare_directory].
+	pub fn prepare_fresh(&self) -> Result<(), std::io::Error> {
+		if let WorkspacePath::PathBuf(path) = &self.workspace_path {
+			if path.exists() {
+				std::fs::remove_dir_all(path)?;
+			}
+		}
+
+		self.prepare_directory()
+	}
+
 	/// Constructs a command to run in the workspace
 	pub fn command<C, I, S>(&self, command: C, args: I) -> commander::Command
 	where
@@ -170,6 +217,27 @@ macro_rules! workspace {
 				Ok(Self::new(workspace_path))
 			}
 
+			/// Creates a new temporary workspace under the given base directory
+			pub fn try_temp_in(base: &std::path::Path) -> Result<Self, std::io::Error> {
+				let temp_dir = include_dir::TempDir::new_in(base)?;
+				let workspace_path = include_dir::WorkspacePath::TempDir(temp_dir);
+				Ok(Self::new(workspace_path))
+			}
+
+			/// Runs a closure with a prepared temporary workspace, cleaning it up afterward
+			pub async fn with_temp<F, T>(f: F) -> Result<T, std::io::Error>
+			where
+				F: for<'a> FnOnce(
+						&'a Self,
+					) -> std::pin::Pin<
+						Box<dyn std::future::Future<Output = T> + Send + 'a>,
+					> + Send,
+			{
+				let workspace = Self::try_temp()?;
+				workspace.prepare_directory()?;
+				Ok(f(&workspace).await)
+			}
+
 			/// Generates a new workspaces in .debug/{uid}
 			pub fn try_debug() -> Result<Self, std::io::Error> {
 				let uuid = include_dir::uuid::Uuid::new_v4();
@@ -198,6 +266,11 @@ macro_rules! workspace {
 				self.workspace.prepare_directory()
 			}
 
+			/// Removes the workspace directory before unzipping the contracts zip file.
+			pub fn prepare_fresh(&self) -> Result<(), std::io::Error> {
+				self.workspace.prepare_fresh()
+			}
+
 			/// Constructs a command to run in the workspace
 			pub fn command<C, I, S>(&self, command: C, args: I) -> include_dir::commander::Command
 			where
@@ -248,3 +321,58 @@ macro_rules! workspace {
 		}
 	};
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::io::Write;
+	use zip::{write::SimpleFileOptions, ZipWriter};
+
+	/// Builds a zip with a single file and leaks it to satisfy the `'static` bound.
+	fn test_zip() -> Result<&'static [u8], anyhow::Error> {
+		let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+		zip.start_file("hello.txt", SimpleFileOptions::default())?;
+		zip.write_all(b"hello")?;
+		let bytes = zip.finish()?.into_inner();
+		Ok(Box::leak(bytes.into_boxed_slice()))
+	}
+
+	#[test]
+	fn test_prepare_fresh_removes_stray_files() -> Result<(), anyhow::Error> {
+		let temp_dir = TempDir::new()?;
+		let path = temp_dir.path().join("workspace");
+		let workspace = Workspace::new(test_zip()?, WorkspacePath::PathBuf(path.clone()));
+
+		workspace.prepare_directory()?;
+		let stray = path.join("stray.txt");
+		std::fs::write(&stray, "stray")?;
+
+		// prepare_directory leaves the stray file in place
+		workspace.prepare_directory()?;
+		assert!(stray.exists());
+
+		workspace.prepare_fresh()?;
+		assert!(!stray.exists());
+		assert_eq!(std::fs::read_to_string(path.join("hello.txt"))?, "hello");
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_with_temp_cleans_up() -> Result<(), anyhow::Error> {
+		// Spawned, so the future must be Send
+		let (path, contents) = tokio::spawn(Workspace::with_temp(test_zip()?, |workspace| {
+			Box::pin(async move {
+				let path = workspace.get_workspace_path().to_path_buf();
+				let contents = std::fs::read_to_string(path.join("hello.txt"));
+				(path, contents)
+			})
+		}))
+		.await??;
+
+		assert_eq!(contents?, "hello");
+		assert!(!path.exists());
+
+		Ok(())
+	}
+}
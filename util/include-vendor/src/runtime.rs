@@ -14,6 +14,30 @@ impl Workspace {
 		Ok(Self { workspace: IncludeDirWorkspace::try_temp(contracts_zip)? })
 	}
 
+	/// Creates a new temporary workspace under the given base directory.
+	///
+	/// See [IncludeDirWorkspace::try_temp_in] for when to prefer this over `try_temp`.
+	pub fn try_temp_in(
+		contracts_zip: &'static [u8],
+		base: &std::path::Path,
+	) -> Result<Self, std::io::Error> {
+		Ok(Self { workspace: IncludeDirWorkspace::try_temp_in(contracts_zip, base)? })
+	}
+
+	/// Runs a closure with a prepared temporary workspace, cleaning it up afterward.
+	pub async fn with_temp<F, T>(contracts_zip: &'static [u8], f: F) -> Result<T, std::io::Error>
+	where
+		F: for<'a> FnOnce(
+				&'a Workspace,
+			)
+				-> std::pin::Pin<Box<dyn std::future::Future<Output = T> + Send + 'a>>
+			+ Send,
+	{
+		let workspace = Workspace::try_temp(contracts_zip)?;
+		workspace.prepare_directory()?;
+		Ok(f(&workspace).await)
+	}
+
 	pub fn try_debug(contracts_zip: &'static [u8]) -> Result<Self, std::io::Error> {
 		Ok(Self { workspace: IncludeDirWorkspace::try_debug(contracts_zip)? })
 	}
@@ -30,6 +54,11 @@ impl Workspace {
 		self.workspace.prepare_directory()
 	}
 
+	/// Removes the workspace directory before unzipping the contracts zip file.
+	pub fn prepare_fresh(&self) -> Result<(), std::io::Error> {
+		self.workspace.prepare_fresh()
+	}
+
 	/// Constructs a command to run in the workspace
 	pub fn command<C, I, S>(&self, command: C, args: I) -> commander::Command
 	where
@@ -99,6 +128,27 @@ macro_rules! vendor_workspace {
 				Ok(Self::new(workspace_path))
 			}
 
+			/// Creates a new workspace with a temporary directory under the given base directory.
+			pub fn try_temp_in(base: &std::path::Path) -> Result<Self, std::io::Error> {
+				let temp_dir = include_vendor::TempDir::new_in(base)?;
+				let workspace_path = include_vendor::WorkspacePath::TempDir(temp_dir);
+				Ok(Self::new(workspace_path))
+			}
+
+			/// Runs a closure with a prepared temporary workspace, cleaning it up afterward.
+			pub async fn with_temp<F, T>(f: F) -> Result<T, std::io::Error>
+			where
+				F: for<'a> FnOnce(
+						&'a Self,
+					) -> std::pin::Pin<
+						Box<dyn std::future::Future<Output = T> + Send + 'a>,
+					> + Send,
+			{
+				let workspace = Self::try_temp()?;
+				workspace.prepare_directory()?;
+				Ok(f(&workspace).await)
+			}
+
 			/// Generates a new workspaces in .debug/{uid}
 			pub fn try_debug() -> Result<Self, std::io::Error> {
 				let uuid = include_vendor::uuid::Uuid::new_v4();
@@ -128,6 +178,11 @@ macro_rules! vendor_workspace {
 				self.workspace.prepare_directory()
 			}
 
+			/// Removes the workspace directory before preparing it.
+			pub fn prepare_fresh(&self) -> Result<(), std::io::Error> {
+				self.workspace.prepare_fresh()
+			}
+
 			/// Constructs a command to run in the workspace
 			pub fn command<C, I, S>(
 				&self,
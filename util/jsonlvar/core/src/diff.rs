@@ -0,0 +1,105 @@
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// A structured diff between two parsed JSONL maps.
+///
+/// Keys are kept sorted so that the diff and its display are deterministic.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JsonlDiff {
+	/// Keys only present in the new map, with their values.
+	pub added: BTreeMap<String, Value>,
+	/// Keys only present in the old map, with their values.
+	pub removed: BTreeMap<String, Value>,
+	/// Keys present in both maps whose values differ, as (old, new).
+	pub changed: BTreeMap<String, (Value, Value)>,
+}
+
+impl JsonlDiff {
+	/// Returns true if the two maps were equal.
+	pub fn is_empty(&self) -> bool {
+		self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+	}
+}
+
+impl fmt::Display for JsonlDiff {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for (key, value) in &self.added {
+			writeln!(f, "+ {} = {}", key, value)?;
+		}
+		for (key, value) in &self.removed {
+			writeln!(f, "- {} = {}", key, value)?;
+		}
+		for (key, (old, new)) in &self.changed {
+			writeln!(f, "~ {} = {} -> {}", key, old, new)?;
+		}
+		Ok(())
+	}
+}
+
+/// Diffs two parsed JSONL maps, reporting added, removed, and changed keys.
+pub fn diff(old: &HashMap<String, Value>, new: &HashMap<String, Value>) -> JsonlDiff {
+	let mut diff = JsonlDiff::default();
+
+	for (key, old_value) in old {
+		match new.get(key) {
+			Some(new_value) if new_value != old_value => {
+				diff.changed.insert(key.clone(), (old_value.clone(), new_value.clone()));
+			}
+			Some(_) => {}
+			None => {
+				diff.removed.insert(key.clone(), old_value.clone());
+			}
+		}
+	}
+
+	for (key, new_value) in new {
+		if !old.contains_key(key) {
+			diff.added.insert(key.clone(), new_value.clone());
+		}
+	}
+
+	diff
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::JsonlParser;
+
+	#[test]
+	fn test_diff() {
+		let parser = JsonlParser::new();
+		let old = parser.parse(
+			r#"
+        JSONL same = 1
+        JSONL changed = "old"
+        JSONL removed = true
+        "#,
+		);
+		let new = parser.parse(
+			r#"
+        JSONL same = 1
+        JSONL changed = "new"
+        JSONL added = [1, 2]
+        "#,
+		);
+
+		let result = diff(&old, &new);
+
+		assert!(!result.is_empty());
+		assert_eq!(result.added.get("added"), Some(&serde_json::json!([1, 2])));
+		assert_eq!(result.removed.get("removed"), Some(&serde_json::json!(true)));
+		assert_eq!(
+			result.changed.get("changed"),
+			Some(&(serde_json::json!("old"), serde_json::json!("new")))
+		);
+		assert!(!result.changed.contains_key("same"));
+		assert_eq!(
+			result.to_string(),
+			"+ added = [1,2]\n- removed = true\n~ changed = \"old\" -> \"new\"\n"
+		);
+
+		assert!(diff(&old, &old).is_empty());
+	}
+}
@@ -1,7 +1,14 @@
+pub mod diff;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+
+pub use diff::*;
 use regex::Regex;
 use serde::Serialize;
 use serde_json::Value;
 use std::collections::HashMap;
+#[cfg(feature = "test-util")]
+pub use test_util::*;
 use thiserror::Error;
 
 pub struct JsonlParser {
@@ -14,32 +21,44 @@ impl JsonlParser {
 	}
 
 	pub fn parse(&self, input: &str) -> HashMap<String, Value> {
-		let mut map = HashMap::new();
-		let re = Regex::new(r"JSONL\s+(\w+)\s*=\s*(.+)$").unwrap();
+		self.parse_ordered(input).into_iter().collect()
+	}
+
+	/// Parses the input, preserving the order in which variables appear
+	///
+	/// Unlike [JsonlParser::parse], repeated variables are kept as separate entries.
+	pub fn parse_ordered(&self, input: &str) -> Vec<(String, Value)> {
+		let mut entries = Vec::new();
+		let re = Regex::new(r"JSONL\s+([\w.:]+)\s*=\s*(.+)$").unwrap();
 
 		for line in input.lines() {
 			if let Some(caps) = re.captures(line) {
 				let var_name = caps.get(1).unwrap().as_str().to_string();
 				let value_str = caps.get(2).unwrap().as_str().trim();
 
-				// Try parsing as JSON first
-				let parsed_value = match serde_json::from_str::<Value>(value_str) {
-					Ok(json_value) => json_value,
-					Err(_) => {
-						// If JSON parsing fails, assume it's a raw string or number
-						if let Ok(number) = value_str.parse::<f64>() {
-							Value::from(number) // Store numbers as JSON numbers
-						} else {
-							Value::from(value_str.to_string()) // Store strings as JSON strings
-						}
-					}
-				};
-
-				map.insert(var_name, parsed_value);
+				let parsed_value = Self::parse_value(value_str);
+
+				entries.push((var_name, parsed_value));
 			}
 		}
 
-		map
+		entries
+	}
+
+	/// Parses a single raw value as JSON, falling back to a number or a string
+	pub fn parse_value(value_str: &str) -> Value {
+		// Try parsing as JSON first
+		match serde_json::from_str::<Value>(value_str) {
+			Ok(json_value) => json_value,
+			Err(_) => {
+				// If JSON parsing fails, assume it's a raw string or number
+				if let Ok(number) = value_str.parse::<f64>() {
+					Value::from(number) // Store numbers as JSON numbers
+				} else {
+					Value::from(value_str.to_string()) // Store strings as JSON strings
+				}
+			}
+		}
 	}
 }
 
@@ -74,6 +93,33 @@ mod tests {
 			&serde_json::json!("{invalid json gets parsed as string}")
 		);
 	}
+
+	#[test]
+	fn test_jsonl_parser_ordered() {
+		let input = r#"
+        JSONL zebra = 1
+        Random log entry
+        JSONL apple = "two"
+        JSONL mango = [3]
+        JSONL apple = "four"
+        "#;
+
+		let parser = JsonlParser::new();
+		let result = parser.parse_ordered(input);
+
+		assert_eq!(
+			result,
+			vec![
+				("zebra".to_string(), serde_json::json!(1)),
+				("apple".to_string(), serde_json::json!("two")),
+				("mango".to_string(), serde_json::json!([3])),
+				("apple".to_string(), serde_json::json!("four")),
+			]
+		);
+
+		// The map keeps the last value for repeated variables
+		assert_eq!(parser.parse(input).get("apple").unwrap(), &serde_json::json!("four"));
+	}
 }
 
 #[derive(Debug, Error)]
@@ -83,6 +129,9 @@ pub enum JsonlError {
 
 	#[error("Missing or invalid field: {0}")]
 	MissingField(String),
+
+	#[error("Missing environment variables: {0:?}")]
+	MissingEnvVars(Vec<String>),
 }
 
 pub trait Jsonl: Sized + Serialize {
@@ -92,6 +141,48 @@ pub trait Jsonl: Sized + Serialize {
 		var_prefix: Option<&str>,
 	) -> Result<Self, JsonlError>;
 
+	/// Overwrites only the fields whose keys are present in a parsed JSONL map
+	///
+	/// Fields without a matching key are left untouched. If any present field fails to
+	/// deserialize, the struct is not modified.
+	fn try_update_from_jsonl_map(
+		&mut self,
+		parsed_data: &HashMap<String, Value>,
+		var_prefix: Option<&str>,
+	) -> Result<(), JsonlError>;
+
+	/// Returns the names of the fields read from JSONL
+	fn jsonl_fields() -> Vec<&'static str>;
+
+	/// Reads the struct from environment variables named `{PREFIX}_{FIELD}` (uppercased)
+	///
+	/// With a prefix, the unprefixed `{FIELD}` variable is never read, so that fields like `path`
+	/// do not pick up unrelated system variables. Values are parsed the same way as JSONL values.
+	/// All missing variables are reported at once.
+	fn try_from_env(var_prefix: Option<&str>) -> Result<Self, JsonlError> {
+		let mut parsed_data = HashMap::new();
+		let mut missing = Vec::new();
+
+		for field in Self::jsonl_fields() {
+			let var_name = match var_prefix {
+				Some(prefix) => format!("{}_{}", prefix, field).to_uppercase(),
+				None => field.to_uppercase(),
+			};
+			match std::env::var(&var_name) {
+				Ok(value) => {
+					parsed_data.insert(field.to_string(), JsonlParser::parse_value(value.trim()));
+				}
+				Err(_) => missing.push(var_name),
+			}
+		}
+
+		if !missing.is_empty() {
+			return Err(JsonlError::MissingEnvVars(missing));
+		}
+
+		Self::try_from_jsonl_map(&parsed_data, None)
+	}
+
 	/// Parses a JSONL string into a struct
 	fn try_from_jsonl(jsonl: &str, var_prefix: Option<&str>) -> Result<Self, JsonlError> {
 		let parser = JsonlParser::new();
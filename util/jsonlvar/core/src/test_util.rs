@@ -0,0 +1,32 @@
+use crate::{diff, Jsonl, JsonlParser};
+use std::fmt::Debug;
+
+/// Asserts that a value survives a flat JSONL round-trip.
+///
+/// Serializes the value via [Jsonl::try_to_jsonl_flat], parses it back via
+/// [Jsonl::try_from_jsonl], and panics with a diff of the emitted variables on mismatch.
+pub fn assert_roundtrip<T>(value: &T, var_prefix: Option<&str>)
+where
+	T: Jsonl + PartialEq + Debug,
+{
+	let jsonl = value
+		.try_to_jsonl_flat(var_prefix.map(String::from))
+		.unwrap_or_else(|e| panic!("failed to serialize {:?} to JSONL: {}", value, e));
+
+	let parsed = T::try_from_jsonl(&jsonl, var_prefix)
+		.unwrap_or_else(|e| panic!("failed to parse JSONL back:\n{}\nerror: {}", jsonl, e));
+
+	if &parsed != value {
+		let reserialized = parsed
+			.try_to_jsonl_flat(var_prefix.map(String::from))
+			.unwrap_or_else(|e| panic!("failed to serialize {:?} to JSONL: {}", parsed, e));
+
+		let parser = JsonlParser::new();
+		let diff = diff(&parser.parse(&jsonl), &parser.parse(&reserialized));
+
+		panic!(
+			"JSONL round-trip mismatch\nexpected: {:?}\n  actual: {:?}\ndiff:\n{}",
+			value, parsed, diff
+		);
+	}
+}
@@ -1,12 +1,44 @@
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{parse_macro_input, Data, DeriveInput};
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Data, DeriveInput, LitStr};
 
-#[proc_macro_derive(Jsonl)]
+/// Parses the `#[jsonl(separator = "...")]` container attribute, defaulting to `_`.
+///
+/// The separator becomes part of the variable names, so it is limited to the characters the
+/// parser accepts in names: word characters, `.` and `:`.
+fn parse_separator(input: &DeriveInput) -> syn::Result<String> {
+	let mut separator = "_".to_string();
+	for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("jsonl")) {
+		attr.parse_nested_meta(|meta| {
+			if meta.path.is_ident("separator") {
+				let value: LitStr = meta.value()?.parse()?;
+				separator = value.value();
+				let valid = |c: char| c.is_alphanumeric() || matches!(c, '_' | '.' | ':');
+				if separator.is_empty() || !separator.chars().all(valid) {
+					return Err(syn::Error::new(
+						value.span(),
+						"jsonl separator must be made of word characters, `.` or `:`",
+					));
+				}
+				Ok(())
+			} else {
+				Err(meta.error("unsupported jsonl attribute"))
+			}
+		})?;
+	}
+	Ok(separator)
+}
+
+#[proc_macro_derive(Jsonl, attributes(jsonl))]
 pub fn derive_jsonl(input: TokenStream) -> TokenStream {
 	let input = parse_macro_input!(input as DeriveInput);
 	let struct_name = &input.ident;
 
+	let separator = match parse_separator(&input) {
+		Ok(separator) => separator,
+		Err(e) => return TokenStream::from(e.to_compile_error()),
+	};
+
 	let fields = match &input.data {
 		Data::Struct(s) => &s.fields,
 		_ => panic!("Jsonl can only be derived for structs"),
@@ -19,7 +51,7 @@ pub fn derive_jsonl(input: TokenStream) -> TokenStream {
 
 		quote! {
             #field_name: {
-                let prefixed_key = var_prefix.map(|p| format!("{}_{}", p, #field_str)).unwrap_or_else(|| #field_str.to_string());
+                let prefixed_key = var_prefix.map(|p| format!("{}{}{}", p, #separator, #field_str)).unwrap_or_else(|| #field_str.to_string());
                 let value = parsed_data.get(&prefixed_key)
                     .or_else(|| parsed_data.get(#field_str)) // fallback to unprefixed key
                     .ok_or_else(|| jsonlvar::JsonlError::MissingField(prefixed_key.clone()))?
@@ -30,6 +62,35 @@ pub fn derive_jsonl(input: TokenStream) -> TokenStream {
         }
 	});
 
+	// Extract fields for partially updating an existing struct from JSONL
+	let field_updates = fields.iter().map(|field| {
+		let field_name = field.ident.as_ref().unwrap();
+		let field_ty = &field.ty;
+		let field_str = field_name.to_string();
+		let update_name = format_ident!("__jsonl_update_{}", field_name);
+
+		quote! {
+			let #update_name: Option<#field_ty> = {
+				let prefixed_key = var_prefix.map(|p| format!("{}{}{}", p, #separator, #field_str)).unwrap_or_else(|| #field_str.to_string());
+				match parsed_data.get(&prefixed_key).or_else(|| parsed_data.get(#field_str)) {
+					Some(value) => Some(jsonlvar::serde_json::from_value(value.clone()).map_err(jsonlvar::JsonlError::Json)?),
+					None => None,
+				}
+			};
+		}
+	});
+
+	let field_assignments = fields.iter().map(|field| {
+		let field_name = field.ident.as_ref().unwrap();
+		let update_name = format_ident!("__jsonl_update_{}", field_name);
+
+		quote! {
+			if let Some(value) = #update_name {
+				self.#field_name = value;
+			}
+		}
+	});
+
 	// Generate JSONL field serialization (flat)
 	let field_serializations = fields.iter().map(|field| {
 		let field_name = field.ident.as_ref().unwrap();
@@ -38,13 +99,15 @@ pub fn derive_jsonl(input: TokenStream) -> TokenStream {
 		quote! {
 			let field_value = jsonlvar::serde_json::to_string(&self.#field_name)?;
 			let prefixed_name = match &var_prefix {
-				Some(prefix) => format!("{}_{}", prefix, #field_str),
+				Some(prefix) => format!("{}{}{}", prefix, #separator, #field_str),
 				None => #field_str.to_string(),
 			};
 			jsonl_entries.push(format!("JSONL {} = {}", prefixed_name, field_value));
 		}
 	});
 
+	let field_names = fields.iter().map(|field| field.ident.as_ref().unwrap().to_string());
+
 	let expanded = quote! {
 		impl Jsonl for #struct_name {
 			fn try_from_jsonl_map(parsed_data: &std::collections::HashMap<String, jsonlvar::serde_json::Value>, var_prefix: Option<&str>)
@@ -54,6 +117,17 @@ pub fn derive_jsonl(input: TokenStream) -> TokenStream {
 				})
 			}
 
+			fn jsonl_fields() -> Vec<&'static str> {
+				vec![#(#field_names),*]
+			}
+
+			fn try_update_from_jsonl_map(&mut self, parsed_data: &std::collections::HashMap<String, jsonlvar::serde_json::Value>, var_prefix: Option<&str>)
+				-> Result<(), jsonlvar::JsonlError> {
+				#(#field_updates)*
+				#(#field_assignments)*
+				Ok(())
+			}
+
 			fn try_to_jsonl_flat_vec(&self, var_prefix: Option<String>) -> Result<Vec<String>, jsonlvar::JsonlError> {
 				let mut jsonl_entries = Vec::new();
 				#(#field_serializations)*
@@ -64,3 +138,30 @@ pub fn derive_jsonl(input: TokenStream) -> TokenStream {
 
 	TokenStream::from(expanded)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_separator() {
+		let dotted: DeriveInput = syn::parse_quote! {
+			#[jsonl(separator = "::")]
+			struct Dotted { key: String }
+		};
+		assert_eq!(parse_separator(&dotted).unwrap(), "::");
+
+		let plain: DeriveInput = syn::parse_quote! {
+			struct Plain { key: String }
+		};
+		assert_eq!(parse_separator(&plain).unwrap(), "_");
+
+		for separator in ["-", "/", ""] {
+			let invalid: DeriveInput = syn::parse_quote! {
+				#[jsonl(separator = #separator)]
+				struct Invalid { key: String }
+			};
+			assert!(parse_separator(&invalid).is_err(), "accepted {:?}", separator);
+		}
+	}
+}
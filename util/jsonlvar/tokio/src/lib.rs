@@ -58,6 +58,21 @@ impl JsonlFiller {
 			Err(e) => Err(JsonlFillerError::FillError(Box::new(e))),
 		}
 	}
+
+	/// Updates the fields of an existing value of type T that are present in the line map
+	pub async fn try_update<T>(
+		&mut self,
+		value: &mut T,
+		var_prefix: Option<&str>,
+	) -> Result<(), JsonlFillerError>
+	where
+		T: Jsonl,
+	{
+		self.update().await;
+		value
+			.try_update_from_jsonl_map(self.line_map(), var_prefix)
+			.map_err(|e| JsonlFillerError::FillError(Box::new(e)))
+	}
 }
 
 #[cfg(test)]
@@ -78,6 +93,50 @@ mod tests {
 		inner: TestStructInner,
 	}
 
+	#[derive(Debug, Serialize, Deserialize, PartialEq, Jsonl)]
+	#[jsonl(separator = ".")]
+	struct TestStructDotted {
+		key_name: String,
+		number: i32,
+	}
+
+	#[derive(Debug, Serialize, Deserialize, PartialEq, Jsonl)]
+	#[jsonl(separator = "::")]
+	struct TestStructPathed {
+		key_name: String,
+		number: i32,
+	}
+
+	#[test]
+	fn test_jsonl_separator() -> Result<(), anyhow::Error> {
+		let value = TestStructDotted { key_name: "value".to_string(), number: 42 };
+
+		let jsonl = value.try_to_jsonl_flat(Some("service".to_string()))?;
+		assert_eq!(jsonl, "JSONL service.key_name = \"value\"\nJSONL service.number = 42");
+
+		let parsed = TestStructDotted::try_from_jsonl(&jsonl, Some("service"))?;
+		assert_eq!(parsed, value);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_jsonl_roundtrip() {
+		let value = TestStruct {
+			key: "value".to_string(),
+			number: 42,
+			inner: TestStructInner { key: "inner".to_string(), number: 7 },
+		};
+		jsonlvar::assert_roundtrip(&value, None);
+		jsonlvar::assert_roundtrip(&value, Some("prefix"));
+
+		let dotted = TestStructDotted { key_name: "value".to_string(), number: 42 };
+		jsonlvar::assert_roundtrip(&dotted, Some("service"));
+
+		let pathed = TestStructPathed { key_name: "value".to_string(), number: 42 };
+		jsonlvar::assert_roundtrip(&pathed, Some("service"));
+	}
+
 	#[tokio::test]
 	async fn test_jsonl_filler() -> Result<(), anyhow::Error> {
 		let mut filler = JsonlFiller::new();
@@ -106,4 +165,45 @@ mod tests {
 		);
 		Ok(())
 	}
+
+	#[tokio::test]
+	async fn test_jsonl_filler_update() -> Result<(), anyhow::Error> {
+		let mut filler = JsonlFiller::new();
+		let sender = filler.clone_sender();
+
+		let mut value = TestStructInner { key: "old".to_string(), number: 1 };
+
+		let _ = sender.send("JSONL number = 42".to_string()).await;
+		filler.try_update(&mut value, None).await?;
+		assert_eq!(value, TestStructInner { key: "old".to_string(), number: 42 });
+
+		let _ = sender.send("JSONL prefix_key = \"new\"".to_string()).await;
+		filler.try_update(&mut value, Some("prefix")).await?;
+		assert_eq!(value, TestStructInner { key: "new".to_string(), number: 42 });
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_jsonl_from_env() -> Result<(), anyhow::Error> {
+		std::env::set_var("JSONL_ENV_TEST_KEY", "value");
+		let result = TestStructInner::try_from_env(Some("jsonl_env_test"));
+		match result {
+			Err(JsonlError::MissingEnvVars(missing)) => {
+				assert_eq!(missing, vec!["JSONL_ENV_TEST_NUMBER".to_string()]);
+			}
+			other => panic!("expected missing env vars, got {:?}", other),
+		}
+
+		// The unprefixed variable is not a fallback for the prefixed one
+		std::env::set_var("NUMBER", "7");
+		let result = TestStructInner::try_from_env(Some("jsonl_env_test"));
+		assert!(matches!(result, Err(JsonlError::MissingEnvVars(_))));
+
+		std::env::set_var("JSONL_ENV_TEST_NUMBER", "42");
+		let result = TestStructInner::try_from_env(Some("jsonl_env_test"))?;
+		assert_eq!(result, TestStructInner { key: "value".to_string(), number: 42 });
+
+		Ok(())
+	}
 }
@@ -45,6 +45,7 @@ where
 	Post: PostBuildHook,
 {
 	images: HashSet<String>,
+	optional: bool,
 	pre_build_hooks: Vec<Pre>,
 	post_build_hooks: Vec<Post>,
 }
@@ -55,7 +56,12 @@ where
 	Post: PostBuildHook,
 {
 	pub fn new() -> Self {
-		Self { images: HashSet::new(), pre_build_hooks: Vec::new(), post_build_hooks: Vec::new() }
+		Self {
+			images: HashSet::new(),
+			optional: false,
+			pre_build_hooks: Vec::new(),
+			post_build_hooks: Vec::new(),
+		}
 	}
 
 	/// Add an image to be pulled
@@ -64,6 +70,15 @@ where
 		self
 	}
 
+	/// Marks the images as optional
+	///
+	/// When optional and the Docker daemon can't be contacted, the build emits a
+	/// `cargo:warning` and skips pulling images instead of failing.
+	pub fn optional(&mut self, optional: bool) -> &mut Self {
+		self.optional = optional;
+		self
+	}
+
 	/// Add a pre-build hook
 	pub fn before(&mut self, hook: Pre) {
 		self.pre_build_hooks.push(hook);
@@ -81,9 +96,47 @@ where
 			hook.before().map_err(|e| BuildtimeError::Internal(e.into()))?;
 		}
 
-		let docker = Docker::connect_with_local_defaults()
-			.map_err(|e| BuildtimeError::Internal(e.into()))?;
+		let docker = Docker::connect_with_local_defaults();
+		if let Some(docker) = self.reachable(docker).await? {
+			self.pull_images(&docker).await?;
+		}
+
+		// Run post-build hooks
+		for hook in &self.post_build_hooks {
+			hook.after().map_err(|e| BuildtimeError::Internal(e.into()))?;
+		}
+
+		Ok(())
+	}
+
+	/// Decides whether images can be pulled with the given connection
+	///
+	/// Strict builds fail only when the connection can't be created. Optional builds also
+	/// ping the daemon, and skip the images with a `cargo:warning` when it can't be reached.
+	async fn reachable(
+		&self,
+		docker: Result<Docker, bollard::errors::Error>,
+	) -> Result<Option<Docker>, BuildtimeError> {
+		if !self.optional {
+			return docker.map(Some).map_err(|e| BuildtimeError::Internal(e.into()));
+		}
+
+		let reached = match docker {
+			Ok(docker) => docker.ping().await.map(|_| docker),
+			Err(e) => Err(e),
+		};
+
+		match reached {
+			Ok(docker) => Ok(Some(docker)),
+			Err(e) => {
+				println!("cargo:warning=Docker unavailable, skipping optional images: {}", e);
+				Ok(None)
+			}
+		}
+	}
 
+	/// Pulls the images which are not already available
+	async fn pull_images(&self, docker: &Docker) -> Result<(), BuildtimeError> {
 		for image in &self.images {
 			// Check if image already exists
 			let mut filters = HashMap::new();
@@ -115,10 +168,44 @@ where
 			}
 		}
 
-		// Run post-build hooks
-		for hook in &self.post_build_hooks {
-			hook.after().map_err(|e| BuildtimeError::Internal(e.into()))?;
-		}
+		Ok(())
+	}
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+	use super::*;
+
+	/// A connection to a socket with no daemon behind it
+	fn unreachable() -> Result<Docker, bollard::errors::Error> {
+		Docker::connect_with_unix("/nonexistent/docker.sock", 1, bollard::API_DEFAULT_VERSION)
+	}
+
+	/// A connection which could not be created
+	fn unconnectable() -> Result<Docker, bollard::errors::Error> {
+		Err(bollard::errors::Error::DockerResponseServerError {
+			status_code: 500,
+			message: "no daemon".to_string(),
+		})
+	}
+
+	#[tokio::test]
+	async fn test_strict_does_not_ping() -> Result<(), anyhow::Error> {
+		let buildtime = Buildtime::<Noop, Noop>::new();
+
+		assert!(buildtime.reachable(unreachable()).await?.is_some());
+		assert!(buildtime.reachable(unconnectable()).await.is_err());
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_optional_skips_unreachable() -> Result<(), anyhow::Error> {
+		let mut buildtime = Buildtime::<Noop, Noop>::new();
+		buildtime.optional(true);
+
+		assert!(buildtime.reachable(unreachable()).await?.is_none());
+		assert!(buildtime.reachable(unconnectable()).await?.is_none());
 
 		Ok(())
 	}
@@ -14,6 +14,41 @@ pub enum CargoVendorPlanError {
 	NoGitUrl(String),
 	#[error("Cargo Vendor Plan: Git dependency '{0}' has no revision")]
 	NoGitRevision(String),
+	#[error("Cargo Vendor Plan: Invalid revision override '{1}' in {0}")]
+	InvalidRevisionOverride(String, String),
+}
+
+/// Gets the name of the environment variable overriding the revision of a dependency.
+///
+/// e.g. `my-dep` becomes `KESTREL_VENDOR_MY_DEP_REV`.
+pub fn rev_override_var(dep_name: &str) -> String {
+	let name = dep_name
+		.chars()
+		.map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+		.collect::<String>();
+	format!("KESTREL_VENDOR_{}_REV", name)
+}
+
+/// Gets the revision override for a dependency from the environment, if any.
+///
+/// The override must look like a git revision: a non-empty commit hash, tag, or branch name.
+pub fn rev_override(dep_name: &str) -> Result<Option<String>, CargoVendorPlanError> {
+	let var = rev_override_var(dep_name);
+	let rev = match std::env::var(&var) {
+		Ok(rev) => rev.trim().to_string(),
+		Err(_) => return Ok(None),
+	};
+
+	let plausible = !rev.is_empty()
+		&& !rev.starts_with('-')
+		&& rev
+			.chars()
+			.all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '/'));
+	if !plausible {
+		return Err(CargoVendorPlanError::InvalidRevisionOverride(var, rev));
+	}
+
+	Ok(Some(rev))
 }
 
 impl VendorPlan {
@@ -21,6 +56,9 @@ impl VendorPlan {
 	/// The dependency must be a git dependency with a URL and revision.
 	///
 	/// NOTE: dependency must be in the crate.
+	///
+	/// The revision can be overridden with the `KESTREL_VENDOR_{NAME}_REV` environment variable
+	/// (see [rev_override_var]), which takes precedence over the revision in cargo metadata.
 	pub fn try_from_cargo_dep(
 		dep_name: impl AsRef<str>,
 		strategy: VendorStrategy,
@@ -77,6 +115,9 @@ impl VendorPlan {
 			return Err(CargoVendorPlanError::NoGitUrl(dep_name.to_string()));
 		}
 
+		// The environment takes precedence over cargo metadata
+		let git_rev = rev_override(dep_name)?.unwrap_or(git_rev);
+
 		Ok(VendorPlan::new(dep_name.to_string(), git_rev, git_url, strategy))
 	}
 }
@@ -93,4 +134,20 @@ mod tests {
 		assert_eq!(plan.git_rev, "070d5bcd1b248673d89faddae3a19f7894ab357e");
 		Ok(())
 	}
+
+	#[test]
+	fn test_rev_override() -> Result<(), anyhow::Error> {
+		let dep_name = "kestrel-rev-override-test";
+		assert_eq!(rev_override_var(dep_name), "KESTREL_VENDOR_KESTREL_REV_OVERRIDE_TEST_REV");
+		assert_eq!(rev_override(dep_name)?, None);
+
+		std::env::set_var(rev_override_var(dep_name), "070d5bcd");
+		assert_eq!(rev_override(dep_name)?, Some("070d5bcd".to_string()));
+
+		std::env::set_var(rev_override_var(dep_name), "not a rev");
+		assert!(rev_override(dep_name).is_err());
+
+		std::env::remove_var(rev_override_var(dep_name));
+		Ok(())
+	}
 }
@@ -1,6 +1,8 @@
 pub mod cargo;
 
 use anyhow::Context;
+use include_dir::WorkspacePath;
+use std::collections::HashMap;
 
 /// Error type for buildtime operations.
 #[derive(Debug, thiserror::Error)]
@@ -24,6 +26,16 @@ pub fn vendors_path() -> Result<std::path::PathBuf, VendorUtilError> {
 	Ok(workspace_root.join(".vendor"))
 }
 
+/// Gets a new [WorkspacePath] at `{workspace_root}/target/{subdir}/{uid}`
+///
+/// Workspaces under `target/` are gitignored and removed by `cargo clean`, which makes this a
+/// better home for throwaway workspaces than `.debug`.
+pub fn target_workspace_path(subdir: &str) -> Result<WorkspacePath, VendorUtilError> {
+	let workspace_root = workspace_root()?;
+	let uid = uuid::Uuid::new_v4();
+	Ok(WorkspacePath::PathBuf(workspace_root.join("target").join(subdir).join(uid.to_string())))
+}
+
 /// Gets the path to a given vendor directory based on the strategy
 pub fn vendor_path(
 	vendor_name: impl AsRef<str>,
@@ -79,6 +91,8 @@ pub struct VendorPlan {
 	pub git_url: String,
 	/// The strategy to use when vendoring.
 	pub strategy: VendorStrategy,
+	/// Arbitrary metadata for downstream build logic, e.g. where to extract the vendor.
+	pub metadata: HashMap<String, String>,
 }
 
 impl VendorPlan {
@@ -89,7 +103,7 @@ impl VendorPlan {
 		git_url: String,
 		strategy: VendorStrategy,
 	) -> Self {
-		Self { vendor_name, git_rev, git_url, strategy }
+		Self { vendor_name, git_rev, git_url, strategy, metadata: HashMap::new() }
 	}
 
 	/// Creates a new [VendorPlan] with the default DotVendor strategy
@@ -112,6 +126,17 @@ impl VendorPlan {
 		self.strategy = strategy;
 	}
 
+	/// Attaches a metadata entry to the vendor plan
+	pub fn with_metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+		self.metadata.insert(key.into(), value.into());
+		self
+	}
+
+	/// Gets a metadata entry of the vendor plan
+	pub fn get_metadata(&self, key: &str) -> Option<&str> {
+		self.metadata.get(key).map(String::as_str)
+	}
+
 	/// Execute the vendor plan, cloning or updating the repository as needed.
 	/// Returns a Vendor instance if successful.
 	pub fn execute(&self) -> Result<Vendor, VendorPlanError> {
@@ -213,10 +238,47 @@ pub struct Vendor {
 	pub path: std::path::PathBuf,
 }
 
+impl Vendor {
+	/// Gets a metadata entry attached to the vendor's plan
+	pub fn get_metadata(&self, key: &str) -> Option<&str> {
+		self.plan.get_metadata(key)
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
 
+	#[test]
+	fn test_vendor_plan_metadata() {
+		let plan = VendorPlan::new_dot_vendor(
+			"qip".to_string(),
+			"070d5bcd1b248673d89faddae3a19f7894ab357e".to_string(),
+			"https://github.com/Renmusxd/RustQIP.git".to_string(),
+		)
+		.with_metadata("feature", "quantum")
+		.with_metadata("extract", "contracts");
+
+		assert_eq!(plan.get_metadata("feature"), Some("quantum"));
+		assert_eq!(plan.get_metadata("extract"), Some("contracts"));
+		assert_eq!(plan.get_metadata("missing"), None);
+
+		let vendor = Vendor { plan, path: std::path::PathBuf::from(".vendor/qip") };
+		assert_eq!(vendor.get_metadata("feature"), Some("quantum"));
+	}
+
+	#[test]
+	fn test_target_workspace_path() -> Result<(), anyhow::Error> {
+		let first = target_workspace_path("debug")?;
+		let second = target_workspace_path("debug")?;
+
+		let target_debug = workspace_root()?.join("target").join("debug");
+		assert_eq!(first.get_path().parent(), Some(target_debug.as_path()));
+		assert_ne!(first.get_path(), second.get_path());
+
+		Ok(())
+	}
+
 	#[test]
 	fn test_vendors_dot_vendor() -> Result<(), anyhow::Error> {
 		// remove the .vendor directory if it exists